@@ -1,12 +1,20 @@
-use crate::{function::Function, lexer::Span, tipo::Tipo, value::Value};
+use crate::{
+    lexer::{Span, Spanned},
+    tipo::Tipo,
+    value::Value,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     // Literals
     Int {
         value: String,
         location: Span,
     },
+    Float {
+        value: String,
+        location: Span,
+    },
     Str {
         value: String,
         location: Span,
@@ -25,6 +33,39 @@ pub enum Expr {
         args: Vec<Expr>,
         location: Span,
     },
+    /// A tuple literal, `(1, "one")`. Unit is the empty tuple.
+    Tuple {
+        elements: Vec<Expr>,
+        location: Span,
+    },
+    /// A list literal, `[a, b, c]`.
+    List {
+        elements: Vec<Expr>,
+        location: Span,
+    },
+    /// `list[index]`
+    Index {
+        list: Box<Expr>,
+        index: Box<Expr>,
+        location: Span,
+    },
+    /// Builds a struct, `Point { x: 1, y: 2 }`.
+    Construct {
+        name: String,
+        fields: Vec<(String, Expr)>,
+        location: Span,
+    },
+    /// Reads a struct's field, `point.x`.
+    Field {
+        expr: Box<Expr>,
+        field: String,
+        location: Span,
+    },
+    /// Unwraps an `option` or `result`, returning `None` or `Err` from the function, `parse(s)?`.
+    Try {
+        expr: Box<Expr>,
+        location: Span,
+    },
     Value {
         value: Value,
         location: Span,
@@ -46,11 +87,28 @@ pub enum Expr {
     },
     Let {
         name: String,
-        let_tipo: Option<Tipo>,
+        /// Declared with `let mut`, only mutable variables can be assigned to.
+        mutable: bool,
+        let_tipo: Option<Spanned<Tipo>>,
+        initializer: Box<Expr>,
+        then: Box<Expr>,
+        location: Span,
+    },
+    /// Binds each element of a tuple to a variable, `let (q, r) = divmod(7, 2); q`.
+    LetTuple {
+        names: Vec<String>,
+        mutable: bool,
+        let_tipo: Option<Spanned<Tipo>>,
         initializer: Box<Expr>,
         then: Box<Expr>,
         location: Span,
     },
+    /// Assigns to a `let mut` variable, evaluates to unit.
+    Assign {
+        name: String,
+        value: Box<Expr>,
+        location: Span,
+    },
     Block {
         expr: Box<Expr>,
         location: Span,
@@ -61,20 +119,153 @@ pub enum Expr {
         falsy_branch: Box<Expr>,
         location: Span,
     },
+    /// Runs the body until the condition is false or it hits a `break`, evaluates to unit.
+    While {
+        condition: Box<Expr>,
+        body: Box<Expr>,
+        location: Span,
+    },
+    Break(Span),
+    Continue(Span),
+    /// Evaluates `first` for it's side effects then evaluates to `then`, written `first; then`.
+    Sequence {
+        first: Box<Expr>,
+        then: Box<Expr>,
+        location: Span,
+    },
     Fn {
-        params: Vec<(String, Tipo)>,
-        return_tipo: Tipo,
+        /// Explicit generic type parameters, e.g. `A` in `fn<A>(x: A) -> A { x }`.
+        type_params: Vec<String>,
+        params: Vec<(String, Option<Spanned<Tipo>>)>,
+        return_tipo: Option<Spanned<Tipo>>,
         body: Box<Expr>,
         location: Span,
     },
     Funk {
+        /// The `///` comments above the funk, a line for each comment.
+        doc: Option<String>,
         name: String,
-        params: Vec<(String, Tipo)>,
-        return_tipo: Tipo,
+        type_params: Vec<String>,
+        params: Vec<(String, Option<Spanned<Tipo>>)>,
+        return_tipo: Option<Spanned<Tipo>>,
         body: Box<Expr>,
         then: Box<Expr>,
         location: Span,
     },
+    /// Declares a struct for the rest of the expression, `struct Point { x: int, y: int }`.
+    Struct {
+        name: String,
+        fields: Vec<(String, Tipo)>,
+        then: Box<Expr>,
+        location: Span,
+    },
+    /// Declares an enum for the rest of the expression, `enum Shape { Circle(int), Rect(int, int) }`.
+    /// Each variant is a constructor with the given field types.
+    Enum {
+        name: String,
+        type_params: Vec<String>,
+        variants: Vec<(String, Vec<Tipo>)>,
+        then: Box<Expr>,
+        location: Span,
+    },
+    /// Evaluates the body of the first arm whose pattern matches the scrutinee and whose guard holds.
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+        location: Span,
+    },
+}
+
+/// `pattern if guard => body`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+    pub location: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_` matches anything.
+    Wildcard(Span),
+    /// A name matches anything and binds it, unless it's a constructor without fields.
+    Binding { name: String, location: Span },
+    /// An int, string or bool literal, matched by equality.
+    Literal(Expr),
+    /// `Circle(r)`, matches a variant and it's fields.
+    Constructor {
+        name: String,
+        args: Vec<Pattern>,
+        location: Span,
+    },
+}
+
+impl Pattern {
+    pub fn location(&self) -> Span {
+        match self {
+            Pattern::Wildcard(location)
+            | Pattern::Binding { location, .. }
+            | Pattern::Constructor { location, .. } => location.clone(),
+            Pattern::Literal(expr) => expr.location(),
+        }
+    }
+}
+
+/// A whole source file, made of top-level declarations.
+/// Every item is an `Expr::Funk`, `Expr::Struct` or `Expr::Enum` whose `then` is unit,
+/// the entry point is the funk named `main`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub items: Vec<Expr>,
+    pub location: Span,
+}
+
+/// A line of REPL input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplEntry {
+    /// A `let`, `funk`, `struct` or `enum` without a following expression,
+    /// it stays in scope for later entries. Like a `Program` item it's `then` is unit.
+    Definition(Expr),
+    Expr(Expr),
+}
+
+impl Expr {
+    /// Returns the span of source code the expression was parsed from.
+    pub fn location(&self) -> Span {
+        use Expr::*;
+        match self {
+            Unit(location) | Break(location) | Continue(location) => location.clone(),
+            Int { location, .. }
+            | Float { location, .. }
+            | Str { location, .. }
+            | Bool { location, .. }
+            | Identifier { location, .. }
+            | Call { location, .. }
+            | List { location, .. }
+            | Index { location, .. }
+            | Tuple { location, .. }
+            | LetTuple { location, .. }
+            | Construct { location, .. }
+            | Field { location, .. }
+            | Try { location, .. }
+            | Value { location, .. }
+            | Grouping { location, .. }
+            | Unary { location, .. }
+            | Binary { location, .. }
+            | Let { location, .. }
+            | Assign { location, .. }
+            | Block { location, .. }
+            | If { location, .. }
+            | While { location, .. }
+            | Sequence { location, .. }
+            | Fn { location, .. }
+            | Funk { location, .. }
+            | Struct { location, .. }
+            | Enum { location, .. }
+            | Match { location, .. } => location.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -83,6 +274,14 @@ pub enum Op {
     Minus,
     Divide,
     Multiply,
+    Modulo,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    BitNot,
     EqualEqual,
     NotEqual,
     Less,
@@ -102,6 +301,14 @@ impl std::fmt::Display for Op {
             Minus => write!(f, "-"),
             Multiply => write!(f, "*"),
             Divide => write!(f, "/"),
+            Modulo => write!(f, "%"),
+            Power => write!(f, "**"),
+            BitAnd => write!(f, "&"),
+            BitOr => write!(f, "|"),
+            BitXor => write!(f, "^"),
+            ShiftLeft => write!(f, "<<"),
+            ShiftRight => write!(f, ">>"),
+            BitNot => write!(f, "~"),
             EqualEqual => write!(f, "=="),
             NotEqual => write!(f, "!="),
             Less => write!(f, "<"),
@@ -115,4 +322,4 @@ impl std::fmt::Display for Op {
     }
 }
 // EOF
-// Ehh
\ No newline at end of file
+// Ehh
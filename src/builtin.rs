@@ -0,0 +1,117 @@
+//! Functions and enums built into the language.
+//! Builtin functions aren't values, a call to one is compiled straight to it's `OpCode`.
+use crate::tipo::Tipo;
+
+/// An enum every script can use without declaring it.
+pub struct BuiltinEnum {
+    pub name: &'static str,
+    pub type_params: Vec<String>,
+    pub variants: Vec<(String, Vec<Tipo>)>,
+}
+
+/// `option<T>` and `result<T, E>`, the first variant of each holds the value `?` unwraps.
+pub fn enums() -> Vec<BuiltinEnum> {
+    let t = || Tipo::new("T");
+
+    vec![
+        BuiltinEnum {
+            name: "option",
+            type_params: vec!["T".to_string()],
+            variants: vec![
+                ("Some".to_string(), vec![t()]),
+                ("None".to_string(), Vec::new()),
+            ],
+        },
+        BuiltinEnum {
+            name: "result",
+            type_params: vec!["T".to_string(), "E".to_string()],
+            variants: vec![
+                ("Ok".to_string(), vec![t()]),
+                ("Err".to_string(), vec![Tipo::new("E")]),
+            ],
+        },
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `len(list)` returns the number of elements in the list.
+    Len,
+    /// `push(list, value)` returns the list with the value added to the end.
+    Push,
+    /// `map(list, f)` returns a list of `f` applied to every element.
+    Map,
+    /// `filter(list, f)` returns a list of the elements `f` returns true for.
+    Filter,
+    /// `fold(list, initial, f)` combines the elements from first to last, starting with `initial`.
+    Fold,
+    /// `float(int)` converts an int to the nearest float.
+    ToFloat,
+    /// `int(float)` converts a float to an int, dropping the fraction.
+    ToInt,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "len" => Some(Builtin::Len),
+            "push" => Some(Builtin::Push),
+            "map" => Some(Builtin::Map),
+            "filter" => Some(Builtin::Filter),
+            "fold" => Some(Builtin::Fold),
+            "float" => Some(Builtin::ToFloat),
+            "int" => Some(Builtin::ToInt),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Len => "len",
+            Builtin::Push => "push",
+            Builtin::Map => "map",
+            Builtin::Filter => "filter",
+            Builtin::Fold => "fold",
+            Builtin::ToFloat => "float",
+            Builtin::ToInt => "int",
+        }
+    }
+
+    /// The number of arguments the builtin takes.
+    pub fn arity(&self) -> usize {
+        match self {
+            Builtin::Len | Builtin::ToFloat | Builtin::ToInt => 1,
+            Builtin::Push | Builtin::Map | Builtin::Filter => 2,
+            Builtin::Fold => 3,
+        }
+    }
+
+    /// Returns the builtin's function type, `fresh` makes the type variables it's generic over.
+    pub fn tipo(&self, mut fresh: impl FnMut() -> Tipo) -> Tipo {
+        let a = fresh();
+
+        match self {
+            Builtin::Len => Tipo::new_fn(vec![Tipo::list_type(a)], Tipo::int_type()),
+            Builtin::Push => Tipo::new_fn(
+                vec![Tipo::list_type(a.clone()), a.clone()],
+                Tipo::list_type(a),
+            ),
+            Builtin::Map => {
+                let b = fresh();
+                let f = Tipo::new_fn(vec![a.clone()], b.clone());
+                Tipo::new_fn(vec![Tipo::list_type(a), f], Tipo::list_type(b))
+            }
+            Builtin::Filter => {
+                let f = Tipo::new_fn(vec![a.clone()], Tipo::bool_type());
+                Tipo::new_fn(vec![Tipo::list_type(a.clone()), f], Tipo::list_type(a))
+            }
+            Builtin::Fold => {
+                let b = fresh();
+                let f = Tipo::new_fn(vec![b.clone(), a.clone()], b.clone());
+                Tipo::new_fn(vec![Tipo::list_type(a), b.clone(), f], b)
+            }
+            Builtin::ToFloat => Tipo::new_fn(vec![Tipo::int_type()], Tipo::float_type()),
+            Builtin::ToInt => Tipo::new_fn(vec![Tipo::float_type()], Tipo::int_type()),
+        }
+    }
+}
@@ -1,62 +1,133 @@
-use chumsky::primitive::todo;
+use std::{collections::HashMap, rc::Rc};
 
 use crate::{
-    ast::{Expr, Op},
-    lexer::Span,
-    value::Value,
+    ast::{Expr, MatchArm, Op, Pattern, Program},
+    builtin::{self, Builtin},
+    diagnostic::{Diagnostic, ToDiagnostic},
+    function::{Capture, Function},
+    lexer::{Span, Spanned},
+    tipo::Tipo,
+    value::{Layout, Value},
     vm::{chunk::Chunk, opcode::OpCode},
 };
 
+#[derive(Clone)]
 pub struct Compiler {
     locals: Vec<Local>,
     scope_depth: usize,
+    /// The number of values on the stack in the current function's frame.
+    stack_depth: usize,
+    /// The variables captured from enclosing functions by the function being compiled.
+    captures: Vec<Capture>,
+    /// The loops around the expression being compiled, innermost last.
+    loops: Vec<Loop>,
+    /// The state of the functions enclosing the one being compiled.
+    enclosing: Vec<FunctionState>,
+    /// The layouts of the structs declared so far.
+    structs: HashMap<String, Rc<Layout>>,
+    /// The tag and number of fields of the constructors declared so far.
+    constructors: HashMap<String, (u8, usize)>,
+    /// The position of the field read by each field expression, found by the type checker.
+    field_indices: HashMap<Span, usize>,
 }
 
 /// What's the plan for locals?
 /// First for Let expression create a new local with the scope depth
 /// When a Block is encountered call 'self.begin_scope()' to increment the 'scope_depth'
 /// When the block is done compiling we call `self.end_scope()`
+/// Temporaries can sit under a local on the stack so each local remembers it's `slot`.
 #[derive(Debug, Clone)]
 pub struct Local {
     name: String,
     depth: usize,
+    slot: usize,
+}
+
+/// Where a variable is found by `resolve_variable`.
+enum Variable {
+    /// The stack slot of a local in the current function.
+    Local(u8),
+    /// The index of an upvalue of the current closure.
+    Upvalue(u8),
+}
+
+/// A loop being compiled, `break` and `continue` pop the stack back to `stack_depth`.
+#[derive(Debug, Clone)]
+struct Loop {
+    /// The index of the loop's condition, where `continue` jumps back to.
+    start: usize,
+    stack_depth: usize,
+    /// The operands of the `break` jumps to patch once the end of the loop is known.
+    breaks: Vec<usize>,
+}
+
+/// The compiler state of a function whose body is interrupted by a nested function.
+#[derive(Clone)]
+struct FunctionState {
+    locals: Vec<Local>,
+    scope_depth: usize,
+    stack_depth: usize,
+    captures: Vec<Capture>,
+    loops: Vec<Loop>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
     pub fn new() -> Compiler {
-        Compiler {
+        let mut compiler = Compiler {
             locals: Vec::new(),
             scope_depth: 0,
+            stack_depth: 0,
+            captures: Vec::new(),
+            loops: Vec::new(),
+            enclosing: Vec::new(),
+            structs: HashMap::new(),
+            constructors: HashMap::new(),
+            field_indices: HashMap::new(),
+        };
+
+        for builtin in builtin::enums() {
+            compiler
+                .declare_enum(&builtin.variants, 0..0)
+                .expect("The builtin enums are small.");
         }
+
+        compiler
     }
+
+    /// Uses the field positions from `TypeChecker::take_field_indices` for the next compile.
+    pub fn set_field_indices(&mut self, field_indices: HashMap<Span, usize>) {
+        self.field_indices = field_indices;
+    }
+
     pub fn compile(&mut self, chunky: &mut Chunk, expr: &Expr) -> CompilerResult<()> {
         match expr {
             Expr::Unit(location) => {
-                chunky.write_opcode(OpCode::Unit, &[], location.clone());
+                self.emit(chunky, OpCode::Unit, &[], location.clone());
                 Ok(())
             }
             Expr::Int { value, location } => {
-                let raw_int = value.parse::<i64>().unwrap();
-                chunky.add_constant(Value::Int(raw_int));
-
-                let index: u8 = (chunky.constants.len() - 1) as u8;
-                chunky.write_opcode(OpCode::GetConstant, &[index], location.clone());
-                Ok(())
+                let raw_int = value
+                    .parse::<i64>()
+                    .expect("The lexer rejects ints that don't fit in an i64.");
+                self.emit_constant(chunky, Value::Int(raw_int), location.clone())
+            }
+            Expr::Float { value, location } => {
+                let raw_float = value.parse::<f64>().unwrap();
+                self.emit_constant(chunky, Value::Float(raw_float), location.clone())
             }
             Expr::Str { value, location } => {
-                chunky.add_constant(Value::Str(Box::new(value.to_string())));
-                let index: u8 = (chunky.constants.len() - 1) as u8;
-
-                chunky.write_opcode(OpCode::GetConstant, &[index], location.clone());
-                Ok(())
+                let string = Value::Str(Box::new(value.to_string()));
+                self.emit_constant(chunky, string, location.clone())
             }
             Expr::Bool { value, location } => {
                 let raw_bool: bool = value.parse().unwrap();
-                chunky.add_constant(Value::Bool(raw_bool));
-                let index: u8 = (chunky.constants.len() - 1) as u8;
-
-                chunky.write_opcode(OpCode::GetConstant, &[index], location.clone());
-                Ok(())
+                self.emit_constant(chunky, Value::Bool(raw_bool), location.clone())
             }
             Expr::Unary { op, rhs, location } => {
                 self.compiler_unary(chunky, location.clone(), *op, rhs)
@@ -70,13 +141,25 @@ impl Compiler {
             Expr::Grouping { expr, location: _ } => self.compile(chunky, expr),
             Expr::Let {
                 name,
-                let_tipo: _,
                 initializer,
                 then,
                 location,
+                ..
             } => self.compile_let(chunky, name, initializer, then, location.clone()),
+            Expr::LetTuple {
+                names,
+                initializer,
+                then,
+                location,
+                ..
+            } => self.compile_let_tuple(chunky, names, initializer, then, location.clone()),
+            Expr::Assign {
+                name,
+                value,
+                location,
+            } => self.compile_assign(chunky, name, value, location.clone()),
             Expr::Identifier { value, location } => {
-                self.compile_identifier(chunky, &value, location.clone())
+                self.compile_identifier(chunky, value, location.clone())
             }
             Expr::Block { expr, location: _ } => self.compile_block(chunky, expr),
             Expr::If {
@@ -91,10 +174,759 @@ impl Compiler {
                 falsy_branch,
                 location.clone(),
             ),
-            _ => todo!(),
+            Expr::While {
+                condition,
+                body,
+                location,
+            } => self.compile_while(chunky, condition, body, location.clone()),
+            Expr::Break(location) => self.compile_break(chunky, location.clone()),
+            Expr::Continue(location) => self.compile_continue(chunky, location.clone()),
+            Expr::Sequence {
+                first,
+                then,
+                location,
+            } => {
+                self.compile(chunky, first)?;
+                self.emit(chunky, OpCode::Pop, &[], location.clone());
+                self.compile(chunky, then)
+            }
+            Expr::Value { value, location } => self.compile_value(chunky, value, location.clone()),
+            Expr::Fn {
+                params,
+                body,
+                location,
+                ..
+            } => self.compile_function(chunky, "", params, body, location.clone()),
+            Expr::Funk {
+                name,
+                params,
+                body,
+                then,
+                location,
+                ..
+            } => {
+                // The funk is a local for the rest of the expression, like a `let`.
+                self.compile_function(chunky, name, params, body, location.clone())?;
+                self.declare_local(name)?;
+                self.compile(chunky, then)?;
+                self.pop_local(chunky, location.clone());
+                Ok(())
+            }
+            Expr::Call {
+                callee,
+                args,
+                location,
+            } => self.compile_call(chunky, callee, args, location.clone()),
+            Expr::List { elements, location } => {
+                self.compile_list(chunky, elements, location.clone())
+            }
+            Expr::Tuple { elements, location } => {
+                if elements.len() > u8::MAX as usize {
+                    return Err(CompilerErr::TooManyElements {
+                        location: location.clone(),
+                    });
+                }
+
+                for element in elements {
+                    self.compile(chunky, element)?;
+                }
+                self.emit(
+                    chunky,
+                    OpCode::BuildTuple,
+                    &[elements.len() as u8],
+                    location.clone(),
+                );
+                Ok(())
+            }
+            Expr::Index {
+                list,
+                index,
+                location,
+            } => {
+                self.compile(chunky, list)?;
+                self.compile(chunky, index)?;
+                self.emit(chunky, OpCode::Index, &[], location.clone());
+                Ok(())
+            }
+            Expr::Struct {
+                name,
+                fields,
+                then,
+                location,
+            } => {
+                // Structs only exist at compile time, the declaration evaluates to `then`.
+                self.declare_struct(name, fields, location.clone())?;
+                self.compile(chunky, then)
+            }
+            Expr::Construct {
+                name,
+                fields,
+                location,
+            } => self.compile_construct(chunky, name, fields, location.clone()),
+            Expr::Field {
+                expr,
+                field,
+                location,
+            } => self.compile_field(chunky, expr, field, location.clone()),
+            Expr::Try { expr, location } => {
+                self.compile(chunky, expr)?;
+                self.emit(chunky, OpCode::Try, &[], location.clone());
+                Ok(())
+            }
+            Expr::Enum {
+                variants,
+                then,
+                location,
+                ..
+            } => {
+                // Like structs, only the constructors are remembered.
+                self.declare_enum(variants, location.clone())?;
+                self.compile(chunky, then)
+            }
+            Expr::Match {
+                scrutinee,
+                arms,
+                location,
+            } => self.compile_match(chunky, scrutinee, arms, location.clone()),
+        }
+    }
+
+    /// Compiles an expression followed by a `Return`, the script evaluates to the expression.
+    /// The value is left on top of the VM's stack for the caller to pop.
+    pub fn compile_script(&mut self, chunky: &mut Chunk, expr: &Expr) -> CompilerResult<()> {
+        self.compile(chunky, expr)?;
+
+        let end = expr.location().end..expr.location().end;
+        self.emit(chunky, OpCode::Return, &[], end);
+        Ok(())
+    }
+
+    /// Compiles a `let`, `funk`, `struct` or `enum` without a following expression, used for REPL definitions.
+    /// The value stays on the stack as a local of the script so later chunks can use it,
+    /// the script returns it without popping it. Structs and enums don't have a value, they're only remembered.
+    pub fn compile_definition(
+        &mut self,
+        chunky: &mut Chunk,
+        definition: &Expr,
+    ) -> CompilerResult<()> {
+        let (name, location) = match definition {
+            Expr::Struct {
+                name,
+                fields,
+                location,
+                ..
+            } => {
+                self.declare_struct(name, fields, location.clone())?;
+                chunky.write_opcode(OpCode::Return, &[], location.end..location.end);
+                return Ok(());
+            }
+            Expr::Enum {
+                variants, location, ..
+            } => {
+                self.declare_enum(variants, location.clone())?;
+                chunky.write_opcode(OpCode::Return, &[], location.end..location.end);
+                return Ok(());
+            }
+            Expr::Let {
+                name,
+                initializer,
+                location,
+                ..
+            } => {
+                self.compile(chunky, initializer)?;
+                (name, location)
+            }
+            Expr::Funk {
+                name,
+                params,
+                body,
+                location,
+                ..
+            } => {
+                self.compile_function(chunky, name, params, body, location.clone())?;
+                (name, location)
+            }
+            expr => {
+                return Err(CompilerErr::NotADefinition {
+                    location: expr.location(),
+                })
+            }
+        };
+
+        self.declare_local(name)?;
+        chunky.write_opcode(OpCode::Return, &[], location.end..location.end);
+        Ok(())
+    }
+
+    /// Compiles a program, every top-level funk is stored in it's own local slot then `main` is called.
+    pub fn compile_program(&mut self, chunky: &mut Chunk, program: &Program) -> CompilerResult<()> {
+        for item in &program.items {
+            if let Expr::Struct {
+                name,
+                fields,
+                location,
+                ..
+            } = item
+            {
+                self.declare_struct(name, fields, location.clone())?;
+            }
+
+            if let Expr::Enum {
+                variants, location, ..
+            } = item
+            {
+                self.declare_enum(variants, location.clone())?;
+            }
+        }
+
+        // Reserve a slot for every funk first so they can refer to each other in any order.
+        let mut slots = Vec::new();
+        let mut names = Vec::new();
+        for item in &program.items {
+            if let Expr::Funk { name, location, .. } = item {
+                if names.contains(&name) {
+                    return Err(CompilerErr::DuplicateDefinition {
+                        name: name.clone(),
+                        location: location.clone(),
+                    });
+                }
+                names.push(name);
+
+                self.emit(chunky, OpCode::Unit, &[], location.clone());
+                slots.push(self.declare_local(name)?);
+            }
+        }
+
+        let funks = program.items.iter().filter_map(|item| match item {
+            Expr::Funk {
+                name,
+                params,
+                body,
+                location,
+                ..
+            } => Some((name, params, body, location)),
+            _ => None,
+        });
+
+        for ((name, params, body, location), slot) in funks.zip(slots) {
+            self.compile_function(chunky, name, params, body, location.clone())?;
+            self.emit(chunky, OpCode::SetLocal, &[slot], location.clone());
+        }
+
+        let end = program.location.end..program.location.end;
+        let call_main = Expr::Call {
+            callee: Box::new(Expr::Identifier {
+                value: "main".to_string(),
+                location: end.clone(),
+            }),
+            args: Vec::new(),
+            location: end.clone(),
+        };
+        self.compile(chunky, &call_main)?;
+        self.emit(chunky, OpCode::Return, &[], end);
+
+        Ok(())
+    }
+
+    /// Compiles a function body into it's own chunk and pushes a closure of the resulting function.
+    /// Slot zero of the function's frame holds the function itself so a funk can call itself by name.
+    fn compile_function(
+        &mut self,
+        chunky: &mut Chunk,
+        name: &str,
+        params: &[(String, Option<Spanned<Tipo>>)],
+        body: &Expr,
+        location: Span,
+    ) -> CompilerResult<()> {
+        if params.len() > u8::MAX as usize {
+            return Err(CompilerErr::TooManyParams { location });
+        }
+
+        let enclosing = self.save_state();
+        self.enclosing.push(enclosing);
+        self.scope_depth = 0;
+        self.stack_depth = 0;
+
+        let mut function_chunk = Chunk::new();
+        let result = self.compile_function_body(&mut function_chunk, name, params, body);
+        let captures = std::mem::take(&mut self.captures);
+
+        let enclosing = self
+            .enclosing
+            .pop()
+            .expect("The enclosing function state was just pushed.");
+        self.restore_state(enclosing);
+        result?;
+
+        let mut function = Function::new(name, params.len(), function_chunk);
+        function.captures = captures;
+
+        self.emit_constant(chunky, Value::Fn(Rc::new(function)), location.clone())?;
+        self.emit(chunky, OpCode::Closure, &[], location);
+
+        Ok(())
+    }
+
+    /// Takes the state of the function being compiled, leaving it empty.
+    fn save_state(&mut self) -> FunctionState {
+        FunctionState {
+            locals: std::mem::take(&mut self.locals),
+            scope_depth: self.scope_depth,
+            stack_depth: self.stack_depth,
+            captures: std::mem::take(&mut self.captures),
+            loops: std::mem::take(&mut self.loops),
         }
     }
 
+    fn restore_state(&mut self, state: FunctionState) {
+        self.locals = state.locals;
+        self.scope_depth = state.scope_depth;
+        self.stack_depth = state.stack_depth;
+        self.captures = state.captures;
+        self.loops = state.loops;
+    }
+
+    fn compile_function_body(
+        &mut self,
+        chunky: &mut Chunk,
+        name: &str,
+        params: &[(String, Option<Spanned<Tipo>>)],
+        body: &Expr,
+    ) -> CompilerResult<()> {
+        // The callee and the arguments are already on the stack when the function starts.
+        self.stack_depth = 1;
+        self.declare_slot(name, 0);
+        for (name, _) in params {
+            self.stack_depth += 1;
+            self.declare_local(name)?;
+        }
+
+        self.compile(chunky, body)?;
+        let end = body.location().end..body.location().end;
+        self.emit(chunky, OpCode::Return, &[], end);
+
+        Ok(())
+    }
+
+    fn compile_call(
+        &mut self,
+        chunky: &mut Chunk,
+        callee: &Expr,
+        args: &[Expr],
+        location: Span,
+    ) -> CompilerResult<()> {
+        if args.len() > u8::MAX as usize {
+            return Err(CompilerErr::TooManyParams { location });
+        }
+
+        if let Some(builtin) = self.builtin(callee) {
+            return self.compile_builtin_call(chunky, builtin, args, location);
+        }
+
+        if let Some((name, tag, arity)) = self.constructor(callee) {
+            if args.len() != arity {
+                return Err(CompilerErr::ConstructorArity {
+                    name,
+                    expected: arity,
+                    got: args.len(),
+                    location,
+                });
+            }
+
+            for arg in args {
+                self.compile(chunky, arg)?;
+            }
+            return self.emit_build_variant(chunky, &name, tag, arity, location);
+        }
+
+        self.compile(chunky, callee)?;
+        for arg in args {
+            self.compile(chunky, arg)?;
+        }
+
+        self.emit(chunky, OpCode::Call, &[args.len() as u8], location);
+        Ok(())
+    }
+
+    /// Builtins have their own `OpCode` that replaces the arguments with the result.
+    fn compile_builtin_call(
+        &mut self,
+        chunky: &mut Chunk,
+        builtin: Builtin,
+        args: &[Expr],
+        location: Span,
+    ) -> CompilerResult<()> {
+        if args.len() != builtin.arity() {
+            return Err(CompilerErr::BuiltinArity {
+                name: builtin.name(),
+                expected: builtin.arity(),
+                got: args.len(),
+                location,
+            });
+        }
+
+        for arg in args {
+            self.compile(chunky, arg)?;
+        }
+
+        let op = match builtin {
+            Builtin::Len => OpCode::Len,
+            Builtin::Push => OpCode::Push,
+            Builtin::Map => OpCode::Map,
+            Builtin::Filter => OpCode::Filter,
+            Builtin::Fold => OpCode::Fold,
+            Builtin::ToFloat => OpCode::ToFloat,
+            Builtin::ToInt => OpCode::ToInt,
+        };
+        self.emit(chunky, op, &[], location);
+        Ok(())
+    }
+
+    /// Returns the builtin a callee names, unless a variable in this or an enclosing function shadows it.
+    fn builtin(&self, callee: &Expr) -> Option<Builtin> {
+        match callee {
+            Expr::Identifier { value, .. } if !self.is_shadowed(value) => Builtin::from_name(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the name, tag and number of fields of the constructor a callee names,
+    /// unless a variable shadows it like with builtins.
+    fn constructor(&self, callee: &Expr) -> Option<(String, u8, usize)> {
+        match callee {
+            Expr::Identifier { value, .. } if !self.is_shadowed(value) => {
+                let (tag, arity) = self.constructors.get(value)?;
+                Some((value.clone(), *tag, *arity))
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks if `name` is a local of this or an enclosing function.
+    fn is_shadowed(&self, name: &str) -> bool {
+        self.locals.iter().any(|local| local.name == name)
+            || self
+                .enclosing
+                .iter()
+                .any(|state| state.locals.iter().any(|local| local.name == name))
+    }
+
+    /// Remembers the tag of each constructor, the tag is it's index in the declaration.
+    fn declare_enum(
+        &mut self,
+        variants: &[(String, Vec<Tipo>)],
+        location: Span,
+    ) -> CompilerResult<()> {
+        if variants.len() > u8::MAX as usize + 1 {
+            return Err(CompilerErr::TooManyVariants { location });
+        }
+
+        for (tag, (name, fields)) in variants.iter().enumerate() {
+            if fields.len() > u8::MAX as usize {
+                return Err(CompilerErr::TooManyFields { location });
+            }
+
+            self.constructors
+                .insert(name.clone(), (tag as u8, fields.len()));
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the `arity` values on top of the stack with a variant.
+    fn emit_build_variant(
+        &mut self,
+        chunky: &mut Chunk,
+        name: &str,
+        tag: u8,
+        arity: usize,
+        location: Span,
+    ) -> CompilerResult<()> {
+        let name = Value::Str(Box::new(name.to_string()));
+        self.emit_constant(chunky, name, location.clone())?;
+
+        self.emit(chunky, OpCode::BuildVariant, &[tag, arity as u8], location);
+        Ok(())
+    }
+
+    /// A constructor used as a value, constructors with fields are functions building the variant.
+    fn compile_constructor(
+        &mut self,
+        chunky: &mut Chunk,
+        name: &str,
+        tag: u8,
+        arity: usize,
+        location: Span,
+    ) -> CompilerResult<()> {
+        if arity == 0 {
+            return self.emit_build_variant(chunky, name, tag, arity, location);
+        }
+
+        // The arguments are in slots 1 to `arity`, above the function itself.
+        let mut function_chunk = Chunk::new();
+        for slot in 1..=arity {
+            function_chunk.write_opcode(OpCode::GetLocal, &[slot as u8], location.clone());
+        }
+
+        let index = function_chunk.add_constant(Value::Str(Box::new(name.to_string())));
+        function_chunk.write_opcode(OpCode::GetConstant, &[index as u8], location.clone());
+        function_chunk.write_opcode(OpCode::BuildVariant, &[tag, arity as u8], location.clone());
+        function_chunk.write_opcode(OpCode::Return, &[], location.clone());
+
+        let function = Function::new(name, arity, function_chunk);
+        self.emit_constant(chunky, Value::Fn(Rc::new(function)), location)
+    }
+
+    /// The scrutinee is a hidden local, each arm tests it in turn and jumps to the next arm
+    /// as soon as a test fails. The bindings of the arm that matches are locals of it's body.
+    fn compile_match(
+        &mut self,
+        chunky: &mut Chunk,
+        scrutinee: &Expr,
+        arms: &[MatchArm],
+        location: Span,
+    ) -> CompilerResult<()> {
+        self.compile(chunky, scrutinee)?;
+        // The space keeps it from clashing with a variable.
+        let slot = self.declare_local(" scrutinee")?;
+        let depth = self.stack_depth;
+
+        let mut ends = Vec::new();
+        for arm in arms {
+            let mut fails = Vec::new();
+            let mut bindings = Vec::new();
+            self.compile_pattern(chunky, &arm.pattern, slot, &[], &mut fails, &mut bindings)?;
+
+            if bindings.len() > u8::MAX as usize {
+                return Err(CompilerErr::TooManyLocals);
+            }
+            let count = bindings.len() as u8;
+
+            let locals = self.locals.len();
+            for (name, path) in &bindings {
+                self.emit_path(chunky, slot, path, arm.location.clone());
+                self.declare_local(name)?;
+            }
+
+            let guard_fail = match &arm.guard {
+                Some(guard) => {
+                    self.compile(chunky, guard)?;
+                    Some(self.emit_jump(chunky, OpCode::JumpIfFalse, guard.location()))
+                }
+                None => None,
+            };
+
+            self.compile(chunky, &arm.body)?;
+            self.locals.truncate(locals);
+            if count > 0 {
+                self.emit(chunky, OpCode::EndScope, &[count], arm.location.clone());
+            }
+            ends.push(self.emit_jump(chunky, OpCode::Jump, arm.location.clone()));
+
+            // A failed guard has to pop the arm's bindings before trying the next arm.
+            if let Some(guard_fail) = guard_fail {
+                self.stack_depth = depth + count as usize;
+                self.patch_jump(chunky, guard_fail, arm.location.clone())?;
+                if count > 0 {
+                    self.emit(chunky, OpCode::PopN, &[count], arm.location.clone());
+                }
+            }
+
+            self.stack_depth = depth;
+            for fail in fails {
+                self.patch_jump(chunky, fail, arm.location.clone())?;
+            }
+        }
+
+        self.emit(chunky, OpCode::NoMatch, &[], location.clone());
+
+        // Every arm that matched left it's result on top of the scrutinee.
+        self.stack_depth = depth + 1;
+        for end in ends {
+            self.patch_jump(chunky, end, location.clone())?;
+        }
+
+        self.pop_local(chunky, location);
+        Ok(())
+    }
+
+    /// Writes the tests for a pattern matching the value at `path` in the scrutinee,
+    /// a failed test jumps to one of `fails`. Collects the pattern's bindings with their paths.
+    fn compile_pattern(
+        &mut self,
+        chunky: &mut Chunk,
+        pattern: &Pattern,
+        slot: u8,
+        path: &[u8],
+        fails: &mut Vec<usize>,
+        bindings: &mut Vec<(String, Vec<u8>)>,
+    ) -> CompilerResult<()> {
+        match pattern {
+            Pattern::Wildcard(_) => {}
+            Pattern::Binding { name, location } if self.constructors.contains_key(name) => {
+                let constructor = Pattern::Constructor {
+                    name: name.clone(),
+                    args: Vec::new(),
+                    location: location.clone(),
+                };
+                self.compile_pattern(chunky, &constructor, slot, path, fails, bindings)?;
+            }
+            Pattern::Binding { name, .. } => bindings.push((name.clone(), path.to_vec())),
+            Pattern::Literal(literal) => {
+                let location = literal.location();
+                self.emit_path(chunky, slot, path, location.clone());
+                self.compile(chunky, literal)?;
+                self.emit(chunky, OpCode::Equal, &[], location.clone());
+                fails.push(self.emit_jump(chunky, OpCode::JumpIfFalse, location));
+            }
+            Pattern::Constructor {
+                name,
+                args,
+                location,
+            } => {
+                let (tag, _) = *self.constructors.get(name).ok_or_else(|| {
+                    CompilerErr::UndefinedConstructor {
+                        name: name.clone(),
+                        location: location.clone(),
+                    }
+                })?;
+
+                self.emit_path(chunky, slot, path, location.clone());
+                self.emit(chunky, OpCode::TestTag, &[tag], location.clone());
+                fails.push(self.emit_jump(chunky, OpCode::JumpIfFalse, location.clone()));
+
+                for (index, arg) in args.iter().enumerate() {
+                    let mut arg_path = path.to_vec();
+                    arg_path.push(index as u8);
+                    self.compile_pattern(chunky, arg, slot, &arg_path, fails, bindings)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pushes the value at `path` in the scrutinee, each step is the index of a variant's field.
+    fn emit_path(&mut self, chunky: &mut Chunk, slot: u8, path: &[u8], location: Span) {
+        self.emit(chunky, OpCode::GetLocal, &[slot], location.clone());
+        for index in path {
+            self.emit(chunky, OpCode::VariantField, &[*index], location.clone());
+        }
+    }
+
+    fn declare_struct(
+        &mut self,
+        name: &str,
+        fields: &[(String, Tipo)],
+        location: Span,
+    ) -> CompilerResult<()> {
+        if fields.len() > u8::MAX as usize {
+            return Err(CompilerErr::TooManyFields { location });
+        }
+
+        let layout = Layout {
+            name: name.to_string(),
+            fields: fields.iter().map(|(field, _)| field.clone()).collect(),
+        };
+        self.structs.insert(name.to_string(), Rc::new(layout));
+
+        Ok(())
+    }
+
+    /// Pushes the struct's layout then it's fields, they're evaluated in the order they're declared in.
+    fn compile_construct(
+        &mut self,
+        chunky: &mut Chunk,
+        name: &str,
+        fields: &[(String, Expr)],
+        location: Span,
+    ) -> CompilerResult<()> {
+        let layout =
+            self.structs
+                .get(name)
+                .cloned()
+                .ok_or_else(|| CompilerErr::UndefinedStruct {
+                    name: name.to_string(),
+                    location: location.clone(),
+                })?;
+
+        self.emit_constant(chunky, Value::Layout(layout.clone()), location.clone())?;
+
+        for field in &layout.fields {
+            let (_, value) = fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .ok_or_else(|| CompilerErr::MissingField {
+                    name: name.to_string(),
+                    field: field.clone(),
+                    location: location.clone(),
+                })?;
+
+            self.compile(chunky, value)?;
+        }
+
+        self.emit(
+            chunky,
+            OpCode::BuildRecord,
+            &[layout.fields.len() as u8],
+            location,
+        );
+        Ok(())
+    }
+
+    fn compile_field(
+        &mut self,
+        chunky: &mut Chunk,
+        expr: &Expr,
+        field: &str,
+        location: Span,
+    ) -> CompilerResult<()> {
+        self.compile(chunky, expr)?;
+
+        let index = match self.field_indices.get(&location) {
+            Some(index) => *index,
+            None => self.unchecked_field_index(field, &location)?,
+        };
+
+        self.emit(chunky, OpCode::GetField, &[index as u8], location);
+        Ok(())
+    }
+
+    /// Finds a field's position without the type checker,
+    /// it has to be in the same position in every struct with that field.
+    fn unchecked_field_index(&self, field: &str, location: &Span) -> CompilerResult<usize> {
+        let mut indices = self
+            .structs
+            .values()
+            .filter_map(|layout| layout.field_index(field));
+
+        match indices.next() {
+            Some(index) if indices.all(|other| other == index) => Ok(index),
+            _ => Err(CompilerErr::UnresolvedField {
+                field: field.to_string(),
+                location: location.clone(),
+            }),
+        }
+    }
+
+    fn compile_list(
+        &mut self,
+        chunky: &mut Chunk,
+        elements: &[Expr],
+        location: Span,
+    ) -> CompilerResult<()> {
+        if elements.len() > u8::MAX as usize {
+            return Err(CompilerErr::TooManyElements { location });
+        }
+
+        for element in elements {
+            self.compile(chunky, element)?;
+        }
+
+        self.emit(chunky, OpCode::BuildList, &[elements.len() as u8], location);
+        Ok(())
+    }
+
     fn compile_value(
         &mut self,
         chunky: &mut Chunk,
@@ -102,15 +934,33 @@ impl Compiler {
         location: Span,
     ) -> CompilerResult<()> {
         match value {
-            Value::Unit => chunky.write_opcode(OpCode::Unit, &[], location.clone()),
-            Value::Bool(true) => chunky.write_opcode(OpCode::True, &[], location.clone()),
-            Value::Bool(false) => chunky.write_opcode(OpCode::False, &[], location.clone()),
-            value => {
-                chunky.add_constant(value.clone());
-                let index: u8 = (chunky.constants.len() - 1) as u8;
-                chunky.write_opcode(OpCode::GetConstant, &[index], location.clone());
-            }
+            Value::Unit => self.emit(chunky, OpCode::Unit, &[], location.clone()),
+            Value::Bool(true) => self.emit(chunky, OpCode::True, &[], location.clone()),
+            Value::Bool(false) => self.emit(chunky, OpCode::False, &[], location.clone()),
+            value => self.emit_constant(chunky, value.clone(), location)?,
+        }
+        Ok(())
+    }
+
+    /// Adds a constant to the chunk and pushes it,
+    /// `GetConstantLong` is used once the index doesn't fit in a byte.
+    fn emit_constant(
+        &mut self,
+        chunky: &mut Chunk,
+        value: Value,
+        location: Span,
+    ) -> CompilerResult<()> {
+        let index = chunky.add_constant(value);
+
+        if index <= u8::MAX as usize {
+            self.emit(chunky, OpCode::GetConstant, &[index as u8], location);
+        } else if index < 1 << 24 {
+            let [_, i1, i2, i3] = (index as u32).to_be_bytes();
+            self.emit(chunky, OpCode::GetConstantLong, &[i1, i2, i3], location);
+        } else {
+            return Err(CompilerErr::TooManyConstants { location });
         }
+
         Ok(())
     }
 
@@ -126,10 +976,11 @@ impl Compiler {
         let unary_op: OpCode = match op {
             Op::Minus => OpCode::Negate,
             Op::Not => OpCode::LogicalNot,
+            Op::BitNot => OpCode::BitNot,
             _ => todo!(),
         };
 
-        chunky.write_opcode(unary_op, &[], location.clone());
+        self.emit(chunky, unary_op, &[], location.clone());
         Ok(())
     }
 
@@ -149,6 +1000,13 @@ impl Compiler {
             Op::Minus => OpCode::Subtract,
             Op::Multiply => OpCode::Multiply,
             Op::Divide => OpCode::Divide,
+            Op::Modulo => OpCode::Modulo,
+            Op::Power => OpCode::Power,
+            Op::BitAnd => OpCode::BitAnd,
+            Op::BitOr => OpCode::BitOr,
+            Op::BitXor => OpCode::BitXor,
+            Op::ShiftLeft => OpCode::ShiftLeft,
+            Op::ShiftRight => OpCode::ShiftRight,
             Op::EqualEqual => OpCode::Equal,
             Op::NotEqual => OpCode::NotEqual,
             Op::Less => OpCode::Less,
@@ -160,7 +1018,7 @@ impl Compiler {
             _ => todo!(),
         };
 
-        chunky.write_opcode(bin_opcode, &[], location.clone());
+        self.emit(chunky, bin_opcode, &[], location.clone());
         Ok(())
     }
 
@@ -170,39 +1028,126 @@ impl Compiler {
         name: &str,
         location: Span,
     ) -> CompilerResult<()> {
-        // When an identifier is found find the it's index on the stack.
-        // I assume the variable exists as the typechecker already checks for that.
-        let (index, _) = self
-            .locals
-            .iter()
-            .enumerate()
-            .find(|(_, local)| local.name == name)
-            .unwrap();
+        let identifier = Expr::Identifier {
+            value: name.to_string(),
+            location: location.clone(),
+        };
+        if let Some((name, tag, arity)) = self.constructor(&identifier) {
+            return self.compile_constructor(chunky, &name, tag, arity, location);
+        }
+
+        match self.resolve_variable(name, location.clone())? {
+            Variable::Local(slot) => self.emit(chunky, OpCode::GetLocal, &[slot], location),
+            Variable::Upvalue(index) => self.emit(chunky, OpCode::GetUpvalue, &[index], location),
+        }
+
+        Ok(())
+    }
+
+    /// Stores the value in the variable's slot, the assignment itself evaluates to unit.
+    fn compile_assign(
+        &mut self,
+        chunky: &mut Chunk,
+        name: &str,
+        value: &Expr,
+        location: Span,
+    ) -> CompilerResult<()> {
+        self.compile(chunky, value)?;
 
-        chunky.write_opcode(OpCode::GetLocal, &[index as u8], location.clone());
+        match self.resolve_variable(name, location.clone())? {
+            Variable::Local(slot) => self.emit(chunky, OpCode::SetLocal, &[slot], location.clone()),
+            Variable::Upvalue(index) => {
+                self.emit(chunky, OpCode::SetUpvalue, &[index], location.clone())
+            }
+        }
 
+        self.emit(chunky, OpCode::Unit, &[], location);
         Ok(())
     }
 
+    /// Finds where a variable lives, capturing it when it belongs to an enclosing function.
+    fn resolve_variable(&mut self, name: &str, location: Span) -> CompilerResult<Variable> {
+        // The latest local with the name wins so shadowing works.
+        // I assume the variable exists as the typechecker already checks for that.
+        let local = self.locals.iter().rev().find(|local| local.name == name);
+
+        if let Some(local) = local {
+            return Ok(Variable::Local(local.slot as u8));
+        }
+
+        // Otherwise the variable belongs to an enclosing function and has to be captured.
+        let current = self.save_state();
+        self.enclosing.push(current);
+        let upvalue = resolve_upvalue(&mut self.enclosing, name);
+        let current = self
+            .enclosing
+            .pop()
+            .expect("The current function state was just pushed.");
+        self.restore_state(current);
+
+        match upvalue {
+            Some(index) if index > u8::MAX as usize => {
+                Err(CompilerErr::TooManyUpvalues { location })
+            }
+            Some(index) => Ok(Variable::Upvalue(index as u8)),
+            None => Err(CompilerErr::UndefinedVariable {
+                name: name.to_string(),
+                location,
+            }),
+        }
+    }
+
     fn compile_let(
         &mut self,
         chunky: &mut Chunk,
         name: &str,
         initializer: &Expr,
         then: &Expr,
-        _location: Span,
+        location: Span,
     ) -> CompilerResult<()> {
-        let local = Local {
-            name: name.to_string(),
-            depth: self.scope_depth,
-        };
-        self.locals.push(local);
-
-        // Compile the initializer leaving it at the top of the stack.
+        // Compile the initializer leaving it at the top of the stack, that's the local's slot.
         // then compile the next expression
         self.compile(chunky, initializer)?;
+        self.declare_local(name)?;
         self.compile(chunky, then)?;
 
+        // The local goes out of scope, leaving the result of `then` on the stack.
+        self.pop_local(chunky, location);
+
+        Ok(())
+    }
+
+    /// Unpacks the tuple so each element is in it's own slot, those are the locals.
+    fn compile_let_tuple(
+        &mut self,
+        chunky: &mut Chunk,
+        names: &[String],
+        initializer: &Expr,
+        then: &Expr,
+        location: Span,
+    ) -> CompilerResult<()> {
+        if names.len() > u8::MAX as usize {
+            return Err(CompilerErr::TooManyLocals);
+        }
+        let count = names.len() as u8;
+
+        self.compile(chunky, initializer)?;
+        self.emit(chunky, OpCode::Unpack, &[count], location.clone());
+
+        let first = self.stack_depth - names.len();
+        for (index, name) in names.iter().enumerate() {
+            if first + index > u8::MAX as usize {
+                return Err(CompilerErr::TooManyLocals);
+            }
+            self.declare_slot(name, first + index);
+        }
+
+        self.compile(chunky, then)?;
+
+        let locals = self.locals.len() - names.len();
+        self.locals.truncate(locals);
+        self.emit(chunky, OpCode::EndScope, &[count], location);
+
         Ok(())
     }
 
@@ -219,26 +1164,156 @@ impl Compiler {
 
         // Write a dummy jump instruction to the else block
         // Store it's jump location's index to be patched after compiling the if block.
-        chunky.write_opcode(OpCode::JumpIfFalse, &[69], location.clone());
-        let jump_to_beginning_of_else_index = chunky.code.len() - 1;
+        let jump_to_beginning_of_else_index =
+            self.emit_jump(chunky, OpCode::JumpIfFalse, location.clone());
+        let depth_before_branches = self.stack_depth;
 
         // Compile the truthy branch
         self.compile(chunky, truthy_branch)?;
 
-        // Patch jump_to_else to after the if block
-        chunky.patch_instruction(jump_to_beginning_of_else_index, chunky.code.len() as u8);
-
         // Write a dummy jump instruction to after the else block
         // Store it's jump location's index to be patched after compiling the if block.
-        chunky.write_opcode(OpCode::Jump, &[69], location);
-        let jump_to_after_else_index = chunky.code.len() - 1;
+        let jump_to_after_else_index = self.emit_jump(chunky, OpCode::Jump, location.clone());
+
+        // Patch jump_to_else to after the if block
+        self.patch_jump(chunky, jump_to_beginning_of_else_index, location.clone())?;
+
+        // Only one of the branches runs.
+        self.stack_depth = depth_before_branches;
 
         // Compile the falsy branch
         self.compile(chunky, falsy_branch)?;
 
         // Patch jump_to_after_else to after the else block
-        chunky.patch_instruction(jump_to_after_else_index, chunky.code.len() as u8);
+        self.patch_jump(chunky, jump_to_after_else_index, location)?;
+
+        Ok(())
+    }
+
+    /// Compiles a loop, it's body's value is popped after every iteration
+    /// and the loop evaluates to unit once the condition is false or it hits a `break`.
+    fn compile_while(
+        &mut self,
+        chunky: &mut Chunk,
+        condition: &Expr,
+        body: &Expr,
+        location: Span,
+    ) -> CompilerResult<()> {
+        let start = chunky.code.len();
+        self.loops.push(Loop {
+            start,
+            stack_depth: self.stack_depth,
+            breaks: Vec::new(),
+        });
+
+        self.compile(chunky, condition)?;
+        let exit = self.emit_jump(chunky, OpCode::JumpIfFalse, location.clone());
+
+        self.compile(chunky, body)?;
+        self.emit(chunky, OpCode::Pop, &[], location.clone());
+        self.emit_loop(chunky, start, location.clone())?;
+
+        let breaks = self
+            .loops
+            .pop()
+            .expect("The loop was pushed before compiling it's body.")
+            .breaks;
+
+        // Leaving through the condition or a `break` leaves the stack as it was before the loop.
+        self.patch_jump(chunky, exit, location.clone())?;
+        for index in breaks {
+            self.patch_jump(chunky, index, location.clone())?;
+        }
+
+        self.emit(chunky, OpCode::Unit, &[], location);
+        Ok(())
+    }
+
+    fn compile_break(&mut self, chunky: &mut Chunk, location: Span) -> CompilerResult<()> {
+        let depth = self.stack_depth;
+        self.pop_to_loop_depth(chunky, location.clone())?;
+
+        let index = self.emit_jump(chunky, OpCode::Jump, location);
+        self.loops
+            .last_mut()
+            .expect("The loop was checked by pop_to_loop_depth.")
+            .breaks
+            .push(index);
+
+        // Nothing after a `break` runs, but it's an expression so it's value is counted.
+        self.stack_depth = depth + 1;
+        Ok(())
+    }
+
+    fn compile_continue(&mut self, chunky: &mut Chunk, location: Span) -> CompilerResult<()> {
+        let depth = self.stack_depth;
+        self.pop_to_loop_depth(chunky, location.clone())?;
+
+        let start = self
+            .loops
+            .last()
+            .expect("The loop was checked by pop_to_loop_depth.")
+            .start;
+        self.emit_loop(chunky, start, location)?;
+
+        self.stack_depth = depth + 1;
+        Ok(())
+    }
+
+    /// Pops the locals and temporaries pushed since the start of the innermost loop.
+    fn pop_to_loop_depth(&mut self, chunky: &mut Chunk, location: Span) -> CompilerResult<()> {
+        let loop_depth = match self.loops.last() {
+            Some(loop_) => loop_.stack_depth,
+            None => return Err(CompilerErr::OutsideLoop { location }),
+        };
+
+        let count = self.stack_depth - loop_depth;
+        if count > u8::MAX as usize {
+            return Err(CompilerErr::TooManyLocals);
+        }
+        if count > 0 {
+            self.emit(chunky, OpCode::PopN, &[count as u8], location);
+        }
+
+        Ok(())
+    }
+
+    /// Writes a `Loop` back to `start`.
+    fn emit_loop(
+        &mut self,
+        chunky: &mut Chunk,
+        start: usize,
+        location: Span,
+    ) -> CompilerResult<()> {
+        // The distance is counted from the end of the `Loop` instruction.
+        let distance = u16::try_from(chunky.code.len() + 3 - start).map_err(|_| {
+            CompilerErr::JumpTooLarge {
+                location: location.clone(),
+            }
+        })?;
+
+        let [high, low] = distance.to_be_bytes();
+        self.emit(chunky, OpCode::Loop, &[high, low], location);
+        Ok(())
+    }
+
+    /// Writes a jump with a placeholder destination, returns the index of it's operand.
+    fn emit_jump(&mut self, chunky: &mut Chunk, op: OpCode, location: Span) -> usize {
+        self.emit(chunky, op, &[u8::MAX, u8::MAX], location);
+        chunky.code.len() - 2
+    }
 
+    /// Points the jump whose operand is at `index` to the end of the chunk.
+    fn patch_jump(
+        &mut self,
+        chunky: &mut Chunk,
+        index: usize,
+        location: Span,
+    ) -> CompilerResult<()> {
+        let destination =
+            u16::try_from(chunky.code.len()).map_err(|_| CompilerErr::JumpTooLarge { location })?;
+
+        chunky.patch_short(index, destination);
         Ok(())
     }
 
@@ -250,6 +1325,38 @@ impl Compiler {
         Ok(())
     }
 
+    /// Writes an OpCode to the chunk, keeping track of the values on the stack.
+    fn emit(&mut self, chunky: &mut Chunk, op: OpCode, operands: &[u8], location: Span) {
+        chunky.write_opcode(op, operands, location);
+        self.stack_depth = (self.stack_depth as isize + op.stack_effect(operands)) as usize;
+    }
+
+    /// Turns the value on top of the stack into a local, returns it's slot.
+    fn declare_local(&mut self, name: &str) -> CompilerResult<u8> {
+        let slot = self.stack_depth - 1;
+
+        if slot > u8::MAX as usize {
+            return Err(CompilerErr::TooManyLocals);
+        }
+
+        self.declare_slot(name, slot);
+        Ok(slot as u8)
+    }
+
+    fn declare_slot(&mut self, name: &str, slot: usize) {
+        self.locals.push(Local {
+            name: name.to_string(),
+            depth: self.scope_depth,
+            slot,
+        });
+    }
+
+    /// Removes the last declared local, it sits under the value on top of the stack.
+    fn pop_local(&mut self, chunky: &mut Chunk, location: Span) {
+        self.locals.pop();
+        self.emit(chunky, OpCode::EndScope, &[1], location);
+    }
+
     fn begin_scope(&mut self) {
         self.scope_depth += 1;
     }
@@ -271,9 +1378,207 @@ impl Compiler {
     }
 }
 
+/// Finds `name` in the functions enclosing the last of `states`, adding the captures needed
+/// for each function in between. Returns the index of the last function's upvalue.
+fn resolve_upvalue(states: &mut [FunctionState], name: &str) -> Option<usize> {
+    let (current, enclosing) = states.split_last_mut()?;
+    let parent = enclosing.last()?;
+
+    let slot = parent
+        .locals
+        .iter()
+        .rev()
+        .find(|local| local.name == name)
+        .map(|local| local.slot);
+
+    let capture = match slot {
+        Some(slot) => Capture {
+            is_local: true,
+            index: slot,
+        },
+        None => Capture {
+            is_local: false,
+            index: resolve_upvalue(enclosing, name)?,
+        },
+    };
+
+    // Capturing the same variable twice shares the upvalue.
+    let index = match current.captures.iter().position(|c| *c == capture) {
+        Some(index) => index,
+        None => {
+            current.captures.push(capture);
+            current.captures.len() - 1
+        }
+    };
+
+    Some(index)
+}
+
 #[derive(Debug)]
 pub enum CompilerErr {
     PlaceHolder,
+    /// A function has more than 255 locals.
+    TooManyLocals,
+    /// A function declares or is called with more than 255 parameters.
+    TooManyParams {
+        location: Span,
+    },
+    /// A chunk has more constants than `GetConstantLong` can index.
+    TooManyConstants {
+        location: Span,
+    },
+    /// A jump's destination doesn't fit in it's `u16` operand.
+    JumpTooLarge {
+        location: Span,
+    },
+    /// A function captures more than 255 variables.
+    TooManyUpvalues {
+        location: Span,
+    },
+    /// `compile_definition` was given something other than a `let` or `funk`.
+    NotADefinition {
+        location: Span,
+    },
+    /// A `break` or `continue` isn't inside a loop.
+    OutsideLoop {
+        location: Span,
+    },
+    /// A list literal has more than 255 elements.
+    TooManyElements {
+        location: Span,
+    },
+    /// A struct is declared with more than 255 fields.
+    TooManyFields {
+        location: Span,
+    },
+    /// A struct literal names a struct that hasn't been declared.
+    UndefinedStruct {
+        name: String,
+        location: Span,
+    },
+    /// A struct literal doesn't set one of the struct's fields.
+    MissingField {
+        name: String,
+        field: String,
+        location: Span,
+    },
+    /// An enum is declared with more than 256 variants.
+    TooManyVariants {
+        location: Span,
+    },
+    /// A pattern names a constructor that hasn't been declared.
+    UndefinedConstructor {
+        name: String,
+        location: Span,
+    },
+    /// A constructor is called with the wrong number of fields.
+    ConstructorArity {
+        name: String,
+        expected: usize,
+        got: usize,
+        location: Span,
+    },
+    /// A builtin is called with the wrong number of arguments.
+    BuiltinArity {
+        name: &'static str,
+        expected: usize,
+        got: usize,
+        location: Span,
+    },
+    /// The variable isn't a local of the current function or any enclosing function.
+    UndefinedVariable {
+        name: String,
+        location: Span,
+    },
+    /// A program defines two top-level items with the same name.
+    DuplicateDefinition {
+        name: String,
+        location: Span,
+    },
+    /// A field expression wasn't type checked and structs have the field in different positions.
+    UnresolvedField {
+        field: String,
+        location: Span,
+    },
+}
+
+impl CompilerErr {
+    pub fn location(&self) -> Option<Span> {
+        use CompilerErr::*;
+
+        match self {
+            PlaceHolder | TooManyLocals => None,
+            TooManyParams { location }
+            | TooManyConstants { location }
+            | JumpTooLarge { location }
+            | TooManyUpvalues { location }
+            | NotADefinition { location }
+            | OutsideLoop { location }
+            | TooManyElements { location }
+            | TooManyFields { location }
+            | UndefinedStruct { location, .. }
+            | MissingField { location, .. }
+            | TooManyVariants { location }
+            | UndefinedConstructor { location, .. }
+            | ConstructorArity { location, .. }
+            | BuiltinArity { location, .. }
+            | UndefinedVariable { location, .. }
+            | DuplicateDefinition { location, .. }
+            | UnresolvedField { location, .. } => Some(location.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for CompilerErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use CompilerErr::*;
+
+        match self {
+            PlaceHolder => write!(f, "Compiler error."),
+            TooManyLocals => write!(f, "Too many local variables in one function."),
+            TooManyParams { .. } => write!(f, "Functions can't take more than 255 arguments."),
+            TooManyConstants { .. } => write!(f, "Too many constants in one function."),
+            JumpTooLarge { .. } => write!(f, "Too much code to jump over."),
+            TooManyUpvalues { .. } => {
+                write!(f, "Functions can't capture more than 255 variables.")
+            }
+            NotADefinition { .. } => write!(
+                f,
+                "Only `let`, `funk`, `struct` and `enum` can be definitions."
+            ),
+            OutsideLoop { .. } => write!(f, "`break` and `continue` can only be used in a loop."),
+            TooManyElements { .. } => write!(f, "List literals can't have more than 255 elements."),
+            TooManyFields { .. } => write!(f, "Structs can't have more than 255 fields."),
+            UndefinedStruct { name, .. } => write!(f, "Undefined struct '{name}'."),
+            MissingField { name, field, .. } => write!(f, "{name} is missing the field '{field}'."),
+            TooManyVariants { .. } => write!(f, "Enums can't have more than 256 variants."),
+            UndefinedConstructor { name, .. } => write!(f, "Undefined constructor '{name}'."),
+            ConstructorArity {
+                name,
+                expected,
+                got,
+                ..
+            } => write!(f, "{name} expected {expected} field(s) but got {got}."),
+            BuiltinArity {
+                name,
+                expected,
+                got,
+                ..
+            } => write!(f, "{name} expected {expected} arguments but got {got}."),
+            UndefinedVariable { name, .. } => write!(f, "Undefined variable '{name}'."),
+            DuplicateDefinition { name, .. } => write!(f, "'{name}' is defined more than once."),
+            UnresolvedField { field, .. } => write!(
+                f,
+                "Can't tell where the field '{field}' is without type checking."
+            ),
+        }
+    }
+}
+
+impl ToDiagnostic for CompilerErr {
+    fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::new(self.to_string(), self.location().unwrap_or(0..0))
+    }
 }
 
 type CompilerResult<T> = Result<T, CompilerErr>;
@@ -316,10 +1621,12 @@ mod tests {
             Local {
                 name: "a".to_string(),
                 depth: 0,
+                slot: 0,
             },
             Local {
                 name: "b".to_string(),
                 depth: 1,
+                slot: 1,
             },
         ];
 
@@ -336,18 +1643,22 @@ mod tests {
             Local {
                 name: "a".to_string(),
                 depth: 0,
+                slot: 0,
             },
             Local {
                 name: "b".to_string(),
                 depth: 1,
+                slot: 1,
             },
             Local {
                 name: "c".to_string(),
                 depth: 1,
+                slot: 2,
             },
             Local {
                 name: "d".to_string(),
                 depth: 3,
+                slot: 3,
             },
         ];
 
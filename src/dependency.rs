@@ -0,0 +1,236 @@
+//! Orders a program's funks so each one is checked after the funks it uses.
+//!
+//! Funks that call each other form a strongly connected component of the call graph,
+//! they're checked together and can only use each other monomorphically. The components
+//! are found with Tarjan's algorithm, which returns them with every component after the
+//! components it depends on.
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Expr, Pattern};
+
+/// Groups the funks, given as their names and bodies, into the order they should be checked in.
+/// Each group is the indices of funks that depend on each other, in declaration order.
+pub fn funk_groups(funks: &[(&str, &Expr)]) -> Vec<Vec<usize>> {
+    let indices: HashMap<&str, usize> = funks
+        .iter()
+        .enumerate()
+        .map(|(index, (name, _))| (*name, index))
+        .collect();
+
+    let edges: Vec<Vec<usize>> = funks
+        .iter()
+        .map(|(_, body)| {
+            let mut names = HashSet::new();
+            free_names(body, &mut Vec::new(), &mut names);
+
+            let mut uses: Vec<usize> = names
+                .iter()
+                .filter_map(|name| indices.get(name.as_str()).copied())
+                .collect();
+            uses.sort_unstable();
+            uses
+        })
+        .collect();
+
+    let mut tarjan = Tarjan {
+        edges: &edges,
+        next_index: 0,
+        indices: vec![None; funks.len()],
+        low_links: vec![0; funks.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; funks.len()],
+        groups: Vec::new(),
+    };
+    for funk in 0..funks.len() {
+        if tarjan.indices[funk].is_none() {
+            tarjan.visit(funk);
+        }
+    }
+
+    tarjan.groups
+}
+
+struct Tarjan<'a> {
+    edges: &'a [Vec<usize>],
+    next_index: usize,
+    /// The order each funk was visited in, `None` before it's visited.
+    indices: Vec<Option<usize>>,
+    /// The earliest visited funk reachable from each funk through the stack.
+    low_links: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    groups: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, funk: usize) {
+        self.indices[funk] = Some(self.next_index);
+        self.low_links[funk] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(funk);
+        self.on_stack[funk] = true;
+
+        for &used in &self.edges[funk] {
+            match self.indices[used] {
+                None => {
+                    self.visit(used);
+                    self.low_links[funk] = self.low_links[funk].min(self.low_links[used]);
+                }
+                Some(index) if self.on_stack[used] => {
+                    self.low_links[funk] = self.low_links[funk].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        // The funk is the first of it's group to be visited, the rest are above it on the stack.
+        if Some(self.low_links[funk]) == self.indices[funk] {
+            let start = self.stack.iter().rposition(|&other| other == funk).unwrap();
+            let mut group = self.stack.split_off(start);
+            for &member in &group {
+                self.on_stack[member] = false;
+            }
+            group.sort_unstable();
+            self.groups.push(group);
+        }
+    }
+}
+
+/// Collects the variables `expr` uses that aren't bound inside it, `bound` are the ones in scope.
+fn free_names(expr: &Expr, bound: &mut Vec<String>, names: &mut HashSet<String>) {
+    let scope_start = bound.len();
+
+    match expr {
+        Expr::Identifier { value, .. } => {
+            if !bound.contains(value) {
+                names.insert(value.clone());
+            }
+        }
+        Expr::Int { .. }
+        | Expr::Float { .. }
+        | Expr::Str { .. }
+        | Expr::Bool { .. }
+        | Expr::Unit(_)
+        | Expr::Value { .. }
+        | Expr::Break(_)
+        | Expr::Continue(_) => {}
+        Expr::Call { callee, args, .. } => {
+            free_names(callee, bound, names);
+            for arg in args {
+                free_names(arg, bound, names);
+            }
+        }
+        Expr::Tuple { elements, .. } | Expr::List { elements, .. } => {
+            for element in elements {
+                free_names(element, bound, names);
+            }
+        }
+        Expr::Index { list, index, .. } => {
+            free_names(list, bound, names);
+            free_names(index, bound, names);
+        }
+        Expr::Construct { fields, .. } => {
+            for (_, value) in fields {
+                free_names(value, bound, names);
+            }
+        }
+        Expr::Field { expr, .. }
+        | Expr::Try { expr, .. }
+        | Expr::Grouping { expr, .. }
+        | Expr::Block { expr, .. }
+        | Expr::Unary { rhs: expr, .. }
+        | Expr::Assign { value: expr, .. } => free_names(expr, bound, names),
+        Expr::Binary { lhs, rhs, .. } => {
+            free_names(lhs, bound, names);
+            free_names(rhs, bound, names);
+        }
+        Expr::Let {
+            name,
+            initializer,
+            then,
+            ..
+        } => {
+            free_names(initializer, bound, names);
+            bound.push(name.clone());
+            free_names(then, bound, names);
+        }
+        Expr::LetTuple {
+            names: tuple_names,
+            initializer,
+            then,
+            ..
+        } => {
+            free_names(initializer, bound, names);
+            bound.extend(tuple_names.iter().cloned());
+            free_names(then, bound, names);
+        }
+        Expr::If {
+            condition,
+            truthy_branch,
+            falsy_branch,
+            ..
+        } => {
+            free_names(condition, bound, names);
+            free_names(truthy_branch, bound, names);
+            free_names(falsy_branch, bound, names);
+        }
+        Expr::While {
+            condition, body, ..
+        } => {
+            free_names(condition, bound, names);
+            free_names(body, bound, names);
+        }
+        Expr::Sequence { first, then, .. } => {
+            free_names(first, bound, names);
+            free_names(then, bound, names);
+        }
+        Expr::Fn { params, body, .. } => {
+            bound.extend(params.iter().map(|(param, _)| param.clone()));
+            free_names(body, bound, names);
+        }
+        Expr::Funk {
+            name,
+            params,
+            body,
+            then,
+            ..
+        } => {
+            bound.push(name.clone());
+            let body_start = bound.len();
+            bound.extend(params.iter().map(|(param, _)| param.clone()));
+            free_names(body, bound, names);
+            bound.truncate(body_start);
+            free_names(then, bound, names);
+        }
+        Expr::Struct { then, .. } | Expr::Enum { then, .. } => free_names(then, bound, names),
+        Expr::Match {
+            scrutinee, arms, ..
+        } => {
+            free_names(scrutinee, bound, names);
+            for arm in arms {
+                let arm_start = bound.len();
+                pattern_names(&arm.pattern, bound);
+                if let Some(guard) = &arm.guard {
+                    free_names(guard, bound, names);
+                }
+                free_names(&arm.body, bound, names);
+                bound.truncate(arm_start);
+            }
+        }
+    }
+
+    bound.truncate(scope_start);
+}
+
+/// Adds the names a pattern binds to `bound`, constructors without fields look like bindings here.
+fn pattern_names(pattern: &Pattern, bound: &mut Vec<String>) {
+    match pattern {
+        Pattern::Binding { name, .. } => bound.push(name.clone()),
+        Pattern::Constructor { args, .. } => {
+            for arg in args {
+                pattern_names(arg, bound);
+            }
+        }
+        Pattern::Wildcard(_) | Pattern::Literal(_) => {}
+    }
+}
@@ -0,0 +1,150 @@
+/// This module turns errors carrying spans into source-annotated reports.
+/// Rendering is done with Ariadne, colours are turned off so the output can be
+/// compared in tests and piped to files.
+use std::{fmt::Display, hash::Hash};
+
+use ariadne::{CharSet, Config, Report, ReportKind};
+use chumsky::error::{Simple, SimpleReason};
+
+use crate::lexer::Span;
+
+/// A secondary annotation pointing at some other piece of the source,
+/// e.g. where a type annotation was declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub location: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(location: Span, message: &str) -> Label {
+        Label {
+            location,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// An error message with a primary span and any number of secondary labels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub location: Span,
+    pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, location: Span) -> Diagnostic {
+        Diagnostic {
+            message,
+            location,
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, label: Label) -> Diagnostic {
+        self.labels.push(label);
+        self
+    }
+
+    /// Renders the diagnostic as caret-underlined snippets of `src`,
+    /// headed by `filename:line:column`.
+    pub fn render(&self, filename: &str, src: &str) -> String {
+        self.render_sources(&[(filename, 0, src)])
+    }
+
+    /// Renders the diagnostic against several sources given as `(name, offset, src)`,
+    /// sorted by offset. Each span belongs to the last source starting at or before it,
+    /// like the entries of a REPL session sharing one span space.
+    pub fn render_sources(&self, sources: &[(&str, usize, &str)]) -> String {
+        let locate = |span: &Span| {
+            let (name, offset, _) = sources
+                .iter()
+                .rev()
+                .find(|(_, offset, _)| *offset <= span.start)
+                .unwrap_or(&sources[0]);
+            let start = span.start.saturating_sub(*offset);
+            let end = span.end.saturating_sub(*offset).max(start);
+
+            (name.to_string(), start..end)
+        };
+
+        let (filename, location) = locate(&self.location);
+        let mut report = Report::build(ReportKind::Error, filename.clone(), location.start)
+            .with_config(
+                Config::default()
+                    .with_color(false)
+                    .with_char_set(CharSet::Ascii),
+            )
+            .with_message(&self.message)
+            .with_label(
+                ariadne::Label::new((filename, location))
+                    .with_message(&self.message)
+                    .with_order(0),
+            );
+
+        for (i, label) in self.labels.iter().enumerate() {
+            report = report.with_label(
+                ariadne::Label::new(locate(&label.location))
+                    .with_message(&label.message)
+                    .with_order(i as i32 + 1),
+            );
+        }
+
+        let mut out: Vec<u8> = Vec::new();
+        report
+            .finish()
+            .write(
+                ariadne::sources(
+                    sources
+                        .iter()
+                        .map(|(name, _, src)| (name.to_string(), src.to_string())),
+                ),
+                &mut out,
+            )
+            .expect("Writing to a Vec can't fail.");
+
+        String::from_utf8_lossy(&out).to_string()
+    }
+}
+
+/// Implemented by errors that can be reported against the source code.
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+/// Lexer and parser errors.
+impl<T: Display + Hash + Eq> ToDiagnostic for Simple<T> {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let describe = |token: Option<&T>| match token {
+            Some(token) => format!("'{token}'"),
+            None => "end of input".to_string(),
+        };
+
+        match self.reason() {
+            SimpleReason::Custom(message) => Diagnostic::new(message.clone(), self.span()),
+            SimpleReason::Unclosed { span, delimiter } => {
+                Diagnostic::new(format!("Unclosed delimiter '{delimiter}'."), self.span())
+                    .with_label(Label::new(span.clone(), "Opened here."))
+            }
+            SimpleReason::Unexpected => {
+                let mut expected: Vec<String> = self
+                    .expected()
+                    .map(|token| describe(token.as_ref()))
+                    .collect();
+                expected.sort();
+
+                let mut message = format!("Unexpected {}", describe(self.found()));
+                if let Some(label) = self.label() {
+                    message.push_str(&format!(" while parsing {label}"));
+                }
+                if !expected.is_empty() {
+                    message.push_str(&format!(", expected {}", expected.join(", ")));
+                }
+                message.push('.');
+
+                Diagnostic::new(message, self.span())
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Generates Markdown documentation for the funks of a program.
+//!
+//! Only funks with `///` comments are listed, each under it's type from the typechecker.
+
+use std::collections::HashMap;
+
+use crate::{
+    ast::{Expr, Program},
+    tipo::Tipo,
+    typechecker::{TypeChecker, TypeResult},
+};
+
+/// Renders the documented top-level funks of `program` under a `title` heading, in the order
+/// they're declared. The program is type checked first but it doesn't need a `main`.
+#[allow(clippy::result_large_err)]
+pub fn markdown(title: &str, program: &Program) -> TypeResult<String> {
+    let mut checker = TypeChecker::new();
+    checker.check_items(program)?;
+
+    let mut markdown = format!("# {title}\n");
+
+    for item in &program.items {
+        let Expr::Funk {
+            doc: Some(doc),
+            name,
+            type_params,
+            params,
+            return_tipo,
+            ..
+        } = item
+        else {
+            continue;
+        };
+
+        let scheme = checker
+            .scheme_of(name)
+            .expect("Checking the items declares every funk.");
+
+        // The type parameters keep the names they're declared with.
+        let mut names = HashMap::new();
+        if let Tipo::Fn { args, ret } = &scheme.tipo {
+            let annotations = params.iter().map(|(_, tipo)| tipo).chain([return_tipo]);
+            for (annotation, tipo) in annotations.zip(args.iter().chain([ret.as_ref()])) {
+                if let Some((annotation, _)) = annotation {
+                    name_type_params(annotation, tipo, type_params, &mut names);
+                }
+            }
+        }
+        let scheme = scheme.display_with(&names);
+
+        markdown.push_str(&format!(
+            "\n## {name}\n\n```text\n{name}: {scheme}\n```\n\n{doc}\n"
+        ));
+    }
+
+    Ok(markdown)
+}
+
+/// Finds the type variable each type parameter in `annotation` became in the checked `tipo`.
+fn name_type_params(
+    annotation: &Tipo,
+    tipo: &Tipo,
+    type_params: &[String],
+    names: &mut HashMap<usize, String>,
+) {
+    match (annotation, tipo) {
+        (Tipo::App { name, args }, Tipo::Var { id })
+            if args.is_empty() && type_params.contains(name) =>
+        {
+            names.insert(*id, name.clone());
+        }
+        (
+            Tipo::App { args, .. },
+            Tipo::App {
+                args: tipo_args, ..
+            },
+        ) => {
+            for (arg, tipo_arg) in args.iter().zip(tipo_args) {
+                name_type_params(arg, tipo_arg, type_params, names);
+            }
+        }
+        (
+            Tipo::Fn { args, ret },
+            Tipo::Fn {
+                args: tipo_args,
+                ret: tipo_ret,
+            },
+        ) => {
+            for (arg, tipo_arg) in args.iter().zip(tipo_args) {
+                name_type_params(arg, tipo_arg, type_params, names);
+            }
+            name_type_params(ret, tipo_ret, type_params, names);
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,242 @@
+//! Checks `match` arms for missing and unreachable patterns.
+//!
+//! The arms are rows of a pattern matrix, the algorithm is the usefulness check from
+//! Luc Maranget's "Warnings for pattern matching": a pattern is useful if it matches
+//! a value none of the rows above it match. An arm is unreachable when it's pattern isn't useful,
+//! the match is exhaustive when a wildcard after the last arm isn't useful.
+
+/// A pattern stripped down to what matters for exhaustiveness, bindings are wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pat {
+    Wild,
+    /// A variant or a bool, `siblings` are every constructor of it's type with their arity.
+    Ctor {
+        name: String,
+        args: Vec<Pat>,
+        siblings: Vec<(String, usize)>,
+    },
+    /// An int or string literal, their types have too many values to list.
+    Lit(String),
+}
+
+impl Pat {
+    /// `true` and `false` are the constructors of `bool`.
+    pub fn bool(value: bool) -> Pat {
+        Pat::Ctor {
+            name: value.to_string(),
+            args: Vec::new(),
+            siblings: vec![("true".to_string(), 0), ("false".to_string(), 0)],
+        }
+    }
+}
+
+/// Checks if there's a value `row` matches that none of `rows` match.
+pub fn is_useful(rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+    let Some((head, rest)) = row.split_first() else {
+        return rows.is_empty();
+    };
+
+    match head {
+        Pat::Ctor { name, args, .. } => {
+            let row: Vec<Pat> = args.iter().chain(rest).cloned().collect();
+            is_useful(&specialize(rows, name, args.len()), &row)
+        }
+        Pat::Lit(literal) => is_useful(&specialize_literal(rows, literal), rest),
+        Pat::Wild => match complete_signature(rows) {
+            Some(siblings) => siblings.iter().any(|(name, arity)| {
+                let row: Vec<Pat> = std::iter::repeat_n(Pat::Wild, *arity)
+                    .chain(rest.iter().cloned())
+                    .collect();
+                is_useful(&specialize(rows, name, *arity), &row)
+            }),
+            None => is_useful(&default(rows), rest),
+        },
+    }
+}
+
+/// Returns values, one for each of `width` columns, that none of the rows match.
+/// `None` means the rows are exhaustive.
+pub fn missing(rows: &[Vec<Pat>], width: usize) -> Option<Vec<Pat>> {
+    if width == 0 {
+        return rows.is_empty().then(Vec::new);
+    }
+
+    if let Some(siblings) = complete_signature(rows) {
+        return siblings.iter().find_map(|(name, arity)| {
+            let mut witness = missing(&specialize(rows, name, *arity), arity + width - 1)?;
+            let rest = witness.split_off(*arity);
+
+            let mut row = vec![Pat::Ctor {
+                name: name.clone(),
+                args: witness,
+                siblings: siblings.clone(),
+            }];
+            row.extend(rest);
+            Some(row)
+        });
+    }
+
+    let rest = missing(&default(rows), width - 1)?;
+
+    // A constructor the column doesn't mention is a better example than a wildcard.
+    let head = signature(rows)
+        .and_then(|siblings| {
+            siblings
+                .iter()
+                .find(|(name, _)| !column_names(rows).contains(&name))
+                .map(|(name, arity)| Pat::Ctor {
+                    name: name.clone(),
+                    args: vec![Pat::Wild; *arity],
+                    siblings: siblings.clone(),
+                })
+        })
+        .unwrap_or(Pat::Wild);
+
+    let mut row = vec![head];
+    row.extend(rest);
+    Some(row)
+}
+
+/// The rows that match constructor `name`, with it's arguments in place of the first column.
+fn specialize(rows: &[Vec<Pat>], name: &str, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let (head, rest) = row.split_first()?;
+
+            let args = match head {
+                Pat::Ctor {
+                    name: other, args, ..
+                } if other == name => args.clone(),
+                Pat::Wild => vec![Pat::Wild; arity],
+                _ => return None,
+            };
+
+            Some(args.into_iter().chain(rest.iter().cloned()).collect())
+        })
+        .collect()
+}
+
+fn specialize_literal(rows: &[Vec<Pat>], literal: &str) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let (head, rest) = row.split_first()?;
+
+            match head {
+                Pat::Lit(other) if other == literal => Some(rest.to_vec()),
+                Pat::Wild => Some(rest.to_vec()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The rows that match anything in the first column, without it.
+fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| match row.split_first()? {
+            (Pat::Wild, rest) => Some(rest.to_vec()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The constructors of the first column's type, if any of them are in the column.
+fn signature(rows: &[Vec<Pat>]) -> Option<&Vec<(String, usize)>> {
+    rows.iter().find_map(|row| match row.first()? {
+        Pat::Ctor { siblings, .. } => Some(siblings),
+        _ => None,
+    })
+}
+
+/// The constructors of the first column's type, if every one of them is in the column.
+fn complete_signature(rows: &[Vec<Pat>]) -> Option<Vec<(String, usize)>> {
+    let siblings = signature(rows)?;
+    let names = column_names(rows);
+
+    siblings
+        .iter()
+        .all(|(name, _)| names.contains(&name))
+        .then(|| siblings.clone())
+}
+
+fn column_names(rows: &[Vec<Pat>]) -> Vec<&String> {
+    rows.iter()
+        .filter_map(|row| match row.first()? {
+            Pat::Ctor { name, .. } => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+impl std::fmt::Display for Pat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pat::Wild => write!(f, "_"),
+            Pat::Lit(literal) => write!(f, "{literal}"),
+            Pat::Ctor { name, args, .. } if args.is_empty() => write!(f, "{name}"),
+            Pat::Ctor { name, args, .. } => {
+                write!(f, "{name}(")?;
+                for (index, arg) in args.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(name: &str, args: Vec<Pat>) -> Pat {
+        Pat::Ctor {
+            name: name.to_string(),
+            args,
+            siblings: vec![("Circle".to_string(), 1), ("Rect".to_string(), 2)],
+        }
+    }
+
+    #[test]
+    fn finds_the_missing_constructor() {
+        let rows = vec![vec![shape("Circle", vec![Pat::Wild])]];
+
+        let witness = missing(&rows, 1).unwrap();
+
+        assert_eq!(witness[0].to_string(), "Rect(_, _)");
+    }
+
+    #[test]
+    fn nested_constructors_are_checked() {
+        let rows = vec![
+            vec![shape("Circle", vec![Pat::Wild])],
+            vec![shape("Rect", vec![Pat::bool(true), Pat::Wild])],
+        ];
+
+        let witness = missing(&rows, 1).unwrap();
+        assert_eq!(witness[0].to_string(), "Rect(false, _)");
+
+        let mut rows = rows;
+        rows.push(vec![shape("Rect", vec![Pat::bool(false), Pat::Wild])]);
+        assert_eq!(missing(&rows, 1), None);
+    }
+
+    #[test]
+    fn literals_need_a_wildcard() {
+        let rows = vec![vec![Pat::Lit("1".to_string())]];
+
+        assert_eq!(missing(&rows, 1), Some(vec![Pat::Wild]));
+        assert!(!is_useful(&rows, &[Pat::Lit("1".to_string())]));
+        assert!(is_useful(&rows, &[Pat::Lit("2".to_string())]));
+    }
+
+    #[test]
+    fn arms_after_a_wildcard_are_useless() {
+        let rows = vec![vec![Pat::Wild]];
+
+        assert!(!is_useful(&rows, &[shape("Circle", vec![Pat::Wild])]));
+    }
+}
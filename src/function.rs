@@ -1,17 +1,74 @@
-use crate::ast::Expr;
-use crate::tipo::Tipo;
+use std::{cell::RefCell, rc::Rc};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::{value::Value, vm::chunk::Chunk};
+
+/// A compiled function, every function gets it's own `Chunk`.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Function {
-    pub params: Vec<(String, Tipo)>,
-    pub ret: Tipo,
-    pub body: Box<Expr>,
+    pub name: String,
+    pub arity: usize,
+    pub chunk: Chunk,
+    /// Where each of the function's upvalues is captured from when a closure is created.
+    pub captures: Vec<Capture>,
 }
 
 impl Function {
-    pub fn get_tipo(&self) -> Tipo {
-        let args = self.params.iter().map(|(_, t)| t).cloned().collect();
+    pub fn new(name: &str, arity: usize, chunk: Chunk) -> Function {
+        Function {
+            name: name.to_string(),
+            arity,
+            chunk,
+            captures: Vec::new(),
+        }
+    }
+}
 
-        Tipo::new_fn(args, self.ret.clone())
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<fn>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
     }
 }
+
+/// Describes a variable captured by a function.
+/// If `is_local` it's the local in slot `index` of the enclosing function's frame,
+/// otherwise it's the enclosing function's upvalue at `index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capture {
+    pub is_local: bool,
+    pub index: usize,
+}
+
+/// A function together with the variables it captured.
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl Closure {
+    pub fn new(function: Rc<Function>) -> Closure {
+        Closure {
+            function,
+            upvalues: Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+/// A captured variable.
+/// While the variable is still on the stack the upvalue is `Open` and points at it's stack slot,
+/// when the variable goes out of scope the value is moved into the upvalue and it's `Closed`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
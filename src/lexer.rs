@@ -13,33 +13,78 @@ pub fn lexer() -> impl Parser<char, Vec<Spanned<Token>>, Error = Simple<char>> {
         just("->").to(Token::RArrow),
         just('-').to(Token::Minus),
         just('/').to(Token::RSlash),
+        just("**").to(Token::StarStar),
         just('*').to(Token::Star),
+        just('%').to(Token::Percent),
+        just('&').to(Token::Ampersand),
+        just('|').to(Token::Pipe),
+        just('^').to(Token::Caret),
+        just('~').to(Token::Tilde),
+    ))
+    // A single `choice` only takes so many parsers.
+    .or(choice((
         just('.').to(Token::Dot),
         just(',').to(Token::Comma),
         just(':').to(Token::Colon),
         just(';').to(Token::SemiColon),
+        just('?').to(Token::Question),
         just("!=").to(Token::NotEqual),
         just('!').to(Token::Not),
         just("==").to(Token::EqualEqual),
+        just("=>").to(Token::FatArrow),
         just('=').to(Token::Equal),
+        just("<<").to(Token::LessLess),
         just("<=").to(Token::LessEqual),
         just('<').to(Token::Less),
         just(">=").to(Token::GreaterEqual),
+        // `>>` is two `>`, the parser joins them so `list<list<int>>` still closes twice.
         just('>').to(Token::Greater),
-    ));
+    )));
 
     let grouping = choice((
-        // just("()").to(Token::Unit),
         just('(').to(Token::LeftParen),
         just(')').to(Token::RightParen),
         just('{').to(Token::LeftBrace),
         just('}').to(Token::RightBrace),
+        just('[').to(Token::LeftBracket),
+        just(']').to(Token::RightBracket),
     ));
 
     let number = text::int(10)
+        .validate(|value: String, span, emit| {
+            if value.parse::<i64>().is_err() {
+                emit(Simple::custom(
+                    span,
+                    format!("The integer literal {value} is too large"),
+                ))
+            }
+            value
+        })
         .map(|value: String| Token::Int { value })
         .labelled("number");
 
+    let exponent = one_of("eE")
+        .ignore_then(one_of("+-").or_not())
+        .then(text::digits(10))
+        .map(|(sign, digits): (Option<char>, String)| match sign {
+            Some(sign) => format!("e{sign}{digits}"),
+            None => format!("e{digits}"),
+        });
+    let fraction = just('.')
+        .ignore_then(text::digits(10))
+        .then(exponent.clone().or_not())
+        .map(|(digits, exponent): (String, _)| {
+            format!(".{digits}{}", exponent.unwrap_or_default())
+        });
+
+    // A float needs a fraction or an exponent, otherwise it's an int.
+    let float = text::int(10)
+        .then(fraction.or(exponent))
+        .map(|(int, rest): (String, String)| Token::Float {
+            value: format!("{int}{rest}"),
+        })
+        .labelled("float");
+
     let boolean = just("true")
         .or(just("false"))
         .map(|s: &str| Token::Bool {
@@ -70,15 +115,103 @@ pub fn lexer() -> impl Parser<char, Vec<Spanned<Token>>, Error = Simple<char>> {
         "fn" => Token::Fn,
         "if" => Token::If,
         "else" => Token::Else,
+        "while" => Token::While,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
+        "struct" => Token::Struct,
+        "enum" => Token::Enum,
+        "match" => Token::Match,
         "let" => Token::Let,
+        "mut" => Token::Mut,
         "and" => Token::And,
         "or" => Token::Or,
         _ => Token::Identifier { value: s },
     });
 
-    choice((number, boolean, string, op, grouping, keyword))
-        .map_with_span(|token, span| (token, span))
-        .padded()
-        .repeated()
+    // `//` runs to the end of the line, exactly three slashes make it a doc comment.
+    let line_comment = just("//").ignore_then(filter(|c: &char| *c != '\n').repeated().collect());
+    let is_doc = |rest: &String| rest.starts_with('/') && !rest.starts_with("//");
+
+    let doc_comment = line_comment
+        .try_map(move |rest: String, span| {
+            if is_doc(&rest) {
+                Ok(Token::DocComment {
+                    value: rest[1..].to_string(),
+                })
+            } else {
+                Err(Simple::custom(span, "Expected a doc comment"))
+            }
+        })
+        .labelled("doc comment");
+
+    // Block comments nest, so code with comments in it can be commented out.
+    let block_comment = recursive(|block_comment| {
+        just("/*")
+            .ignore_then(
+                block_comment
+                    .or(filter(|c: &char| *c != '*').ignored())
+                    .or(just('*').then(none_of('/').rewind()).ignored())
+                    .repeated(),
+            )
+            .ignore_then(just("*/").or_not())
+            .validate(|closed, span, emit| {
+                if closed.is_none() {
+                    emit(Simple::custom(span, "Unterminated block comment"))
+                }
+            })
+            .ignored()
+    })
+    .labelled("block comment");
+
+    let trivia = choice((
+        filter(|c: &char| c.is_whitespace()).ignored(),
+        line_comment
+            .try_map(move |rest, span| {
+                if is_doc(&rest) {
+                    Err(Simple::custom(span, "Doc comments aren't trivia"))
+                } else {
+                    Ok(())
+                }
+            })
+            .ignored(),
+        block_comment,
+    ))
+    .repeated();
+
+    let token = choice((
+        doc_comment,
+        float,
+        number,
+        boolean,
+        string,
+        op,
+        grouping,
+        keyword,
+    ))
+    .map_with_span(|token, span| (token, span));
+
+    trivia
+        .clone()
+        .ignore_then(token.then_ignore(trivia).repeated())
         .then_ignore(end())
+        .map(drop_detached_docs)
+}
+
+/// Doc comments only document funks, elsewhere they're ordinary comments and are dropped.
+fn drop_detached_docs(tokens: Vec<Spanned<Token>>) -> Vec<Spanned<Token>> {
+    let mut attached = false;
+    let mut kept: Vec<Spanned<Token>> = tokens
+        .into_iter()
+        .rev()
+        .filter(|(token, _)| match token {
+            Token::DocComment { .. } => attached,
+            token => {
+                attached = *token == Token::Funk;
+                true
+            }
+        })
+        .collect();
+
+    kept.reverse();
+    kept
 }
@@ -1,8 +1,14 @@
 pub mod ast;
+pub mod builtin;
 pub mod compiler;
+pub mod dependency;
+pub mod diagnostic;
+pub mod doc;
+pub mod exhaustiveness;
 pub mod function;
 pub mod lexer;
 pub mod parser;
+pub mod repl;
 pub mod tipo;
 pub mod token;
 pub mod typechecker;
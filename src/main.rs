@@ -1,49 +1,294 @@
+//! The `pico` command line driver.
+//!
+//! ```text
+//! pico <command> [file]
+//! pico compile <file> [-o out.jkc]
+//! ```
+//!
+//! Running `pico` without a command starts the REPL.
+//! Source is read from `file`, or from stdin when it's missing or `-`.
+//! `compile` writes a `.jkc` bytecode file, which `run` and `disasm` also take.
+use std::{io::Read, path::Path, process::ExitCode};
+
 use chumsky::Parser;
 use pico_typechecker::{
-    ast::Expr,
+    ast::Program,
     compiler::Compiler,
-    lexer::{lexer, Span},
+    diagnostic::{Diagnostic, ToDiagnostic},
+    doc,
+    lexer::{lexer, Spanned},
     parser,
+    repl::Repl,
+    tipo::Tipo,
     token::Token,
-    typechecker::*,
-    vm::{chunk::Chunk, opcode::OpCode, VM},
+    typechecker::TypeChecker,
+    value::Value,
+    vm::{
+        bytecode::{self, MAGIC},
+        chunk::Chunk,
+        VM,
+    },
 };
 
-fn main() {
-    let expr = try_parsing(
-        "
-                if 1  < 2 {
-                    7
-                } else {
-                    9
-                }
-    ",
-    );
-    let mut checker = TypeChecker::new();
+const USAGE: &str = "\
+Usage: pico <command> [file]
+       pico compile <file> [-o out.jkc]
+
+Commands:
+    run       Type check, compile and run a program, or run a .jkc file
+    compile   Compile a program to a .jkc file, next to it unless -o is given
+    check     Type check a program
+    disasm    Print the bytecode of a program or a .jkc file
+    tokens    Print the tokens of a program
+    ast       Print the syntax tree of a program
+    doc       Print Markdown documentation for the funks with /// comments
+    repl      Start an interactive session, the default without a command
+    help      Print this message
 
-    let tipo = checker
-        .check_expr(&expr)
-        .unwrap_or_else(|e| panic!("Type Error: {e}"));
+The source is read from stdin when [file] is missing or '-'.";
 
-    println!("Tipo: {tipo}");
+// Exit codes, following sysexits.h.
+const EX_USAGE: u8 = 64;
+const EX_DATAERR: u8 = 65;
+const EX_NOINPUT: u8 = 66;
+const EX_SOFTWARE: u8 = 70;
+const EX_CANTCREAT: u8 = 73;
+
+type CliResult<T> = Result<T, u8>;
+
+/// The contents of the file being run, along with the name used in diagnostics.
+struct Input {
+    name: String,
+    bytes: Vec<u8>,
+}
+
+impl Input {
+    fn is_bytecode(&self) -> bool {
+        self.bytes.starts_with(MAGIC)
+    }
+
+    fn source(&self) -> CliResult<&str> {
+        std::str::from_utf8(&self.bytes).map_err(|_| {
+            eprintln!("error: {} isn't valid UTF-8.", self.name);
+            EX_DATAERR
+        })
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run_cli(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(code) => ExitCode::from(code),
+    }
+}
+
+fn run_cli(args: &[String]) -> CliResult<()> {
+    let (command, path, out) = match args {
+        [] => return repl(),
+        [command] => (command.as_str(), None, None),
+        [command, path] => (command.as_str(), Some(path.as_str()), None),
+        [command, path, flag, out] if command == "compile" && flag == "-o" => {
+            (command.as_str(), Some(path.as_str()), Some(out.as_str()))
+        }
+        _ => return usage_error(),
+    };
+
+    if matches!(command, "help" | "-h" | "--help") {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    if command == "repl" && path.is_none() {
+        return repl();
+    }
+
+    if !matches!(
+        command,
+        "run" | "compile" | "check" | "disasm" | "tokens" | "ast" | "doc"
+    ) {
+        eprintln!("error: Unknown command '{command}'.");
+        return usage_error();
+    }
+
+    let input = read_input(path)?;
+
+    match command {
+        "run" => run(&input),
+        "compile" => compile(&input, path, out),
+        "check" => {
+            let src = input.source()?;
+            let program = parse(&input.name, src)?;
+            let tipo = check(&mut TypeChecker::new(), &input.name, src, &program)?;
+            println!("main returns {tipo}");
+            Ok(())
+        }
+        "disasm" => {
+            load(&input)?.disassemble(&input.name);
+            Ok(())
+        }
+        "tokens" => {
+            for (token, span) in lex(&input.name, input.source()?)? {
+                println!("{:>5}..{:<5} {token:?}", span.start, span.end);
+            }
+            Ok(())
+        }
+        "ast" => {
+            let src = input.source()?;
+            println!("{:#?}", parse(&input.name, src)?);
+            Ok(())
+        }
+        "doc" => {
+            let src = input.source()?;
+            let markdown =
+                doc::markdown(&input.name, &parse(&input.name, src)?).map_err(|err| {
+                    report(&input.name, src, [err.to_diagnostic()]);
+                    EX_DATAERR
+                })?;
+            print!("{markdown}");
+            Ok(())
+        }
+        _ => unreachable!("The command was checked above."),
+    }
+}
+
+fn repl() -> CliResult<()> {
+    let stdin = std::io::stdin();
+
+    Repl::new()
+        .run(stdin.lock(), std::io::stdout())
+        .map_err(|err| {
+            eprintln!("error: {err}");
+            EX_SOFTWARE
+        })
+}
+
+fn usage_error() -> CliResult<()> {
+    eprintln!("{USAGE}");
+    Err(EX_USAGE)
+}
+
+fn read_input(path: Option<&str>) -> CliResult<Input> {
+    match path {
+        None | Some("-") => {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes).map_err(|err| {
+                eprintln!("error: Couldn't read stdin: {err}");
+                EX_NOINPUT
+            })?;
+
+            Ok(Input {
+                name: "<stdin>".to_string(),
+                bytes,
+            })
+        }
+        Some(path) => {
+            let bytes = std::fs::read(path).map_err(|err| {
+                eprintln!("error: Couldn't read {path}: {err}");
+                EX_NOINPUT
+            })?;
+
+            Ok(Input {
+                name: path.to_string(),
+                bytes,
+            })
+        }
+    }
+}
+
+fn run(input: &Input) -> CliResult<()> {
+    let mut vm = VM::new(load(input)?);
+
+    match vm.run() {
+        Ok(Value::Unit) => Ok(()),
+        Ok(value) => {
+            println!("{value}");
+            Ok(())
+        }
+        Err(err) if input.is_bytecode() => {
+            eprintln!("error: {err}");
+            Err(EX_SOFTWARE)
+        }
+        Err(err) => {
+            report(&input.name, input.source()?, [err.to_diagnostic()]);
+            Err(EX_SOFTWARE)
+        }
+    }
+}
+
+/// Writes the program's chunk to `out`, or next to the source file with a `.jkc` extension.
+fn compile(input: &Input, path: Option<&str>, out: Option<&str>) -> CliResult<()> {
+    let out = match (out, path) {
+        (Some(out), _) => Path::new(out).to_path_buf(),
+        (None, Some(path)) if path != "-" => Path::new(path).with_extension("jkc"),
+        (None, _) => {
+            eprintln!("error: Compiling stdin needs an output file, pass one with -o.");
+            return Err(EX_USAGE);
+        }
+    };
+
+    bytecode::write_file(&out, &load(input)?).map_err(|err| {
+        eprintln!("error: Couldn't write {}: {err}", out.display());
+        EX_CANTCREAT
+    })
+}
+
+/// Returns the chunk of a `.jkc` file, or compiles it from source.
+fn load(input: &Input) -> CliResult<Chunk> {
+    if input.is_bytecode() {
+        return bytecode::deserialize(&input.bytes).map_err(|err| {
+            eprintln!("error: {err}");
+            EX_DATAERR
+        });
+    }
+
+    let src = input.source()?;
+    let program = parse(&input.name, src)?;
+    let mut checker = TypeChecker::new();
+    check(&mut checker, &input.name, src, &program)?;
 
     let mut chunky = Chunk::new();
     let mut compiler = Compiler::new();
+    compiler.set_field_indices(checker.take_field_indices());
+    compiler
+        .compile_program(&mut chunky, &program)
+        .map_err(|err| {
+            report(&input.name, src, [err.to_diagnostic()]);
+            EX_DATAERR
+        })?;
 
-    compiler.compile(&mut chunky, &expr).unwrap();
-    chunky.write_opcode(OpCode::Return, &[], 0..0);
+    Ok(chunky)
+}
 
-    chunky.disassemble("If/Else test");
+fn lex(name: &str, src: &str) -> CliResult<Vec<Spanned<Token>>> {
+    lexer().parse(src).map_err(|errs| {
+        report(name, src, errs.iter().map(ToDiagnostic::to_diagnostic));
+        EX_DATAERR
+    })
+}
 
-    let mut vm = VM::new(chunky);
-    vm.run().unwrap();
+fn parse(name: &str, src: &str) -> CliResult<Program> {
+    let tokens = lex(name, src)?;
+    let eoi = src.len()..src.len();
+
+    parser::program_parser()
+        .parse(chumsky::Stream::from_iter(eoi, tokens.into_iter()))
+        .map_err(|errs| {
+            report(name, src, errs.iter().map(ToDiagnostic::to_diagnostic));
+            EX_DATAERR
+        })
+}
 
-    println!("VM SNAPSHOT: {vm:?}");
+fn check(checker: &mut TypeChecker, name: &str, src: &str, program: &Program) -> CliResult<Tipo> {
+    checker.check_program_all(program).map_err(|errs| {
+        report(name, src, errs.iter().map(ToDiagnostic::to_diagnostic));
+        EX_DATAERR
+    })
 }
 
-fn try_parsing(src: &str) -> Expr {
-    let toks: Vec<(Token, Span)> = lexer().parse(src).unwrap();
-    parser::expr_parser()
-        .parse(chumsky::Stream::from_iter(1..1, toks.into_iter()))
-        .unwrap()
+fn report(name: &str, src: &str, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+    for diagnostic in diagnostics {
+        eprint!("{}", diagnostic.render(name, src));
+    }
 }
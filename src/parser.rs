@@ -1,337 +1,1046 @@
-/// This module contains a parser for the language
-/// Language Specification in  Pseudo EBNF
-/// TODO Add If/Else
-///
-/// rawExpr ::=
-///  
-/// logicalOr ::= logicalAnd (or logicalAnd)* ;
-///
-/// logicalAnd ::= equality (( and | or ) equality)* ;
-///
-/// equality ::= comparison (( == | != ) comparison)* ;
-///
-/// comparison ::= term (( < | <= | > | >= ) term)* ;
-///
-/// term ::= factor (( + | - )  factor)* ;
-///
-/// factor ::= unary (( * | / ) factor)* ;
-///
-/// unary ::= (- | not) unary | primary ;
-///
-/// primary ::= IDENTIFIER | NUMBER | STRING | BOOL | UNIT ;
+//! This module contains a parser for the language
+//! Language Specification in  Pseudo EBNF
+//! TODO Add If/Else
+//!
+//! Whitespace, `//` and nestable `/* */` comments are skipped by the lexer,
+//! `///` doc comments are tokens above a funk, the lexer drops them anywhere else.
+//!
+//! program ::= (funkDecl | structDecl | enumDecl)* ;
+//!
+//! funkDecl ::= DOC_COMMENT* 'funk' IDENT typeParams? params ('->' tipo)? block rawExpr? ;
+//!
+//! replEntry ::= letDecl | funkDecl | structDecl | enumDecl | rawExpr ;
+//!
+//! rawExpr ::= letExpr | funkDecl | structDecl | enumDecl | sequence ;
+//!
+//! structDecl ::= 'struct' IDENT '{' (IDENT ':' tipo (',' IDENT ':' tipo)* ','?)? '}' rawExpr? ;
+//!
+//! enumDecl ::= 'enum' IDENT typeParams? '{' (variant (',' variant)* ','?)? '}' rawExpr? ;
+//!
+//! variant ::= IDENT ('(' tipo (',' tipo)* ','? ')')? ;
+//!
+//! sequence ::= expr (';' rawExpr?)? ;
+//!
+//! expr ::= assignment | block | logicalOr | ifExpr | whileExpr | matchExpr | 'break' | 'continue' | fnExpr ;
+//!
+//! matchExpr ::= 'match' logicalOr '{' (arm (',' arm)* ','?)? '}' ;
+//!
+//! arm ::= pattern ('if' logicalOr)? '=>' rawExpr ;
+//!
+//! pattern ::= '_' | '-'? NUMBER | STRING | BOOL | IDENT ('(' pattern (',' pattern)* ','? ')')? ;
+//!
+//! whileExpr ::= 'while' logicalOr block ;
+//!
+//! assignment ::= IDENT '=' (block | ifExpr | fnExpr | logicalOr) ;
+//!
+//! logicalOr ::= logicalAnd (or logicalAnd)* ;
+//!
+//! logicalAnd ::= equality (( and | or ) equality)* ;
+//!
+//! equality ::= comparison (( == | != ) comparison)* ;
+//!
+//! comparison ::= bitOr (( < | <= | > | >= ) bitOr)* ;
+//!
+//! bitOr ::= bitXor ('|' bitXor)* ;
+//!
+//! bitXor ::= bitAnd ('^' bitAnd)* ;
+//!
+//! bitAnd ::= shift ('&' shift)* ;
+//!
+//! shift ::= term (( << | >> ) term)* ;
+//!
+//! term ::= factor (( + | - )  factor)* ;
+//!
+//! factor ::= unary (( * | / | % ) factor)* ;
+//!
+//! unary ::= (- | not | ~) unary | power ;
+//!
+//! power ::= call ('**' unary)? ;
+//!
+//! call ::= primary ( '(' args ')' | '[' rawExpr ']' | '.' IDENT | '?' )* ;
+//! A builtin called like a method, `xs.map(f)`, is a call with the receiver first, `map(xs, f)`.
+//!
+//! primary ::= construct | IDENTIFIER | NUMBER | FLOAT | STRING | BOOL | parens | list ;
+//!
+//! parens ::= '(' (rawExpr (',' rawExpr)* ','?)? ')' ;
+//!
+//! construct ::= IDENT '{' (IDENT ':' rawExpr (',' IDENT ':' rawExpr)* ','?)? '}' ;
+//!
+//! list ::= '[' (rawExpr (',' rawExpr)* ','?)? ']' ;
 
 /// Import Chumsky and get to work
 use chumsky::prelude::*;
 
 use crate::{
-    ast::{Expr, Op},
-    function::Function,
-    lexer::Span,
+    ast::{Expr, MatchArm, Op, Pattern, Program, ReplEntry},
+    builtin::Builtin,
+    lexer::{Span, Spanned},
     tipo::Tipo,
     token::Token,
     value::Value,
 };
 
-pub fn expr_parser() -> impl Parser<Token, Expr, Error = Simple<Token>> {
+#[allow(clippy::result_large_err)]
+pub fn expr_parser() -> impl Parser<Token, Expr, Error = Simple<Token>> + Clone {
     recursive(|raw_expr| {
-        let int = select! { Token::Int {value} => value}
-            .map_with_span(|value, location| Expr::Int { value, location });
-        let string = select! { Token::Str {value} => value}
-            .map_with_span(|value, location| Expr::Str { value, location });
-        let bool_ = select! { Token::Bool {value} => value}
-            .map_with_span(|value, location| Expr::Bool { value, location });
-        let unit = just(Token::Unit).map_with_span(|_, location| Expr::Unit(location));
+        // Anything but a `let` or `funk`, those already take the expression after their `;`.
+        let expr = simple_expr_parser(raw_expr.clone());
 
-        let raw_ident = select! {Token::Identifier { value } => value.clone()};
-        let ident = raw_ident
-            .map_with_span(|value: String, location: Span| Expr::Identifier { value, location })
-            .labelled("Identifier");
+        let raw_ident = ident_parser();
+        let funk = funk_parser(raw_expr.clone());
+        let then_expr = then_parser(raw_expr.clone());
 
-        let value = choice((int, string, bool_, unit));
-
-        let grouping = raw_expr
+        // sequence ::= expr (';' rawExpr?)?
+        let sequence = expr
             .clone()
-            .delimited_by(just(Token::LeftParen), just(Token::RightParen));
+            .then(
+                just(Token::SemiColon)
+                    .ignore_then(then_expr.clone())
+                    .or_not(),
+            )
+            .map_with_span(|(first, then), location| match then {
+                Some(then) => Expr::Sequence {
+                    first: Box::new(first),
+                    then: Box::new(then),
+                    location,
+                },
+                None => first,
+            });
 
-        let primary = choice((value, ident, grouping));
+        let let_ = let_parser(initializer_parser(raw_expr.clone()), then_expr.clone());
 
-        let args = raw_expr
-            .clone()
-            .separated_by(just(Token::Comma))
-            .then_ignore(just(Token::Comma).or_not())
-            .delimited_by(just(Token::LeftParen), just(Token::RightParen));
+        // The doc comments aren't part of the funk's location.
+        let funk_decl = doc_parser()
+            .then(
+                just(Token::Funk)
+                    .ignore_then(raw_ident)
+                    .then(funk)
+                    .then(then_expr.clone())
+                    .map_with_span(|parts, location: Span| (parts, location)),
+            )
+            .map(
+                |(doc, (((name, (type_params, params, return_tipo, body)), then), location))| {
+                    Expr::Funk {
+                        doc,
+                        name,
+                        type_params,
+                        params,
+                        return_tipo,
+                        body: Box::new(body),
+                        location,
+                        then: Box::new(then),
+                    }
+                },
+            );
 
-        let call = primary
-            .clone()
-            .then(args.repeated().or_not())
-            .map_with_span(|(initial_callee, args), location: Span| {
-                if let Some(args) = args {
-                    args.into_iter()
-                        .fold(initial_callee, |callee, args_| Expr::Call {
-                            callee: Box::new(callee),
-                            args: args_,
-                            location: location.clone(),
-                        })
-                } else {
-                    initial_callee
-                }
-            });
+        let struct_decl = struct_parser().then(then_expr.clone()).map_with_span(
+            |((name, fields), then), location| Expr::Struct {
+                name,
+                fields,
+                then: Box::new(then),
+                location,
+            },
+        );
 
-        let unary_op = choice((
-            just(Token::Not).to(Op::Not),
-            just(Token::Minus).to(Op::Minus),
-        ));
-
-        let unary = unary_op
-            .repeated()
-            .then(call)
-            .map_with_span(|(ops, expr), location: Span| {
-                ops.into_iter().rev().fold(expr, |acc, op| Expr::Unary {
-                    op,
-                    rhs: Box::new(acc),
-                    location: location.clone(),
-                })
-            });
-        // .foldr(|op, rhs| Expr::Unary {
-        // op,
-        // rhs: Box::new(rhs),
-        // });
+        let enum_decl = enum_parser().then(then_expr.clone()).map_with_span(
+            |((name, type_params, variants), then), location| Expr::Enum {
+                name,
+                type_params,
+                variants,
+                then: Box::new(then),
+                location,
+            },
+        );
 
-        // factor ::= unary (( * | / ) factor)* ;
-        let factor_op = just(Token::RSlash)
-            .to(Op::Divide)
-            .or(just(Token::Star).to(Op::Multiply));
+        choice((let_, funk_decl, struct_decl, enum_decl, sequence))
+    })
+}
 
-        let factor = unary
-            .clone()
-            .then(factor_op.then(unary).repeated())
-            .map_with_span(|(lhs, rhss), location: Span| {
-                rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
-                    lhs: Box::new(acc),
-                    op,
-                    rhs: Box::new(rhs),
-                    location: location.clone(),
-                })
-            });
+// letExpr ::= 'let' 'mut'? (IDENT | '(' IDENT (',' IDENT)* ','? ')') annotation? '=' initializer ';' then
+#[allow(clippy::result_large_err)]
+fn let_parser<I, T>(
+    initializer: I,
+    then: T,
+) -> impl Parser<Token, Expr, Error = Simple<Token>> + Clone
+where
+    I: Parser<Token, Expr, Error = Simple<Token>> + Clone,
+    T: Parser<Token, Expr, Error = Simple<Token>> + Clone,
+{
+    let names = ident_parser()
+        .separated_by(just(Token::Comma))
+        .at_least(1)
+        .then_ignore(just(Token::Comma).or_not())
+        .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+        .map(LetTarget::Tuple);
 
-        // term ::= factor (( + | - )  factor)* ;
-        let term_op = just(Token::Plus)
-            .to(Op::Plus)
-            .or(just(Token::Minus).to(Op::Minus));
+    just(Token::Let)
+        .ignore_then(just(Token::Mut).or_not())
+        .then(ident_parser().map(LetTarget::Name).or(names))
+        .then(annotation_parser().or_not())
+        .then_ignore(just(Token::Equal))
+        .then(initializer)
+        .then_ignore(just(Token::SemiColon))
+        .then(then)
+        .map_with_span(
+            |((((mutable, target), let_tipo), initializer), then), location| match target {
+                LetTarget::Name(name) => Expr::Let {
+                    name,
+                    mutable: mutable.is_some(),
+                    let_tipo,
+                    initializer: Box::new(initializer),
+                    then: Box::new(then),
+                    location,
+                },
+                LetTarget::Tuple(names) => Expr::LetTuple {
+                    names,
+                    mutable: mutable.is_some(),
+                    let_tipo,
+                    initializer: Box::new(initializer),
+                    then: Box::new(then),
+                    location,
+                },
+            },
+        )
+        .labelled("Let Expression")
+}
 
-        let term = factor
-            .clone()
-            .then(term_op.then(factor).repeated())
-            .map_with_span(|(lhs, rhss), location: Span| {
-                rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
-                    lhs: Box::new(acc),
-                    op,
-                    rhs: Box::new(rhs),
-                    location: location.clone(),
-                })
-            });
+/// What a `let` binds.
+enum LetTarget {
+    Name(String),
+    /// The elements of a tuple.
+    Tuple(Vec<String>),
+}
 
-        // comparison ::= term (( < | <= | > | >= ) term)* ;
-        let comparison_op = choice((
-            just(Token::Less).to(Op::Less),
-            just(Token::LessEqual).to(Op::LessEqual),
-            just(Token::Greater).to(Op::GreaterEqual),
-        ));
+/// Parses a parenthesized list, `()` is unit and `(x)` is just `x`
+/// so a tuple of one needs a trailing comma, `(x,)`.
+#[allow(clippy::result_large_err)]
+fn parens_parser<P, T>(item: P) -> impl Parser<Token, Parens<T>, Error = Simple<Token>> + Clone
+where
+    P: Parser<Token, T, Error = Simple<Token>> + Clone,
+{
+    item.separated_by(just(Token::Comma))
+        .then(just(Token::Comma).or_not())
+        .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+        .try_map(
+            |(mut items, trailing), location| match (items.len(), trailing) {
+                (0, Some(_)) => Err(Simple::custom(location, "Expected an element before ','")),
+                (0, None) => Ok(Parens::Unit),
+                (1, None) => Ok(Parens::Grouping(items.remove(0))),
+                _ => Ok(Parens::Tuple(items)),
+            },
+        )
+}
 
-        let comparison = term
-            .clone()
-            .then(comparison_op.then(term).repeated())
-            .map_with_span(|(lhs, rhss), location: Span| {
-                rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
-                    lhs: Box::new(acc),
-                    op,
-                    rhs: Box::new(rhs),
-                    location: location.clone(),
-                })
-            });
+enum Parens<T> {
+    Unit,
+    Grouping(T),
+    Tuple(Vec<T>),
+}
 
-        // equality ::= comparison (( == | != ) comparison)* ;
-        let equality_op = just(Token::EqualEqual)
-            .to(Op::EqualEqual)
-            .or(just(Token::EqualEqual).to(Op::EqualEqual));
+/// Parses a `let` initializer, it can't be a sequence as it's `;` ends the initializer.
+/// A `let` in the initializer can't end in a sequence either.
+// initializer ::= 'let' 'mut'? IDENT annotation? '=' initializer ';' initializer? | expr
+#[allow(clippy::result_large_err)]
+fn initializer_parser<P>(raw_expr: P) -> impl Parser<Token, Expr, Error = Simple<Token>> + Clone
+where
+    P: Parser<Token, Expr, Error = Simple<Token>> + Clone + 'static,
+{
+    recursive(|initializer| {
+        let let_ = let_parser(initializer.clone(), then_parser(initializer));
+        choice((let_, simple_expr_parser(raw_expr)))
+    })
+}
 
-        let equality = comparison
-            .clone()
-            .then(equality_op.then(comparison).repeated())
-            .map_with_span(|(lhs, rhss), location: Span| {
-                rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
-                    lhs: Box::new(acc),
-                    op,
-                    rhs: Box::new(rhs),
-                    location: location.clone(),
-                })
+/// Parses the optional expression after a `let` or `funk`, it's unit when missing.
+#[allow(clippy::result_large_err)]
+fn then_parser<P>(expr: P) -> impl Parser<Token, Expr, Error = Simple<Token>> + Clone
+where
+    P: Parser<Token, Expr, Error = Simple<Token>> + Clone,
+{
+    expr.or_not().map(|e| {
+        e.unwrap_or(Expr::Value {
+            value: Value::Unit,
+            location: 0..0,
+        })
+    })
+}
+
+// annotation ::= ':' tipo
+#[allow(clippy::result_large_err)]
+fn annotation_parser() -> impl Parser<Token, Spanned<Tipo>, Error = Simple<Token>> + Clone {
+    just(Token::Colon)
+        .ignore_then(tipo_parser().map_with_span(|t, span| (t, span)))
+        .labelled("Type Annotation")
+}
+
+/// Parses an expression that isn't a `let`, `funk` or sequence,
+/// `raw_expr` parses the full expressions nested inside it.
+#[allow(clippy::result_large_err)]
+fn simple_expr_parser<P>(raw_expr: P) -> impl Parser<Token, Expr, Error = Simple<Token>> + Clone
+where
+    P: Parser<Token, Expr, Error = Simple<Token>> + Clone + 'static,
+{
+    let int = select! { Token::Int {value} => value}
+        .map_with_span(|value, location| Expr::Int { value, location });
+    let float = select! { Token::Float {value} => value}
+        .map_with_span(|value, location| Expr::Float { value, location });
+    let string = select! { Token::Str {value} => value}
+        .map_with_span(|value, location| Expr::Str { value, location });
+    let bool_ = select! { Token::Bool {value} => value}
+        .map_with_span(|value, location| Expr::Bool { value, location });
+    let raw_ident = ident_parser();
+    let ident = raw_ident
+        .map_with_span(|value: String, location: Span| Expr::Identifier { value, location })
+        .labelled("Identifier");
+
+    let value = choice((int, float, string, bool_));
+
+    // parens ::= '(' (rawExpr (',' rawExpr)* ','?)? ')'
+    let parens = parens_parser(raw_expr.clone()).map_with_span(|parens, location| match parens {
+        Parens::Unit => Expr::Unit(location),
+        Parens::Grouping(expr) => expr,
+        Parens::Tuple(elements) => Expr::Tuple { elements, location },
+    });
+
+    // list ::= '[' (rawExpr (',' rawExpr)* ','?)? ']'
+    let list = raw_expr
+        .clone()
+        .separated_by(just(Token::Comma))
+        .then_ignore(just(Token::Comma).or_not())
+        .delimited_by(just(Token::LeftBracket), just(Token::RightBracket))
+        .map_with_span(|elements, location| Expr::List { elements, location })
+        .labelled("List");
+
+    // Tried before a plain identifier, `if x { y }` isn't a struct literal as `y` isn't followed by a `:`.
+    let construct = raw_ident
+        .then(
+            raw_ident
+                .then_ignore(just(Token::Colon))
+                .then(raw_expr.clone())
+                .separated_by(just(Token::Comma))
+                .then_ignore(just(Token::Comma).or_not())
+                .delimited_by(just(Token::LeftBrace), just(Token::RightBrace)),
+        )
+        .map_with_span(|(name, fields), location| Expr::Construct {
+            name,
+            fields,
+            location,
+        })
+        .labelled("Struct Literal");
+
+    let primary = choice((value, construct, ident, parens, list));
+
+    let args = raw_expr
+        .clone()
+        .separated_by(just(Token::Comma))
+        .then_ignore(just(Token::Comma).or_not())
+        .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+        .map(Postfix::Call);
+
+    let index = raw_expr
+        .clone()
+        .delimited_by(just(Token::LeftBracket), just(Token::RightBracket))
+        .map(Postfix::Index);
+
+    let field = just(Token::Dot).ignore_then(raw_ident).map(Postfix::Field);
+
+    let try_ = just(Token::Question).map(|_| Postfix::Try);
+
+    // call ::= primary ( '(' args ')' | '[' rawExpr ']' | '.' IDENT | '?' )*
+    // Each postfix expression spans from the primary to the end of the postfix.
+    let call = primary
+        .clone()
+        .then(
+            choice((args, index, field, try_))
+                .map_with_span(|postfix, span: Span| (postfix, span.end))
+                .repeated(),
+        )
+        .map_with_span(|(initial, postfixes), span: Span| {
+            postfixes.into_iter().fold(initial, |expr, (postfix, end)| {
+                let location = span.start..end;
+                match postfix {
+                    Postfix::Call(args) => match expr {
+                        Expr::Field {
+                            expr: receiver,
+                            field,
+                            location: field_location,
+                        } if Builtin::from_name(&field).is_some() => Expr::Call {
+                            callee: Box::new(Expr::Identifier {
+                                value: field,
+                                location: field_location,
+                            }),
+                            args: std::iter::once(*receiver).chain(args).collect(),
+                            location,
+                        },
+                        expr => Expr::Call {
+                            callee: Box::new(expr),
+                            args,
+                            location,
+                        },
+                    },
+                    Postfix::Index(index) => Expr::Index {
+                        list: Box::new(expr),
+                        index: Box::new(index),
+                        location,
+                    },
+                    Postfix::Field(field) => Expr::Field {
+                        expr: Box::new(expr),
+                        field,
+                        location,
+                    },
+                    Postfix::Try => Expr::Try {
+                        expr: Box::new(expr),
+                        location,
+                    },
+                }
             })
-            .labelled("equality");
+        });
 
-        // logicalAnd ::= equality (( and | or ) equality)* ;
-        let logical_and = equality
-            .clone()
-            .then(just(Token::And).to(Op::And).then(equality).repeated())
-            .map_with_span(|(lhs, rhss), location: Span| {
-                rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
-                    lhs: Box::new(acc),
-                    op,
-                    rhs: Box::new(rhs),
-                    location: location.clone(),
+    let unary_op = choice((
+        just(Token::Not).to(Op::Not),
+        just(Token::Minus).to(Op::Minus),
+        just(Token::Tilde).to(Op::BitNot),
+    ));
+
+    // power ::= call ('**' unary)? ;
+    // The exponent is a unary so `**` groups to the right and `2 ** -1` works.
+    let power =
+        recursive(|power| {
+            let exponent = unary_op.clone().repeated().then(power).map_with_span(
+                |(ops, expr), location: Span| {
+                    ops.into_iter().rev().fold(expr, |acc, op| Expr::Unary {
+                        op,
+                        rhs: Box::new(acc),
+                        location: location.clone(),
+                    })
+                },
+            );
+
+            call.clone()
+                .then(just(Token::StarStar).ignore_then(exponent).or_not())
+                .map_with_span(|(lhs, rhs), location: Span| match rhs {
+                    Some(rhs) => Expr::Binary {
+                        lhs: Box::new(lhs),
+                        op: Op::Power,
+                        rhs: Box::new(rhs),
+                        location,
+                    },
+                    None => lhs,
                 })
+        })
+        .boxed();
+
+    let unary = unary_op
+        .repeated()
+        .then(power)
+        .map_with_span(|(ops, expr), location: Span| {
+            ops.into_iter().rev().fold(expr, |acc, op| Expr::Unary {
+                op,
+                rhs: Box::new(acc),
+                location: location.clone(),
             })
-            .labelled("logical_and");
+        });
+    // .foldr(|op, rhs| Expr::Unary {
+    // op,
+    // rhs: Box::new(rhs),
+    // });
 
-        // logicalOr ::= logicalAnd (or logicalAnd)* ;
-        let logical_or = logical_and
-            .clone()
-            .then(just(Token::Or).to(Op::Or).then(logical_and).repeated())
-            .map_with_span(|(lhs, rhss), location: Span| {
-                rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
-                    lhs: Box::new(acc),
-                    op,
-                    rhs: Box::new(rhs),
-                    location: location.clone(),
-                })
+    // factor ::= unary (( * | / | % ) factor)* ;
+    let factor_op = choice((
+        just(Token::RSlash).to(Op::Divide),
+        just(Token::Star).to(Op::Multiply),
+        just(Token::Percent).to(Op::Modulo),
+    ));
+
+    let factor = unary
+        .clone()
+        .then(factor_op.then(unary).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
             })
-            .labelled("logical_or");
+        });
 
-        let block = raw_expr
-            .clone()
-            .delimited_by(just(Token::LeftBrace), just(Token::RightBrace))
-            .map_with_span(|e, location| Expr::Block {
-                expr: Box::new(e),
-                location,
+    // term ::= factor (( + | - )  factor)* ;
+    let term_op = just(Token::Plus)
+        .to(Op::Plus)
+        .or(just(Token::Minus).to(Op::Minus));
+
+    let term = factor
+        .clone()
+        .then(term_op.then(factor).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
             })
-            .labelled("block");
+        })
+        .boxed();
 
-        let else_block = just(Token::Else)
-            .ignore_then(block.clone())
-            .or_not()
-            .map_with_span(|maybe_block, location| {
-                if let Some(block) = maybe_block {
-                    block
-                } else {
-                    Expr::Value {
-                        value: Value::Unit,
-                        location,
-                    }
-                }
+    // shift ::= term (( << | >> ) term)* ;
+    // The lexer never joins `>>` so nested type arguments close, two touching `>` are a shift.
+    let shift_right = just(Token::Greater)
+        .map_with_span(|_, span: Span| span)
+        .then(just(Token::Greater).map_with_span(|_, span: Span| span))
+        .try_map(|(first, second), span| {
+            if first.end == second.start {
+                Ok(Op::ShiftRight)
+            } else {
+                Err(Simple::custom(span, "Expected `>>` without a space"))
+            }
+        });
+
+    let shift_op = just(Token::LessLess).to(Op::ShiftLeft).or(shift_right);
+
+    let shift = term
+        .clone()
+        .then(shift_op.then(term).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
             })
-            .labelled("Else Block");
-
-        let if_ = just(Token::If)
-            .ignore_then(logical_or.clone())
-            .then(block.clone())
-            .then(else_block)
-            .map_with_span(
-                |((condition, truthy_branch), falsy_branch), location| Expr::If {
-                    condition: Box::new(condition),
-                    truthy_branch: Box::new(truthy_branch),
-                    falsy_branch: Box::new(falsy_branch),
-                    location,
-                },
-            )
-            .labelled("If Expression");
-
-        let tipo = recursive(|raw_tipo| {
-            just(Token::Fn)
-                .ignore_then(
-                    raw_tipo
-                        .clone()
-                        .separated_by(just(Token::Comma))
-                        .then_ignore(just(Token::Comma).or_not())
-                        .delimited_by(just(Token::LeftParen), just(Token::RightParen))
-                        .then_ignore(just(Token::RArrow))
-                        .then(raw_tipo.clone())
-                        .map(|(args, ret): (Vec<Tipo>, Tipo)| Tipo::new_fn(args, ret)),
-                )
-                .or(raw_ident.map(|name| Tipo::new(name.as_str())))
         });
 
-        // annotation ::= ':' IDENT
-        let annotation = just(Token::Colon)
-            .ignore_then(tipo.clone())
-            .labelled("Type Annotation");
+    // bitAnd ::= shift ('&' shift)* ;
+    let bit_and = shift
+        .clone()
+        .then(just(Token::Ampersand).to(Op::BitAnd).then(shift).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
+            })
+        });
 
-        let then_expr = raw_expr.clone().or_not().map(|e| {
-            e.clone().unwrap_or(Expr::Value {
-                value: Value::Unit,
-                location: 0..0,
+    // bitXor ::= bitAnd ('^' bitAnd)* ;
+    let bit_xor = bit_and
+        .clone()
+        .then(just(Token::Caret).to(Op::BitXor).then(bit_and).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
             })
         });
 
-        // letExpr ::= 'let' IDENT '=' Expr ; Expr
-        let let_ = just(Token::Let)
-            .ignore_then(raw_ident)
-            .then(annotation.clone().or_not())
-            .then_ignore(just(Token::Equal))
-            .then(raw_expr.clone())
-            .then_ignore(just(Token::SemiColon))
-            .then(then_expr.clone())
-            .map_with_span(
-                |(((name, let_tipo), initializer), then), location| Expr::Let {
-                    name,
-                    let_tipo,
-                    initializer: Box::new(initializer),
-                    then: Box::new(then),
+    // bitOr ::= bitXor ('|' bitXor)* ;
+    let bit_or = bit_xor
+        .clone()
+        .then(just(Token::Pipe).to(Op::BitOr).then(bit_xor).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
+            })
+        })
+        .boxed();
+
+    // comparison ::= bitOr (( < | <= | > | >= ) bitOr)* ;
+    let comparison_op = choice((
+        just(Token::Less).to(Op::Less),
+        just(Token::LessEqual).to(Op::LessEqual),
+        just(Token::Greater).to(Op::Greater),
+        just(Token::GreaterEqual).to(Op::GreaterEqual),
+    ));
+
+    let comparison = bit_or
+        .clone()
+        .then(comparison_op.then(bit_or).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
+            })
+        });
+
+    // equality ::= comparison (( == | != ) comparison)* ;
+    let equality_op = just(Token::EqualEqual)
+        .to(Op::EqualEqual)
+        .or(just(Token::NotEqual).to(Op::NotEqual));
+
+    let equality = comparison
+        .clone()
+        .then(equality_op.then(comparison).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
+            })
+        })
+        .labelled("equality")
+        // Boxing keeps the nested parser types, and their stack frames in debug builds, small.
+        .boxed();
+
+    // logicalAnd ::= equality (( and | or ) equality)* ;
+    let logical_and = equality
+        .clone()
+        .then(just(Token::And).to(Op::And).then(equality).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
+            })
+        })
+        .labelled("logical_and");
+
+    // logicalOr ::= logicalAnd (or logicalAnd)* ;
+    let logical_or = logical_and
+        .clone()
+        .then(just(Token::Or).to(Op::Or).then(logical_and).repeated())
+        .map_with_span(|(lhs, rhss), location: Span| {
+            rhss.into_iter().fold(lhs, |acc, (op, rhs)| Expr::Binary {
+                lhs: Box::new(acc),
+                op,
+                rhs: Box::new(rhs),
+                location: location.clone(),
+            })
+        })
+        .labelled("logical_or");
+
+    let block = raw_expr
+        .clone()
+        .delimited_by(just(Token::LeftBrace), just(Token::RightBrace))
+        .map_with_span(|e, location| Expr::Block {
+            expr: Box::new(e),
+            location,
+        })
+        .labelled("block");
+
+    let else_block = just(Token::Else)
+        .ignore_then(block.clone())
+        .or_not()
+        .map_with_span(|maybe_block, location| {
+            if let Some(block) = maybe_block {
+                block
+            } else {
+                Expr::Value {
+                    value: Value::Unit,
                     location,
+                }
+            }
+        })
+        .labelled("Else Block");
+
+    let if_ = just(Token::If)
+        .ignore_then(logical_or.clone())
+        .then(block.clone())
+        .then(else_block)
+        .map_with_span(
+            |((condition, truthy_branch), falsy_branch), location| Expr::If {
+                condition: Box::new(condition),
+                truthy_branch: Box::new(truthy_branch),
+                falsy_branch: Box::new(falsy_branch),
+                location,
+            },
+        )
+        .labelled("If Expression");
+
+    let funk = funk_parser(raw_expr.clone());
+
+    // whileExpr ::= 'while' logicalOr block
+    let while_ = just(Token::While)
+        .ignore_then(logical_or.clone())
+        .then(block.clone())
+        .map_with_span(|(condition, body), location| Expr::While {
+            condition: Box::new(condition),
+            body: Box::new(body),
+            location,
+        })
+        .labelled("While Loop");
+
+    // matchExpr ::= 'match' logicalOr '{' (arm (',' arm)* ','?)? '}'
+    let arm = pattern_parser()
+        .then(just(Token::If).ignore_then(logical_or.clone()).or_not())
+        .then_ignore(just(Token::FatArrow))
+        .then(raw_expr.clone())
+        .map_with_span(|((pattern, guard), body), location| MatchArm {
+            pattern,
+            guard,
+            body,
+            location,
+        });
+
+    let match_ = just(Token::Match)
+        .ignore_then(logical_or.clone())
+        .then(
+            arm.separated_by(just(Token::Comma))
+                .then_ignore(just(Token::Comma).or_not())
+                .delimited_by(just(Token::LeftBrace), just(Token::RightBrace)),
+        )
+        .map_with_span(|(scrutinee, arms), location| Expr::Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            location,
+        })
+        .labelled("Match Expression");
+
+    let break_ = just(Token::Break).map_with_span(|_, location| Expr::Break(location));
+    let continue_ = just(Token::Continue).map_with_span(|_, location| Expr::Continue(location));
+
+    let fn_ = just(Token::Fn).ignore_then(funk.clone()).map_with_span(
+        |(type_params, params, return_tipo, body), location| Expr::Fn {
+            type_params,
+            params,
+            return_tipo,
+            body: Box::new(body),
+            location,
+        },
+    );
+
+    // assignment ::= IDENT '=' (block | ifExpr | fnExpr | logicalOr)
+    let assignment = ident_parser()
+        .then_ignore(just(Token::Equal))
+        .then(choice((
+            block.clone(),
+            if_.clone(),
+            fn_.clone(),
+            logical_or.clone(),
+        )))
+        .map_with_span(|(name, value), location| Expr::Assign {
+            name,
+            value: Box::new(value),
+            location,
+        })
+        .labelled("Assignment");
+
+    choice((
+        assignment, block, logical_or, if_, while_, match_, break_, continue_, fn_,
+    ))
+    .boxed()
+}
+
+/// Parses a `match` arm's pattern, a name followed by arguments is a constructor.
+// pattern ::= '_' | '-'? NUMBER | STRING | BOOL | IDENT ('(' pattern (',' pattern)* ','? ')')?
+#[allow(clippy::result_large_err)]
+fn pattern_parser() -> impl Parser<Token, Pattern, Error = Simple<Token>> + Clone {
+    recursive(|pattern| {
+        let int = just(Token::Minus)
+            .or_not()
+            .then(select! { Token::Int {value} => value})
+            .map_with_span(|(minus, value), location| Expr::Int {
+                value: match minus {
+                    Some(_) => format!("-{value}"),
+                    None => value,
                 },
-            )
-            .labelled("Let Expression");
+                location,
+            });
+        let string = select! { Token::Str {value} => value}
+            .map_with_span(|value, location| Expr::Str { value, location });
+        let bool_ = select! { Token::Bool {value} => value}
+            .map_with_span(|value, location| Expr::Bool { value, location });
 
-        // params ::= ( ((ident annotation) (',' ident annotation)* ','?)?   )
-        let params = raw_ident
-            .clone()
-            .then(annotation.clone())
+        let literal = choice((int, string, bool_)).map(Pattern::Literal);
+
+        let args = pattern
             .separated_by(just(Token::Comma))
             .then_ignore(just(Token::Comma).or_not())
-            .delimited_by(just(Token::LeftParen), just(Token::RightParen))
-            .labelled("Function Parameters");
+            .delimited_by(just(Token::LeftParen), just(Token::RightParen));
 
-        // funkAnnotation ::= '->' IDENT
-        let return_annotation = just(Token::RArrow)
-            .ignore_then(tipo.clone())
-            .or_not()
-            .labelled("Funk Type Annotation");
-
-        // funkDecl ::= funk IDENT params block
-        let funk = params
-            .then(return_annotation)
-            .then(block.clone())
-            .map(|((params, ret), body)| (params, ret.unwrap_or(Tipo::unit_type()), body))
-            .labelled("Function body");
-
-        let funk_decl = just(Token::Funk)
-            .ignore_then(raw_ident.clone())
-            .then(funk.clone())
-            .then(then_expr.clone())
-            .map_with_span(
-                |((name, (params, return_tipo, body)), then): (
-                    (String, (Vec<(String, Tipo)>, Tipo, Expr)),
-                    Expr,
-                ),
-                 location| {
-                    Expr::Funk {
-                        name,
-                        params,
-                        return_tipo,
-                        body: Box::new(body),
-                        location,
-                        then: Box::new(then),
-                    }
+        let named = ident_parser()
+            .then(args.or_not())
+            .map_with_span(|(name, args), location| match args {
+                Some(args) => Pattern::Constructor {
+                    name,
+                    args,
+                    location,
                 },
-            );
+                None if name == "_" => Pattern::Wildcard(location),
+                None => Pattern::Binding { name, location },
+            });
+
+        choice((literal, named)).labelled("Pattern")
+    })
+}
+
+/// Parses a whole source file, a list of top-level `funk`, `struct` and `enum` declarations.
+/// Top-level declarations don't have a following expression so their `then` is unit.
+#[allow(clippy::result_large_err)]
+pub fn program_parser() -> impl Parser<Token, Program, Error = Simple<Token>> {
+    choice((funk_item_parser(), struct_item_parser(), enum_item_parser()))
+        .repeated()
+        .then_ignore(end())
+        .map_with_span(|items, location| Program { items, location })
+}
+
+/// Parses a line of REPL input, definitions are tried before expressions
+/// so `let x = 1;` defines `x` while `let x = 1; x` is an expression.
+// entry ::= 'let' 'mut'? IDENT annotation? '=' Expr ';'? | funkItem | structItem | enumItem | Expr ;
+#[allow(clippy::result_large_err)]
+pub fn repl_parser() -> impl Parser<Token, ReplEntry, Error = Simple<Token>> {
+    let let_item = just(Token::Let)
+        .ignore_then(just(Token::Mut).or_not())
+        .then(ident_parser())
+        .then(annotation_parser().or_not())
+        .then_ignore(just(Token::Equal))
+        .then(initializer_parser(expr_parser()))
+        .then_ignore(just(Token::SemiColon).or_not())
+        .map_with_span(
+            |(((mutable, name), let_tipo), initializer), location: Span| Expr::Let {
+                name,
+                mutable: mutable.is_some(),
+                let_tipo,
+                initializer: Box::new(initializer),
+                then: Box::new(Expr::Unit(location.end..location.end)),
+                location,
+            },
+        )
+        .labelled("Let Definition");
 
-        let fn_ = just(Token::Fn).ignore_then(funk).map_with_span(
-            |(params, return_tipo, body), location| Expr::Fn {
+    let definition = choice((
+        let_item,
+        funk_item_parser(),
+        struct_item_parser(),
+        enum_item_parser(),
+    ))
+    .then_ignore(end())
+    .map(ReplEntry::Definition);
+
+    let expr = expr_parser().then_ignore(end()).map(ReplEntry::Expr);
+
+    choice((definition, expr))
+}
+
+// funkItem ::= DOC_COMMENT* funk IDENT typeParams? params block
+#[allow(clippy::result_large_err)]
+fn funk_item_parser() -> impl Parser<Token, Expr, Error = Simple<Token>> {
+    doc_parser()
+        .then(
+            just(Token::Funk)
+                .ignore_then(ident_parser())
+                .then(funk_parser(expr_parser()))
+                .map_with_span(|parts, location: Span| (parts, location)),
+        )
+        .map(
+            |(doc, ((name, (type_params, params, return_tipo, body)), location))| Expr::Funk {
+                doc,
+                name,
+                type_params,
                 params,
                 return_tipo,
                 body: Box::new(body),
+                then: Box::new(Expr::Unit(location.end..location.end)),
                 location,
             },
-        );
+        )
+        .labelled("Top-level Funk")
+}
+
+/// Parses the `///` comments above a funk into it's doc.
+/// A single space after the slashes is dropped from each line.
+// docs ::= DOC_COMMENT*
+#[allow(clippy::result_large_err)]
+fn doc_parser() -> impl Parser<Token, Option<String>, Error = Simple<Token>> + Clone {
+    select! {Token::DocComment { value } => value}
+        .repeated()
+        .map(|lines: Vec<String>| {
+            let lines: Vec<&str> = lines
+                .iter()
+                .map(|line| line.strip_prefix(' ').unwrap_or(line))
+                .collect();
+
+            (!lines.is_empty()).then(|| lines.join("\n"))
+        })
+}
+
+// structItem ::= 'struct' IDENT '{' fields '}'
+#[allow(clippy::result_large_err)]
+fn struct_item_parser() -> impl Parser<Token, Expr, Error = Simple<Token>> {
+    struct_parser()
+        .map_with_span(|(name, fields), location: Span| Expr::Struct {
+            name,
+            fields,
+            then: Box::new(Expr::Unit(location.end..location.end)),
+            location,
+        })
+        .labelled("Top-level Struct")
+}
+
+/// Parses a struct's name and it's fields.
+// struct ::= 'struct' IDENT '{' (IDENT ':' tipo (',' IDENT ':' tipo)* ','?)? '}'
+#[allow(clippy::result_large_err)]
+fn struct_parser(
+) -> impl Parser<Token, (String, Vec<(String, Tipo)>), Error = Simple<Token>> + Clone {
+    let field = ident_parser()
+        .then_ignore(just(Token::Colon))
+        .then(tipo_parser());
+
+    just(Token::Struct)
+        .ignore_then(ident_parser())
+        .then(
+            field
+                .separated_by(just(Token::Comma))
+                .then_ignore(just(Token::Comma).or_not())
+                .delimited_by(just(Token::LeftBrace), just(Token::RightBrace)),
+        )
+        .labelled("Struct Declaration")
+}
 
-        choice((block, let_, logical_or, if_, funk_decl, fn_))
+// enumItem ::= 'enum' IDENT typeParams? '{' variants '}'
+#[allow(clippy::result_large_err)]
+fn enum_item_parser() -> impl Parser<Token, Expr, Error = Simple<Token>> {
+    enum_parser()
+        .map_with_span(|(name, type_params, variants), location: Span| Expr::Enum {
+            name,
+            type_params,
+            variants,
+            then: Box::new(Expr::Unit(location.end..location.end)),
+            location,
+        })
+        .labelled("Top-level Enum")
+}
+
+type EnumParts = (String, Vec<String>, Vec<(String, Vec<Tipo>)>);
+
+/// Parses an enum's name, type parameters and variants.
+// enum ::= 'enum' IDENT typeParams? '{' (variant (',' variant)* ','?)? '}'
+#[allow(clippy::result_large_err)]
+fn enum_parser() -> impl Parser<Token, EnumParts, Error = Simple<Token>> + Clone {
+    // variant ::= IDENT ('(' tipo (',' tipo)* ','? ')')?
+    let variant = ident_parser().then(
+        tipo_parser()
+            .separated_by(just(Token::Comma))
+            .then_ignore(just(Token::Comma).or_not())
+            .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+            .or_not()
+            .map(Option::unwrap_or_default),
+    );
+
+    just(Token::Enum)
+        .ignore_then(ident_parser())
+        .then(type_params_parser())
+        .then(
+            variant
+                .separated_by(just(Token::Comma))
+                .then_ignore(just(Token::Comma).or_not())
+                .delimited_by(just(Token::LeftBrace), just(Token::RightBrace)),
+        )
+        .map(|((name, type_params), variants)| (name, type_params, variants))
+        .labelled("Enum Declaration")
+}
+
+// typeParams ::= '<' IDENT (',' IDENT)* ','? '>'
+#[allow(clippy::result_large_err)]
+fn type_params_parser() -> impl Parser<Token, Vec<String>, Error = Simple<Token>> + Clone {
+    ident_parser()
+        .separated_by(just(Token::Comma))
+        .at_least(1)
+        .then_ignore(just(Token::Comma).or_not())
+        .delimited_by(just(Token::Less), just(Token::Greater))
+        .or_not()
+        .map(Option::unwrap_or_default)
+        .labelled("Type Parameters")
+}
+
+/// What can follow a primary expression.
+enum Postfix {
+    Call(Vec<Expr>),
+    Index(Expr),
+    Field(String),
+    Try,
+}
+
+type FunkParts = (
+    Vec<String>,
+    Vec<(String, Option<Spanned<Tipo>>)>,
+    Option<Spanned<Tipo>>,
+    Expr,
+);
+
+#[allow(clippy::result_large_err)]
+fn ident_parser() -> impl Parser<Token, String, Error = Simple<Token>> + Copy {
+    select! {Token::Identifier { value } => value.clone()}
+}
+
+/// Parses a type annotation.
+// tipo ::= 'fn' '(' tipo* ')' '->' tipo | IDENT ('<' tipo (',' tipo)* '>')? | '(' (tipo (',' tipo)* ','?)? ')'
+#[allow(clippy::result_large_err)]
+pub fn tipo_parser() -> impl Parser<Token, Tipo, Error = Simple<Token>> + Clone {
+    recursive(|raw_tipo| {
+        let tipo_args = raw_tipo
+            .clone()
+            .separated_by(just(Token::Comma))
+            .at_least(1)
+            .then_ignore(just(Token::Comma).or_not())
+            .delimited_by(just(Token::Less), just(Token::Greater));
+
+        just(Token::Fn)
+            .ignore_then(
+                raw_tipo
+                    .clone()
+                    .separated_by(just(Token::Comma))
+                    .then_ignore(just(Token::Comma).or_not())
+                    .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+                    .then_ignore(just(Token::RArrow))
+                    .then(raw_tipo.clone())
+                    .map(|(args, ret): (Vec<Tipo>, Tipo)| Tipo::new_fn(args, ret)),
+            )
+            .or(ident_parser()
+                .then(tipo_args.or_not())
+                .map(|(name, args)| Tipo::new_app(&name, args.unwrap_or_default())))
+            .or(parens_parser(raw_tipo).map(|parens| match parens {
+                Parens::Unit => Tipo::unit_type(),
+                Parens::Grouping(tipo) => tipo,
+                Parens::Tuple(elements) => Tipo::tuple_type(elements),
+            }))
     })
 }
+
+/// Parses everything after the `funk` name or `fn` keyword:
+/// the type parameters, parameters, return annotation and body.
+#[allow(clippy::result_large_err)]
+fn funk_parser<P>(expr: P) -> impl Parser<Token, FunkParts, Error = Simple<Token>> + Clone
+where
+    P: Parser<Token, Expr, Error = Simple<Token>> + Clone,
+{
+    let raw_ident = ident_parser();
+
+    let block = expr
+        .delimited_by(just(Token::LeftBrace), just(Token::RightBrace))
+        .map_with_span(|e, location| Expr::Block {
+            expr: Box::new(e),
+            location,
+        })
+        .labelled("block");
+
+    // params ::= ( ((ident annotation?) (',' ident annotation?)* ','?)?   )
+    let params = raw_ident
+        .then(annotation_parser().or_not())
+        .separated_by(just(Token::Comma))
+        .then_ignore(just(Token::Comma).or_not())
+        .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+        .labelled("Function Parameters");
+
+    // funkAnnotation ::= '->' IDENT
+    let return_annotation = just(Token::RArrow)
+        .ignore_then(tipo_parser().map_with_span(|t, span| (t, span)))
+        .or_not()
+        .labelled("Funk Type Annotation");
+
+    // funkDecl ::= DOC_COMMENT* funk IDENT typeParams? params block
+    type_params_parser()
+        .then(params)
+        .then(return_annotation)
+        .then(block)
+        .map(|(((type_params, params), ret), body)| (type_params, params, ret, body))
+        .labelled("Function body")
+}
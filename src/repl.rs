@@ -0,0 +1,350 @@
+//! An interactive REPL.
+//!
+//! Every entry is compiled into it's own chunk and run on the same VM.
+//! `let` and `funk` definitions stay on the VM's stack as locals of the script,
+//! the `TypeChecker` and `Compiler` keep them in scope for later entries.
+//! Entries share one span space, each is offset past the end of the one before it
+//! so errors can be reported against whichever entry they came from.
+use std::io::{BufRead, Write};
+
+use chumsky::{error::Simple, Parser};
+
+use crate::{
+    ast::{Expr, ReplEntry},
+    compiler::Compiler,
+    diagnostic::{Diagnostic, ToDiagnostic},
+    lexer::lexer,
+    parser,
+    typechecker::TypeChecker,
+    value::Value,
+    vm::{chunk::Chunk, VM},
+};
+
+const PROMPT: &str = "pico> ";
+const CONTINUE_PROMPT: &str = "  ... ";
+
+const HELP: &str = "\
+Enter an expression to evaluate it, `let`, `funk`, `struct` and `enum` definitions are kept for later entries.
+
+:type <expr>     Print the type of an expression
+:disasm <expr>   Print the bytecode of an expression
+:ast <expr>      Print the syntax tree of an expression
+:reset           Forget every definition
+:help            Print this message
+:quit            Exit the REPL";
+
+pub struct Repl {
+    checker: TypeChecker,
+    compiler: Compiler,
+    vm: VM,
+    /// The source of every entry that's been kept, with the offset it's spans start at.
+    entries: Vec<(usize, String)>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            checker: TypeChecker::new(),
+            compiler: Compiler::new(),
+            vm: VM::new(Chunk::new()),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Reads entries from `input` until it ends or `:quit` is entered.
+    /// Entries with unbalanced brackets carry on over the next lines.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        let mut entry = String::new();
+
+        loop {
+            let prompt = if entry.is_empty() {
+                PROMPT
+            } else {
+                CONTINUE_PROMPT
+            };
+            write!(output, "{prompt}")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                return Ok(());
+            }
+
+            entry.push_str(&line);
+            if is_unbalanced(&entry) {
+                continue;
+            }
+
+            let source = std::mem::take(&mut entry);
+            let source = source.trim();
+
+            match source {
+                "" => {}
+                ":quit" | ":q" => return Ok(()),
+                _ => match self.eval(source) {
+                    Ok(out) if out.is_empty() => {}
+                    Ok(out) => writeln!(output, "{out}")?,
+                    Err(err) => write!(output, "{err}")?,
+                },
+            }
+        }
+    }
+
+    /// Evaluates one entry or meta-command, returning what should be printed.
+    /// Errors are returned as rendered diagnostics.
+    pub fn eval(&mut self, source: &str) -> Result<String, String> {
+        let entry_count = self.entries.len();
+
+        if let Some(command) = source.strip_prefix(':') {
+            let (command, arg) = command
+                .split_once(char::is_whitespace)
+                .unwrap_or((command, ""));
+            let result = self.meta_command(command, arg.trim());
+
+            // Nothing defined by a meta-command is kept so neither is it's source.
+            self.entries.truncate(entry_count);
+            return result;
+        }
+
+        // Failed entries leave no trace, the state is restored from before the entry.
+        let checker = self.checker.clone();
+        let compiler = self.compiler.clone();
+        let stack_len = self.vm.values.len();
+
+        let result = self.eval_entry(source);
+
+        if result.is_err() {
+            self.checker = checker;
+            self.compiler = compiler;
+            self.vm.reset_stack(stack_len);
+            self.entries.truncate(entry_count);
+        }
+
+        result
+    }
+
+    fn eval_entry(&mut self, source: &str) -> Result<String, String> {
+        let entry = self.parse(source)?;
+        let mut chunky = Chunk::new();
+        let stack_len = self.vm.values.len();
+
+        match entry {
+            // Structs and enums don't have a value so there's nothing to run.
+            ReplEntry::Definition(definition @ (Expr::Struct { .. } | Expr::Enum { .. })) => {
+                let (name, _) = self
+                    .checker
+                    .check_definition(&definition)
+                    .map_err(|err| self.render(&err))?;
+                self.compiler
+                    .compile_definition(&mut chunky, &definition)
+                    .map_err(|err| self.render(&err))?;
+
+                let keyword = match definition {
+                    Expr::Struct { .. } => "struct",
+                    _ => "enum",
+                };
+                Ok(format!("{keyword} {name}"))
+            }
+            ReplEntry::Definition(definition) => {
+                let (name, scheme) = self
+                    .checker
+                    .check_definition(&definition)
+                    .map_err(|err| self.render(&err))?;
+                self.compiler
+                    .set_field_indices(self.checker.take_field_indices());
+                self.compiler
+                    .compile_definition(&mut chunky, &definition)
+                    .map_err(|err| self.render(&err))?;
+                self.vm.run_chunk(chunky).map_err(|err| self.render(&err))?;
+
+                Ok(format!("{name} : {scheme}"))
+            }
+            ReplEntry::Expr(expr) => {
+                let tipo = self
+                    .checker
+                    .check_all(&expr)
+                    .map_err(|errs| errs.iter().map(|err| self.render(err)).collect::<String>())?;
+                self.compiler
+                    .set_field_indices(self.checker.take_field_indices());
+                self.compiler
+                    .compile_script(&mut chunky, &expr)
+                    .map_err(|err| self.render(&err))?;
+                let value = self.vm.run_chunk(chunky).map_err(|err| self.render(&err))?;
+
+                // The result isn't a definition so it's dropped.
+                self.vm.reset_stack(stack_len);
+
+                match value {
+                    Value::Unit => Ok(String::new()),
+                    value => Ok(format!("{value} : {tipo}")),
+                }
+            }
+        }
+    }
+
+    /// Meta-commands work on copies so they don't change the REPL's state.
+    fn meta_command(&mut self, command: &str, arg: &str) -> Result<String, String> {
+        match command {
+            "type" | "t" => match self.parse(arg)? {
+                ReplEntry::Definition(definition) => {
+                    let (name, scheme) = self
+                        .checker
+                        .clone()
+                        .check_definition(&definition)
+                        .map_err(|err| self.render(&err))?;
+                    Ok(format!("{name} : {scheme}"))
+                }
+                ReplEntry::Expr(expr) => {
+                    let scheme = self
+                        .checker
+                        .clone()
+                        .infer_scheme(&expr)
+                        .map_err(|err| self.render(&err))?;
+                    Ok(scheme.to_string())
+                }
+            },
+            "disasm" => {
+                let mut chunky = Chunk::new();
+                let mut compiler = self.compiler.clone();
+
+                match self.parse(arg)? {
+                    ReplEntry::Definition(definition) => {
+                        compiler.compile_definition(&mut chunky, &definition)
+                    }
+                    ReplEntry::Expr(expr) => compiler.compile_script(&mut chunky, &expr),
+                }
+                .map_err(|err| self.render(&err))?;
+
+                chunky.disassemble(arg);
+                Ok(String::new())
+            }
+            "ast" => match self.parse(arg)? {
+                ReplEntry::Definition(expr) | ReplEntry::Expr(expr) => Ok(format!("{expr:#?}")),
+            },
+            "reset" => {
+                *self = Repl::new();
+                Ok("Forgot every definition.".to_string())
+            }
+            "help" | "h" => Ok(HELP.to_string()),
+            _ => Err(format!("Unknown command ':{command}', try :help.\n")),
+        }
+    }
+
+    /// Parses an entry, keeping it's source so errors in it can be rendered later.
+    fn parse(&mut self, source: &str) -> Result<ReplEntry, String> {
+        let offset = match self.entries.last() {
+            Some((offset, last)) => offset + last.len() + 1,
+            None => 0,
+        };
+        self.entries.push((offset, source.to_string()));
+
+        let tokens = lexer().parse(source).map_err(|errs| {
+            errs.iter()
+                .map(|err| self.render_diagnostic(shift(err, offset)))
+                .collect::<String>()
+        })?;
+        let tokens = tokens
+            .into_iter()
+            .map(|(token, span)| (token, span.start + offset..span.end + offset));
+        let eoi = offset + source.len()..offset + source.len();
+
+        parser::repl_parser()
+            .parse(chumsky::Stream::from_iter(eoi, tokens))
+            .map_err(|errs| errs.iter().map(|err| self.render(err)).collect::<String>())
+    }
+
+    /// Renders an error against the entries it's spans point into.
+    fn render(&self, err: &impl ToDiagnostic) -> String {
+        self.render_diagnostic(err.to_diagnostic())
+    }
+
+    fn render_diagnostic(&self, diagnostic: Diagnostic) -> String {
+        let names: Vec<String> = (1..=self.entries.len())
+            .map(|n| format!("<repl:{n}>"))
+            .collect();
+        let sources: Vec<(&str, usize, &str)> = self
+            .entries
+            .iter()
+            .zip(&names)
+            .map(|((offset, source), name)| (name.as_str(), *offset, source.as_str()))
+            .collect();
+
+        diagnostic.render_sources(&sources)
+    }
+}
+
+/// Moves a lexer error's span past the entries before it.
+fn shift(err: &Simple<char>, offset: usize) -> Diagnostic {
+    let mut diagnostic = err.to_diagnostic();
+    diagnostic.location = diagnostic.location.start + offset..diagnostic.location.end + offset;
+    for label in &mut diagnostic.labels {
+        label.location = label.location.start + offset..label.location.end + offset;
+    }
+    diagnostic
+}
+
+/// Checks if an entry has more opening brackets than closing ones or an unclosed block comment,
+/// ignoring strings and comments.
+fn is_unbalanced(source: &str) -> bool {
+    let mut depth = 0;
+    let mut comment_depth = 0;
+    let mut in_string = false;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if comment_depth > 0 {
+            // Block comments nest like they do in the lexer.
+            match (c, chars.peek()) {
+                ('/', Some('*')) => {
+                    chars.next();
+                    comment_depth += 1;
+                }
+                ('*', Some('/')) => {
+                    chars.next();
+                    comment_depth -= 1;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if in_string {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                // Line comments run to the end of the line.
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                comment_depth += 1;
+            }
+            ('"', _) => in_string = true,
+            ('{' | '(' | '[', _) => depth += 1,
+            ('}' | ')' | ']', _) => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth > 0 || comment_depth > 0
+}
@@ -1,13 +1,37 @@
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Tipo {
-    App { name: String },
-    Fn { args: Vec<Tipo>, ret: Box<Tipo> },
+    App {
+        name: String,
+        args: Vec<Tipo>,
+    },
+    Fn {
+        args: Vec<Tipo>,
+        ret: Box<Tipo>,
+    },
+    /// A type variable, solved by unification in the `TypeChecker`.
+    Var {
+        id: usize,
+    },
+    /// The type given to expressions that failed to type check.
+    /// There's no syntax for it so only the `TypeChecker` can make one.
+    Error,
 }
 
 impl Tipo {
     pub fn new(name: &str) -> Tipo {
         Tipo::App {
             name: name.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Creates an applied type e.g. `list<int>`.
+    pub fn new_app(name: &str, args: Vec<Tipo>) -> Tipo {
+        Tipo::App {
+            name: name.to_string(),
+            args,
         }
     }
 
@@ -18,10 +42,18 @@ impl Tipo {
         }
     }
 
+    pub fn new_var(id: usize) -> Tipo {
+        Tipo::Var { id }
+    }
+
     pub fn int_type() -> Tipo {
         Tipo::new("int")
     }
 
+    pub fn float_type() -> Tipo {
+        Tipo::new("float")
+    }
+
     pub fn string_type() -> Tipo {
         Tipo::new("string")
     }
@@ -30,17 +62,49 @@ impl Tipo {
         Tipo::new("bool")
     }
 
+    pub fn list_type(element: Tipo) -> Tipo {
+        Tipo::new_app("list", vec![element])
+    }
+
+    pub fn option_type(value: Tipo) -> Tipo {
+        Tipo::new_app("option", vec![value])
+    }
+
+    pub fn result_type(value: Tipo, error: Tipo) -> Tipo {
+        Tipo::new_app("result", vec![value, error])
+    }
+
+    /// Creates a tuple type e.g. `(int, string)`.
+    pub fn tuple_type(elements: Vec<Tipo>) -> Tipo {
+        Tipo::new_app("tuple", elements)
+    }
+
+    /// Unit is the empty tuple.
     pub fn unit_type() -> Tipo {
-        Tipo::new("__unit__")
+        Tipo::tuple_type(Vec::new())
+    }
+
+    /// The type given to expressions that failed to type check,
+    /// it's compatible with everything so one mistake doesn't cause a cascade of errors.
+    pub fn error_type() -> Tipo {
+        Tipo::Error
     }
 
     pub fn is_fn(&self) -> bool {
         matches!(self, Tipo::Fn { .. })
     }
 
+    pub fn is_var(&self) -> bool {
+        matches!(self, Tipo::Var { .. })
+    }
+
     pub fn is_int(&self) -> bool {
         *self == Tipo::int_type()
     }
+    pub fn is_float(&self) -> bool {
+        *self == Tipo::float_type()
+    }
+
     pub fn is_string(&self) -> bool {
         *self == Tipo::string_type()
     }
@@ -52,14 +116,116 @@ impl Tipo {
     pub fn is_unit(&self) -> bool {
         *self == Tipo::unit_type()
     }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Tipo::Error)
+    }
+
+    /// Returns the type variables occuring in the type, in order of first appearance.
+    pub fn free_vars(&self) -> Vec<usize> {
+        let mut vars = Vec::new();
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars(&self, vars: &mut Vec<usize>) {
+        match self {
+            Tipo::App { args, .. } => {
+                for arg in args {
+                    arg.collect_vars(vars);
+                }
+            }
+            Tipo::Fn { args, ret } => {
+                for arg in args {
+                    arg.collect_vars(vars);
+                }
+                ret.collect_vars(vars);
+            }
+            Tipo::Var { id } => {
+                if !vars.contains(id) {
+                    vars.push(*id)
+                }
+            }
+            Tipo::Error => {}
+        }
+    }
+
+    /// Checks if the type variable `id` occurs anywhere in the type.
+    pub fn occurs(&self, id: usize) -> bool {
+        match self {
+            Tipo::App { args, .. } => args.iter().any(|a| a.occurs(id)),
+            Tipo::Fn { args, ret } => args.iter().any(|a| a.occurs(id)) || ret.occurs(id),
+            Tipo::Var { id: other } => *other == id,
+            Tipo::Error => false,
+        }
+    }
+
+    /// Replaces every type variable bound in `substitution`, following chains of variables.
+    pub fn apply(&self, substitution: &HashMap<usize, Tipo>) -> Tipo {
+        match self {
+            Tipo::App { name, args } => {
+                Tipo::new_app(name, args.iter().map(|a| a.apply(substitution)).collect())
+            }
+            Tipo::Fn { args, ret } => Tipo::new_fn(
+                args.iter().map(|a| a.apply(substitution)).collect(),
+                ret.apply(substitution),
+            ),
+            Tipo::Var { id } => match substitution.get(id) {
+                Some(tipo) => tipo.apply(substitution),
+                None => self.clone(),
+            },
+            Tipo::Error => Tipo::Error,
+        }
+    }
+
+    /// Replaces the named types in `names`, used to swap a funk's type parameters for type variables.
+    pub fn substitute_names(&self, names: &HashMap<String, Tipo>) -> Tipo {
+        match self {
+            Tipo::App { name, args } if args.is_empty() && names.contains_key(name) => {
+                names[name].clone()
+            }
+            Tipo::App { name, args } => Tipo::new_app(
+                name,
+                args.iter().map(|a| a.substitute_names(names)).collect(),
+            ),
+            Tipo::Fn { args, ret } => Tipo::new_fn(
+                args.iter().map(|a| a.substitute_names(names)).collect(),
+                ret.substitute_names(names),
+            ),
+            Tipo::Var { .. } | Tipo::Error => self.clone(),
+        }
+    }
 }
 
 impl std::fmt::Display for Tipo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Tipo::*;
         match self {
-            App { name } => {
+            App { name, args } if name == "tuple" => match args.as_slice() {
+                [element] => write!(f, "({element},)")?,
+                _ => {
+                    write!(f, "(")?;
+                    for (index, arg) in args.iter().enumerate() {
+                        if index > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{arg}")?;
+                    }
+                    write!(f, ")")?;
+                }
+            },
+            App { name, args } => {
                 write!(f, "{name}")?;
+
+                if !args.is_empty() {
+                    write!(f, "<{}", args[0])?;
+
+                    for arg in &args[1..args.len()] {
+                        write!(f, ", {arg}")?;
+                    }
+
+                    write!(f, ">")?;
+                }
             }
             Fn { args, ret } => {
                 write!(f, "fn")?;
@@ -83,8 +249,80 @@ impl std::fmt::Display for Tipo {
                 };
                 write!(f, " -> {ret}")?;
             }
+            Var { id } => {
+                write!(f, "t{id}")?;
+            }
+            Error => {
+                write!(f, "<error>")?;
+            }
         };
 
         Ok(())
     }
 }
+
+/// A type scheme, a type with universally quantified type variables.
+/// e.g. the identity function has the scheme `forall a. fn(a) -> a`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheme {
+    pub vars: Vec<usize>,
+    pub tipo: Tipo,
+}
+
+impl Scheme {
+    /// Creates a scheme that doesn't quantify any variables.
+    pub fn mono(tipo: Tipo) -> Scheme {
+        Scheme {
+            vars: Vec::new(),
+            tipo,
+        }
+    }
+}
+
+impl Scheme {
+    /// Displays the scheme with the given names for some of it's quantified variables,
+    /// like the type parameters a funk declares. The rest are named like `Display` does,
+    /// skipping the given names.
+    pub fn display_with(&self, given: &HashMap<usize, String>) -> String {
+        if self.vars.is_empty() {
+            return self.tipo.to_string();
+        }
+
+        let mut fresh = (0..)
+            .map(var_name)
+            .filter(|name| !given.values().any(|n| n == name));
+        let names: Vec<String> = self
+            .vars
+            .iter()
+            .map(|id| match given.get(id) {
+                Some(name) => name.clone(),
+                None => fresh.next().expect("There are always more names."),
+            })
+            .collect();
+        let renaming: HashMap<usize, Tipo> = self
+            .vars
+            .iter()
+            .zip(names.iter())
+            .map(|(id, name)| (*id, Tipo::new(name)))
+            .collect();
+
+        format!("forall {}. {}", names.join(" "), self.tipo.apply(&renaming))
+    }
+}
+
+impl std::fmt::Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Rename the quantified variables to `a`, `b`, `c`... for readability.
+        write!(f, "{}", self.display_with(&HashMap::new()))
+    }
+}
+
+/// Returns the name of the `n`th quantified variable: `a`...`z`, `a1`...`z1` and so on.
+fn var_name(n: usize) -> String {
+    let letter = (b'a' + (n % 26) as u8) as char;
+
+    match n / 26 {
+        0 => letter.to_string(),
+        round => format!("{letter}{round}"),
+    }
+}
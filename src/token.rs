@@ -2,16 +2,26 @@
 pub enum Token {
     Identifier { value: String },
     Int { value: String },
+    Float { value: String },
     Str { value: String },
     Bool { value: String },
+    DocComment { value: String },
     Plus,
     Minus,
     RSlash,
     Star,
+    StarStar,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    LessLess,
     Dot,
     Comma,
     Colon,
     SemiColon,
+    Question,
     And,
     Or,
     Not,
@@ -26,11 +36,80 @@ pub enum Token {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     RArrow,
+    FatArrow,
     Funk,
     Let,
+    Mut,
     If,
     Else,
-    Unit,
+    While,
+    Break,
+    Continue,
+    Struct,
+    Enum,
+    Match,
     Fn,
 }
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Token::*;
+
+        match self {
+            Identifier { value } | Int { value } | Float { value } | Bool { value } => {
+                write!(f, "{value}")
+            }
+            Str { value } => write!(f, "\"{value}\""),
+            DocComment { value } => write!(f, "///{value}"),
+            Plus => write!(f, "+"),
+            Minus => write!(f, "-"),
+            RSlash => write!(f, "/"),
+            Star => write!(f, "*"),
+            StarStar => write!(f, "**"),
+            Percent => write!(f, "%"),
+            Ampersand => write!(f, "&"),
+            Pipe => write!(f, "|"),
+            Caret => write!(f, "^"),
+            Tilde => write!(f, "~"),
+            LessLess => write!(f, "<<"),
+            Dot => write!(f, "."),
+            Comma => write!(f, ","),
+            Colon => write!(f, ":"),
+            SemiColon => write!(f, ";"),
+            Question => write!(f, "?"),
+            And => write!(f, "and"),
+            Or => write!(f, "or"),
+            Not => write!(f, "!"),
+            Equal => write!(f, "="),
+            NotEqual => write!(f, "!="),
+            EqualEqual => write!(f, "=="),
+            Less => write!(f, "<"),
+            LessEqual => write!(f, "<="),
+            Greater => write!(f, ">"),
+            GreaterEqual => write!(f, ">="),
+            LeftParen => write!(f, "("),
+            RightParen => write!(f, ")"),
+            LeftBrace => write!(f, "{{"),
+            RightBrace => write!(f, "}}"),
+            LeftBracket => write!(f, "["),
+            RightBracket => write!(f, "]"),
+            RArrow => write!(f, "->"),
+            FatArrow => write!(f, "=>"),
+            Funk => write!(f, "funk"),
+            Let => write!(f, "let"),
+            Mut => write!(f, "mut"),
+            If => write!(f, "if"),
+            Else => write!(f, "else"),
+            While => write!(f, "while"),
+            Break => write!(f, "break"),
+            Continue => write!(f, "continue"),
+            Struct => write!(f, "struct"),
+            Enum => write!(f, "enum"),
+            Match => write!(f, "match"),
+            Fn => write!(f, "fn"),
+        }
+    }
+}
@@ -1,129 +1,853 @@
 /// This module is the core of the TypeChecker it's a huge match statement on the AST.
+/// Types are inferred Hindley-Milner style: unknown types become type variables which are
+/// solved by unification, and `let` bound values are generalized into type schemes.
 use std::collections::HashMap;
 
 use crate::{
-    ast::{Expr, Op},
-    tipo::Tipo,
+    ast::{Expr, MatchArm, Op, Pattern, Program},
+    builtin::{self, Builtin},
+    dependency,
+    diagnostic::{Diagnostic, Label, ToDiagnostic},
+    exhaustiveness::{self, Pat},
+    lexer::{Span, Spanned},
+    tipo::{Scheme, Tipo},
 };
+
+/// The types that exist without being declared, `option` and `result` are builtin enums.
+const BUILTIN_TYPES: [&str; 6] = ["int", "float", "string", "bool", "list", "tuple"];
+
+#[derive(Clone)]
 pub struct TypeChecker {
-    scopes: Vec<HashMap<String, Tipo>>,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// The solutions found so far for type variables.
+    substitution: HashMap<usize, Tipo>,
+    next_var: usize,
+    /// The number of loops around the expression being checked in the current function.
+    loop_depth: usize,
+    /// The return type of the function being checked, `?` returns from it.
+    return_tipo: Option<Tipo>,
+    /// The fields of the structs declared so far, in declaration order.
+    structs: HashMap<String, Vec<(String, Tipo)>>,
+    enums: HashMap<String, EnumDef>,
+    /// The enum each constructor belongs to.
+    constructors: HashMap<String, String>,
+    /// Where each struct and enum was declared.
+    type_locations: HashMap<String, Span>,
+    /// The generic type parameters of the functions around the expression being checked.
+    type_params: Vec<String>,
+    /// The position in it's struct of the field read by each field expression, by location.
+    field_indices: HashMap<Span, usize>,
+    /// Errors recorded while checking in error-accumulating mode,
+    /// `None` when the checker stops at the first error.
+    errors: Option<Vec<TypeError>>,
+}
+
+/// A declared enum, it's variants' field types can refer to it's type parameters by name.
+#[derive(Debug, Clone)]
+struct EnumDef {
+    type_params: Vec<String>,
+    variants: Vec<(String, Vec<Tipo>)>,
 }
 
+/// A variable in scope.
+#[derive(Debug, Clone)]
+struct Binding {
+    scheme: Scheme,
+    /// Declared with `let mut`.
+    mutable: bool,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(clippy::result_large_err)]
 impl TypeChecker {
     pub fn new() -> TypeChecker {
-        TypeChecker {
+        let mut checker = TypeChecker {
             scopes: vec![HashMap::new()],
+            substitution: HashMap::new(),
+            next_var: 0,
+            loop_depth: 0,
+            return_tipo: None,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            constructors: HashMap::new(),
+            type_locations: HashMap::new(),
+            type_params: Vec::new(),
+            field_indices: HashMap::new(),
+            errors: None,
+        };
+
+        for builtin in builtin::enums() {
+            checker
+                .declare_enum(builtin.name, &builtin.type_params, &builtin.variants, 0..0)
+                .expect("The builtin enums don't repeat variants.");
+        }
+        // The builtins aren't declared anywhere in the source.
+        checker.type_locations.clear();
+
+        checker
+    }
+
+    /// Checks the whole expression, reporting every type error instead of stopping at the first.
+    /// The offending subexpressions are given the error type so they don't cause further errors.
+    pub fn check_all(&mut self, expr: &Expr) -> Result<Tipo, Vec<TypeError>> {
+        self.accumulate_errors(|checker| checker.check_expr(expr))
+    }
+
+    /// Like `check_all` but for a whole program.
+    pub fn check_program_all(&mut self, program: &Program) -> Result<Tipo, Vec<TypeError>> {
+        self.accumulate_errors(|checker| checker.check_program(program))
+    }
+
+    fn accumulate_errors<F>(&mut self, check: F) -> Result<Tipo, Vec<TypeError>>
+    where
+        F: FnOnce(&mut Self) -> TypeResult<Tipo>,
+    {
+        self.errors = Some(Vec::new());
+        let result = check(self);
+        let mut errors = self.errors.take().unwrap_or_default();
+
+        match result {
+            Ok(tipo) if errors.is_empty() => Ok(tipo),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.push(e);
+                Err(errors)
+            }
+        }
+    }
+
+    /// Infers the type of an expression and generalizes it,
+    /// e.g. `fn(x) { x }` has the scheme `forall a. fn(a) -> a`.
+    pub fn infer_scheme(&mut self, expr: &Expr) -> TypeResult<Scheme> {
+        let tipo = self.check_expr(expr)?;
+        Ok(self.generalize(&tipo))
+    }
+
+    /// Checks every top-level funk in a program and returns the return type of `main`.
+    /// Top-level funks can refer to each other regardless of the order they're declared in.
+    pub fn check_program(&mut self, program: &Program) -> TypeResult<Tipo> {
+        self.check_items(program)?;
+
+        // `main` takes no arguments, the program evaluates to whatever it returns.
+        let end = program.location.end..program.location.end;
+        let main_tipo =
+            self.get_var_tipo("main", end.clone())
+                .map_err(|_| TypeError::MissingMain {
+                    location: end.clone(),
+                })?;
+        let ret = self.new_var();
+        let main_location = program
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Expr::Funk { name, body, .. } if name == "main" => Some(body.location()),
+                _ => None,
+            })
+            .unwrap_or(end);
+        self.unify_or(
+            &Tipo::new_fn(Vec::new(), ret.clone()),
+            &main_tipo,
+            main_location.clone(),
+            |_, got| TypeError::InvalidMain {
+                got,
+                location: main_location.clone(),
+            },
+        )?;
+
+        Ok(self.resolve(&ret))
+    }
+
+    /// Checks a program's structs, enums and funks without needing a `main`.
+    /// The funks are left in scope with their generalized schemes, see `scheme_of`.
+    pub fn check_items(&mut self, program: &Program) -> TypeResult<()> {
+        for item in &program.items {
+            if let Expr::Struct {
+                name,
+                fields,
+                location,
+                ..
+            } = item
+            {
+                self.declare_struct(name, fields, location.clone())?;
+            }
+
+            if let Expr::Enum {
+                name,
+                type_params,
+                variants,
+                location,
+                ..
+            } = item
+            {
+                self.declare_enum(name, type_params, variants, location.clone())?;
+            }
+        }
+
+        // Fields can name types declared after them.
+        for item in &program.items {
+            self.check_field_tipos(item)?;
+        }
+
+        let funks: Vec<_> = program
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Expr::Funk {
+                    name,
+                    type_params,
+                    params,
+                    return_tipo,
+                    body,
+                    location,
+                    ..
+                } => Some((name, type_params, params, return_tipo, body, location)),
+                _ => None,
+            })
+            .collect();
+
+        for (index, (name, .., location)) in funks.iter().enumerate() {
+            if let Some((.., previous)) = funks[..index].iter().find(|funk| funk.0 == *name) {
+                self.report(TypeError::DuplicateDefinition {
+                    name: name.to_string(),
+                    location: (*location).clone(),
+                    previous: (*previous).clone(),
+                })?;
+            }
+        }
+
+        let mut expected_tipos = Vec::new();
+        for (_, type_params, params, return_tipo, _, location) in &funks {
+            expected_tipos.push(self.expected_fn_tipo(
+                type_params,
+                params,
+                return_tipo,
+                location,
+            )?);
+        }
+
+        // Fully annotated funks are generalized straight away so they can be used polymorphically
+        // and in any order.
+        let annotated: Vec<bool> = funks
+            .iter()
+            .map(|(_, _, params, return_tipo, ..)| {
+                params.iter().all(|(_, t)| t.is_some()) && return_tipo.is_some()
+            })
+            .collect();
+        for (index, (name, type_params, ..)) in funks.iter().enumerate() {
+            if annotated[index] {
+                let tipo = self.bind_type_params(type_params, &expected_tipos[index]);
+                let scheme = self.generalize(&tipo);
+                self.set_var_scheme(name, scheme);
+            }
+        }
+
+        // The rest are checked after the funks they use and generalized before the funks using them.
+        // Funks that use each other are checked together, they're monomorphic in each other's bodies.
+        let bodies: Vec<_> = funks
+            .iter()
+            .map(|(name, _, _, _, body, _)| (name.as_str(), body.as_ref()))
+            .collect();
+        for group in dependency::funk_groups(&bodies) {
+            self.begin_scope();
+            for &index in group.iter().filter(|index| !annotated[**index]) {
+                self.set_var_tipo(funks[index].0, expected_tipos[index].clone());
+            }
+
+            let mut funk_tipos = Vec::new();
+            for &index in &group {
+                let (_, type_params, params, _, body, _) = funks[index];
+                match self.check_funk(type_params, params, &expected_tipos[index], body) {
+                    Ok(tipo) => funk_tipos.push(tipo),
+                    Err(e) => {
+                        self.end_scope();
+                        return Err(e);
+                    }
+                }
+            }
+            self.end_scope();
+
+            for (&index, tipo) in group.iter().zip(funk_tipos.iter()) {
+                let tipo = self.bind_type_params(funks[index].1, tipo);
+                let scheme = self.generalize(&tipo);
+                self.set_var_scheme(funks[index].0, scheme);
+            }
         }
+
+        Ok(())
+    }
+
+    /// Returns the field positions found since the last call, for `Compiler::set_field_indices`.
+    pub fn take_field_indices(&mut self) -> HashMap<Span, usize> {
+        std::mem::take(&mut self.field_indices)
+    }
+
+    /// Returns the scheme of a variable in scope, e.g. a funk after `check_items`.
+    pub fn scheme_of(&self, name: &str) -> Option<Scheme> {
+        let binding = self.get_var(name, 0..0).ok()?;
+
+        Some(Scheme {
+            vars: binding.scheme.vars,
+            tipo: self.resolve(&binding.scheme.tipo),
+        })
+    }
+
+    /// Checks a `let` or `funk` without a following expression, like a REPL definition,
+    /// and keeps it's binding in the current scope. Returns the binding's name and type.
+    /// A `struct` or `enum` is declared for later expressions, it's type is the declared type itself.
+    pub fn check_definition(&mut self, definition: &Expr) -> TypeResult<(String, Scheme)> {
+        let (name, scheme, mutable) = match definition {
+            Expr::Enum {
+                name,
+                type_params,
+                variants,
+                location,
+                ..
+            } => {
+                self.declare_enum(name, type_params, variants, location.clone())?;
+                self.check_field_tipos(definition)?;
+                let tipo = self.bind_type_params(type_params, &enum_tipo(name, type_params));
+                return Ok((name.clone(), self.generalize(&tipo)));
+            }
+            Expr::Struct {
+                name,
+                fields,
+                location,
+                ..
+            } => {
+                self.declare_struct(name, fields, location.clone())?;
+                self.check_field_tipos(definition)?;
+                return Ok((name.clone(), Scheme::mono(Tipo::new(name))));
+            }
+            Expr::Let {
+                name,
+                mutable,
+                let_tipo,
+                initializer,
+                ..
+            } => (
+                name,
+                self.let_scheme(name, *mutable, let_tipo, initializer)?,
+                *mutable,
+            ),
+            Expr::Funk {
+                name,
+                type_params,
+                params,
+                return_tipo,
+                body,
+                location,
+                ..
+            } => (
+                name,
+                self.funk_scheme(name, type_params, params, return_tipo, body, location)?,
+                false,
+            ),
+            expr => {
+                return Err(TypeError::Basic {
+                    message: "Only `let` and `funk` can be definitions.".to_string(),
+                    location: expr.location(),
+                })
+            }
+        };
+
+        self.set_var(name, scheme.clone(), mutable);
+        Ok((name.clone(), scheme))
     }
 
     pub fn check_expr(&mut self, expr: &Expr) -> TypeResult<Tipo> {
+        let result = self.check_expr_inner(expr);
+
+        // In error-accumulating mode record the error and poison the expression.
+        match (result, &mut self.errors) {
+            (Err(e), Some(errors)) => {
+                errors.push(e);
+                Ok(Tipo::error_type())
+            }
+            (result, _) => result.map(|tipo| self.resolve(&tipo)),
+        }
+    }
+
+    fn check_expr_inner(&mut self, expr: &Expr) -> TypeResult<Tipo> {
         match expr {
             Expr::Int { .. } => Ok(Tipo::int_type()),
+            Expr::Float { .. } => Ok(Tipo::float_type()),
             Expr::Str { .. } => Ok(Tipo::string_type()),
             Expr::Bool { .. } => Ok(Tipo::bool_type()),
             Expr::Unit(..) => Ok(Tipo::unit_type()),
-            Expr::Identifier { value, .. } => self.get_var_tipo(&value),
-            Expr::Value { value, .. } => Ok(value.get_tipo()),
-            Expr::Grouping { expr, .. } => self.check_expr(&expr),
-            Expr::Unary { op, rhs, .. } => self.check_unary_expr(op.clone(), rhs),
-            Expr::Binary { lhs, op, rhs, .. } => self.check_binary_expr(op.clone(), lhs, rhs),
+            Expr::Identifier { value, location } => {
+                // Variables shadow constructors.
+                match self.get_var_tipo(value, location.clone()) {
+                    Err(err) => self.constructor_tipo(value).ok_or(err),
+                    tipo => tipo,
+                }
+            }
+            Expr::Value { value, location } => value.get_tipo().ok_or_else(|| TypeError::Basic {
+                message: format!("Can't infer the type of the value '{value}'."),
+                location: location.clone(),
+            }),
+            Expr::Grouping { expr, .. } => self.check_expr(expr),
+            Expr::Unary { op, rhs, location } => self.check_unary_expr(*op, rhs, location.clone()),
+            Expr::Binary {
+                lhs,
+                op,
+                rhs,
+                location,
+            } => self.check_binary_expr(*op, lhs, rhs, location.clone()),
             Expr::Let {
                 name,
+                mutable,
                 let_tipo,
                 initializer,
                 then,
                 ..
-            } => self.check_let_expr(name, let_tipo, initializer, then),
+            } => self.check_let_expr(name, *mutable, let_tipo, initializer, then),
+            Expr::LetTuple {
+                names,
+                mutable,
+                let_tipo,
+                initializer,
+                then,
+                ..
+            } => self.check_let_tuple_expr(names, *mutable, let_tipo, initializer, then),
+            Expr::Tuple { elements, .. } => {
+                let mut tipos = Vec::new();
+                for element in elements {
+                    tipos.push(self.check_expr(element)?);
+                }
+                Ok(Tipo::tuple_type(tipos))
+            }
+            Expr::Assign {
+                name,
+                value,
+                location,
+            } => self.check_assign_expr(name, value, location.clone()),
             Expr::If {
                 condition,
                 truthy_branch,
                 falsy_branch,
                 ..
             } => self.check_if_expr(condition, truthy_branch, falsy_branch),
-            Expr::Block { expr, .. } => self.check_expr(&expr),
+            Expr::Block { expr, .. } => self.check_expr(expr),
+            Expr::While {
+                condition, body, ..
+            } => self.check_while_expr(condition, body),
+            Expr::Break(location) => self.check_loop_control("break", location.clone()),
+            Expr::Continue(location) => self.check_loop_control("continue", location.clone()),
+            Expr::Sequence { first, then, .. } => {
+                self.check_expr(first)?;
+                self.check_expr(then)
+            }
             Expr::Fn {
+                type_params,
                 params,
                 return_tipo,
                 body,
-                ..
-            } => self.check_funk(params, return_tipo, &body),
+                location,
+            } => {
+                let expected_tipo =
+                    self.expected_fn_tipo(type_params, params, return_tipo, location)?;
+                let fn_tipo = self.check_funk(type_params, params, &expected_tipo, body)?;
+                Ok(self.bind_type_params(type_params, &fn_tipo))
+            }
             Expr::Funk {
                 name,
+                type_params,
                 params,
                 return_tipo,
                 body,
                 then,
                 location,
+                ..
             } => {
-                // Put the expected function type in the scope to handle recursive functions
-                let param_tipos = params.iter().map(|(_, tipo)| tipo.clone()).collect();
-                let expected_tipo = Tipo::new_fn(param_tipos, return_tipo.clone());
-                self.set_var_tipo(name, expected_tipo.clone());
+                let scheme =
+                    self.funk_scheme(name, type_params, params, return_tipo, body, location)?;
+
+                self.begin_scope();
+                self.set_var_scheme(name, scheme);
+                let then_tipo = self.check_expr(then);
+                self.end_scope();
 
-                // Check the inner `Function` struct
-                self.check_funk(params, return_tipo, body)?;
-                Ok(expected_tipo)
+                then_tipo
             }
-            Expr::Call { callee, args, .. } => self.check_call(callee, args),
+            Expr::Call {
+                callee,
+                args,
+                location,
+            } => self.check_call(callee, args, location.clone()),
+            Expr::List { elements, .. } => self.check_list_expr(elements),
+            Expr::Index { list, index, .. } => self.check_index_expr(list, index),
+            Expr::Struct {
+                name,
+                fields,
+                then,
+                location,
+            } => {
+                self.declare_struct(name, fields, location.clone())?;
+                self.check_field_tipos(expr)?;
+                self.check_expr(then)
+            }
+            Expr::Construct {
+                name,
+                fields,
+                location,
+            } => self.check_construct_expr(name, fields, location.clone()),
+            Expr::Enum {
+                name,
+                type_params,
+                variants,
+                then,
+                location,
+            } => {
+                self.declare_enum(name, type_params, variants, location.clone())?;
+                self.check_field_tipos(expr)?;
+                self.check_expr(then)
+            }
+            Expr::Match {
+                scrutinee,
+                arms,
+                location,
+            } => self.check_match_expr(scrutinee, arms, location.clone()),
+            Expr::Field {
+                expr,
+                field,
+                location,
+            } => self.check_field_expr(expr, field, location.clone()),
+            Expr::Try { expr, location } => self.check_try_expr(expr, location.clone()),
         }
     }
 
-    fn check_call(&mut self, callee: &Expr, call_args: &[Expr]) -> TypeResult<Tipo> {
+    /// Checks a funk declaration and returns it's generalized type.
+    fn funk_scheme(
+        &mut self,
+        name: &str,
+        type_params: &[String],
+        params: &[(String, Option<Spanned<Tipo>>)],
+        return_tipo: &Option<Spanned<Tipo>>,
+        body: &Expr,
+        location: &Span,
+    ) -> TypeResult<Scheme> {
+        // Put the expected function type in the scope to handle recursive functions,
+        // it's kept in it's own scope so it doesn't stop the type being generalized.
+        let expected_tipo = self.expected_fn_tipo(type_params, params, return_tipo, location)?;
+        self.begin_scope();
+        self.set_var_tipo(name, expected_tipo.clone());
+        let funk_tipo = self.check_funk(type_params, params, &expected_tipo, body);
+        self.end_scope();
+
+        // Generic type parameters are quantified, so every use of the funk gets fresh ones.
+        let funk_tipo = self.bind_type_params(type_params, &funk_tipo?);
+        Ok(self.generalize(&funk_tipo))
+    }
+
+    fn check_call(
+        &mut self,
+        callee: &Expr,
+        call_args: &[Expr],
+        location: Span,
+    ) -> TypeResult<Tipo> {
+        let callee_tipo = match self.builtin(callee) {
+            Some(builtin) => builtin.tipo(|| self.new_var()),
+            None => self.check_expr(callee)?,
+        };
+
+        if callee_tipo.is_error() {
+            for arg in call_args {
+                self.check_expr(arg)?;
+            }
+            return Ok(Tipo::error_type());
+        }
+
+        // Calling something whose type isn't known yet tells us it's a function.
+        let callee_tipo = if callee_tipo.is_var() {
+            let args = call_args.iter().map(|_| self.new_var()).collect();
+            let fn_tipo = Tipo::new_fn(args, self.new_var());
+            self.unify_or(&fn_tipo, &callee_tipo, location.clone(), |_, _| {
+                TypeError::IncorrectCallee {
+                    location: callee.location(),
+                }
+            })?;
+            fn_tipo
+        } else {
+            callee_tipo
+        };
+
         if let Tipo::Fn {
             args: expected_args,
             ret,
-        } = self.check_expr(callee)?
+        } = callee_tipo
         {
             if expected_args.len() != call_args.len() {
-                return Err(TypeError::IncorrectArgNo {
+                self.report(TypeError::IncorrectArgNo {
                     expected: expected_args.len(),
                     got: call_args.len(),
-                });
+                    location,
+                })?;
             }
 
             for (index, arg) in call_args.iter().enumerate() {
                 let actual_tipo = self.check_expr(arg)?;
 
-                if actual_tipo != expected_args[index] {
-                    return Err(TypeError::IncorrectArgType {
-                        expected: expected_args[index].clone(),
-                        got: actual_tipo,
-                        index,
-                    });
+                if let Some(expected) = expected_args.get(index) {
+                    self.unify_or(expected, &actual_tipo, arg.location(), |expected, got| {
+                        TypeError::IncorrectArgType {
+                            expected,
+                            got,
+                            index,
+                            location: arg.location(),
+                        }
+                    })?;
                 }
             }
             Ok(ret.as_ref().clone())
         } else {
-            Err(TypeError::IncorrectCallee)
+            Err(TypeError::IncorrectCallee {
+                location: callee.location(),
+            })
+        }
+    }
+
+    /// Returns the builtin a callee names, unless a variable shadows it.
+    fn builtin(&self, callee: &Expr) -> Option<Builtin> {
+        match callee {
+            Expr::Identifier { value, .. } if self.get_var(value, 0..0).is_err() => {
+                Builtin::from_name(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Builds the type a function is expected to have from it's annotations,
+    /// missing annotations are filled in with fresh type variables.
+    fn expected_fn_tipo(
+        &mut self,
+        type_params: &[String],
+        params: &[(String, Option<Spanned<Tipo>>)],
+        return_tipo: &Option<Spanned<Tipo>>,
+        location: &Span,
+    ) -> TypeResult<Tipo> {
+        for (index, type_param) in type_params.iter().enumerate() {
+            if type_params[..index].contains(type_param) {
+                self.report(TypeError::DuplicateTypeParam {
+                    name: type_param.clone(),
+                    location: location.clone(),
+                })?;
+            }
+        }
+
+        for (tipo, annotation) in params
+            .iter()
+            .filter_map(|(_, t)| t.as_ref())
+            .chain(return_tipo)
+        {
+            self.check_tipo_names(tipo, type_params, annotation)?;
         }
+
+        let mut param_tipos = Vec::new();
+        for (_, tipo) in params {
+            let tipo = match tipo {
+                Some((tipo, _)) => tipo.clone(),
+                None => self.new_var(),
+            };
+            param_tipos.push(tipo);
+        }
+        let ret = match return_tipo {
+            Some((tipo, _)) => tipo.clone(),
+            None => self.new_var(),
+        };
+
+        Ok(Tipo::new_fn(param_tipos, ret))
     }
 
-    /// There's a small bug with using variables in local scopes
+    /// Checks that every type a type annotation names has been declared,
+    /// `type_params` are the generic parameters declared alongside it.
+    fn check_tipo_names(
+        &self,
+        tipo: &Tipo,
+        type_params: &[String],
+        location: &Span,
+    ) -> TypeResult<()> {
+        match tipo {
+            Tipo::App { name, args } => {
+                let declared = BUILTIN_TYPES.contains(&name.as_str())
+                    || self.structs.contains_key(name)
+                    || self.enums.contains_key(name)
+                    || type_params.contains(name)
+                    || self.type_params.contains(name);
+
+                if !declared {
+                    return Err(TypeError::UnknownType {
+                        name: name.clone(),
+                        location: location.clone(),
+                    });
+                }
+
+                for arg in args {
+                    self.check_tipo_names(arg, type_params, location)?;
+                }
+            }
+            Tipo::Fn { args, ret } => {
+                for arg in args {
+                    self.check_tipo_names(arg, type_params, location)?;
+                }
+                self.check_tipo_names(ret, type_params, location)?;
+            }
+            Tipo::Var { .. } | Tipo::Error => {}
+        }
+
+        Ok(())
+    }
+
+    /// Checks the field types of a struct or enum declaration name declared types.
+    fn check_field_tipos(&self, declaration: &Expr) -> TypeResult<()> {
+        match declaration {
+            Expr::Struct {
+                fields, location, ..
+            } => {
+                for (_, tipo) in fields {
+                    self.check_tipo_names(tipo, &[], location)?;
+                }
+            }
+            Expr::Enum {
+                type_params,
+                variants,
+                location,
+                ..
+            } => {
+                for tipo in variants.iter().flat_map(|(_, fields)| fields) {
+                    self.check_tipo_names(tipo, type_params, location)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Replaces a function's generic type parameters with fresh type variables.
+    /// While checking the body they're opaque types that only unify with themselves.
+    fn bind_type_params(&mut self, type_params: &[String], tipo: &Tipo) -> Tipo {
+        let names: HashMap<String, Tipo> = type_params
+            .iter()
+            .map(|name| (name.clone(), self.new_var()))
+            .collect();
+
+        tipo.substitute_names(&names)
+    }
+
+    /// Checks a function body against the `expected` function type.
     fn check_funk(
         &mut self,
-        params: &Vec<(String, Tipo)>,
-        return_tipo: &Tipo,
+        type_params: &[String],
+        params: &[(String, Option<Spanned<Tipo>>)],
+        expected: &Tipo,
         body: &Expr,
     ) -> TypeResult<Tipo> {
-        self.begin_scope();
+        let (param_tipos, return_tipo) = match expected {
+            Tipo::Fn { args, ret } => (args.clone(), ret.as_ref().clone()),
+            _ => unreachable!("Functions are always expected to have a function type."),
+        };
 
-        let mut tipo_params: Vec<Tipo> = Vec::new();
+        self.begin_scope();
 
-        for (name, tipo) in params {
+        for ((name, _), tipo) in params.iter().zip(param_tipos.iter()) {
             self.set_var_tipo(name, tipo.clone());
-            tipo_params.push(tipo.clone());
         }
 
-        let actual_ret = self.check_expr(body)?;
+        // Loops don't reach into the function body, a `break` can't leave the function.
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let enclosing_return = self.return_tipo.replace(return_tipo.clone());
+        let type_param_count = self.type_params.len();
+        self.type_params.extend_from_slice(type_params);
+        let actual_ret = self.check_expr(body);
+        self.loop_depth = loop_depth;
+        self.return_tipo = enclosing_return;
+        self.type_params.truncate(type_param_count);
 
         self.end_scope();
-        if actual_ret == *return_tipo {
-            Ok(Tipo::new_fn(tipo_params, return_tipo.clone()))
-        } else {
-            Err(TypeError::Basic(format!(
-                "Expected return type of {return_tipo}, got {actual_ret}."
-            )))
+        let actual_ret = actual_ret?;
+
+        self.unify_or(
+            &return_tipo,
+            &actual_ret,
+            body.location(),
+            |expected, got| TypeError::ReturnMismatch {
+                expected,
+                got,
+                location: body.location(),
+            },
+        )?;
+
+        Ok(self.resolve(expected))
+    }
+
+    /// `?` unwraps an `option` or `result`, the enclosing function has to return the same kind
+    /// so the `None` or `Err` can be returned from it as is.
+    fn check_try_expr(&mut self, expr: &Expr, location: Span) -> TypeResult<Tipo> {
+        let tipo = self.check_expr(expr)?;
+        if tipo.is_error() {
+            return Ok(tipo);
+        }
+
+        let Some(return_tipo) = self.return_tipo.clone() else {
+            self.report(TypeError::TryOutsideFunction { location })?;
+            return Ok(Tipo::error_type());
+        };
+
+        // The operand's type decides which kind it is, or the return type if it isn't known yet.
+        let kind = match (self.resolve(&tipo), self.resolve(&return_tipo)) {
+            (Tipo::App { name, .. }, _) | (Tipo::Var { .. }, Tipo::App { name, .. }) => name,
+            _ => String::new(),
+        };
+
+        let value = self.new_var();
+        let (expected, returned) = match kind.as_str() {
+            "option" => (
+                Tipo::option_type(value.clone()),
+                Tipo::option_type(self.new_var()),
+            ),
+            "result" => {
+                let error = self.new_var();
+                (
+                    Tipo::result_type(value.clone(), error.clone()),
+                    Tipo::result_type(self.new_var(), error),
+                )
+            }
+            _ => {
+                self.report(TypeError::NotTryable {
+                    got: self.resolve(&tipo),
+                    location: expr.location(),
+                })?;
+                return Ok(Tipo::error_type());
+            }
+        };
+
+        let unified = self.unify_or(&expected, &tipo, expr.location(), |_, got| {
+            TypeError::NotTryable {
+                got,
+                location: expr.location(),
+            }
+        })?;
+        if !unified {
+            return Ok(Tipo::error_type());
         }
+
+        self.unify_or(
+            &return_tipo,
+            &returned,
+            location.clone(),
+            |expected, got| TypeError::TryMismatch {
+                expected,
+                got,
+                location: location.clone(),
+            },
+        )?;
+
+        Ok(self.resolve(&value))
     }
 
     fn check_if_expr(
@@ -133,210 +857,1087 @@ impl TypeChecker {
         falsy_branch: &Expr,
     ) -> TypeResult<Tipo> {
         // Check that the condition is a boolean.
-        if !self.check_expr(condition)?.is_bool() {
-            return Err(TypeError::Basic(
-                "If/Else condition must be a boolean expression".to_string(),
-            ));
-        }
+        let condition_tipo = self.check_expr(condition)?;
+        self.unify_or(
+            &Tipo::bool_type(),
+            &condition_tipo,
+            condition.location(),
+            |_, got| TypeError::NonBoolCondition {
+                got,
+                location: condition.location(),
+            },
+        )?;
 
         let truthy_tipo = self.check_expr(truthy_branch)?;
         let falsy_tipo = self.check_expr(falsy_branch)?;
 
+        // Don't complain about branches that already failed to check.
+        if truthy_tipo.is_error() {
+            return Ok(falsy_tipo);
+        } else if falsy_tipo.is_error() {
+            return Ok(truthy_tipo);
+        }
+
         // Check that the truthy and falsy branches have the same type.
-        if falsy_tipo != truthy_tipo {
-            return Err(TypeError::Basic(
-                "Truthy and falsy branch in an if/else expression must have the same type."
-                    .to_string(),
-            ));
+        let unified = self.unify_or(
+            &truthy_tipo,
+            &falsy_tipo,
+            falsy_branch.location(),
+            |truthy, falsy| TypeError::BranchMismatch {
+                truthy,
+                falsy,
+                location: falsy_branch.location(),
+                truthy_location: truthy_branch.location(),
+            },
+        )?;
+
+        if unified {
+            Ok(truthy_tipo)
+        } else {
+            Ok(Tipo::error_type())
+        }
+    }
+
+    fn check_while_expr(&mut self, condition: &Expr, body: &Expr) -> TypeResult<Tipo> {
+        let condition_tipo = self.check_expr(condition)?;
+        self.unify_or(
+            &Tipo::bool_type(),
+            &condition_tipo,
+            condition.location(),
+            |_, got| TypeError::NonBoolCondition {
+                got,
+                location: condition.location(),
+            },
+        )?;
+
+        // The body's value is thrown away so it can have any type.
+        self.loop_depth += 1;
+        let body_tipo = self.check_expr(body);
+        self.loop_depth -= 1;
+        body_tipo?;
+
+        Ok(Tipo::unit_type())
+    }
+
+    /// `break` and `continue` never produce a value, so they fit wherever an expression is expected.
+    fn check_loop_control(&mut self, keyword: &str, location: Span) -> TypeResult<Tipo> {
+        if self.loop_depth == 0 {
+            self.report(TypeError::OutsideLoop {
+                keyword: keyword.to_string(),
+                location,
+            })?;
+        }
+
+        Ok(self.new_var())
+    }
+
+    /// Structs are nominal, their type is an `App` of the struct's name.
+    fn declare_struct(
+        &mut self,
+        name: &str,
+        fields: &[(String, Tipo)],
+        location: Span,
+    ) -> TypeResult<()> {
+        self.check_type_is_new(name, &location)?;
+
+        for (index, (field, _)) in fields.iter().enumerate() {
+            if fields[..index].iter().any(|(other, _)| other == field) {
+                return Err(TypeError::DuplicateField {
+                    name: name.to_string(),
+                    field: field.clone(),
+                    location,
+                });
+            }
+        }
+
+        self.structs.insert(name.to_string(), fields.to_vec());
+        self.type_locations.insert(name.to_string(), location);
+        Ok(())
+    }
+
+    /// Structs and enums share one namespace with the builtin types.
+    fn check_type_is_new(&self, name: &str, location: &Span) -> TypeResult<()> {
+        let exists = BUILTIN_TYPES.contains(&name)
+            || self.structs.contains_key(name)
+            || self.enums.contains_key(name);
+
+        if exists {
+            return Err(TypeError::DuplicateType {
+                name: name.to_string(),
+                location: location.clone(),
+                previous: self.type_locations.get(name).cloned(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Declares an enum and it's constructors, a constructor with fields is a function.
+    fn declare_enum(
+        &mut self,
+        name: &str,
+        type_params: &[String],
+        variants: &[(String, Vec<Tipo>)],
+        location: Span,
+    ) -> TypeResult<()> {
+        self.check_type_is_new(name, &location)?;
+
+        for (index, type_param) in type_params.iter().enumerate() {
+            if type_params[..index].contains(type_param) {
+                return Err(TypeError::DuplicateTypeParam {
+                    name: type_param.clone(),
+                    location,
+                });
+            }
+        }
+
+        for (index, (variant, _)) in variants.iter().enumerate() {
+            if variants[..index].iter().any(|(other, _)| other == variant) {
+                return Err(TypeError::DuplicateVariant {
+                    name: name.to_string(),
+                    variant: variant.clone(),
+                    location,
+                });
+            }
+
+            // Constructors are global, another enum can't reuse the name.
+            if let Some(enum_name) = self.constructors.get(variant) {
+                return Err(TypeError::DuplicateConstructor {
+                    name: variant.clone(),
+                    enum_name: enum_name.clone(),
+                    location,
+                    previous: self.type_locations.get(enum_name).cloned(),
+                });
+            }
+        }
+
+        for (variant, _) in variants {
+            self.constructors.insert(variant.clone(), name.to_string());
+        }
+
+        let definition = EnumDef {
+            type_params: type_params.to_vec(),
+            variants: variants.to_vec(),
+        };
+        self.enums.insert(name.to_string(), definition);
+        self.type_locations.insert(name.to_string(), location);
+
+        Ok(())
+    }
+
+    /// Returns a constructor's field types and the enum's type, with fresh type parameters.
+    fn instantiate_constructor(&mut self, constructor: &str) -> Option<(Vec<Tipo>, Tipo)> {
+        let enum_name = self.constructors.get(constructor)?.clone();
+        let definition = self.enums.get(&enum_name)?.clone();
+        let (_, fields) = definition
+            .variants
+            .iter()
+            .find(|(variant, _)| variant == constructor)?;
+
+        let names: HashMap<String, Tipo> = definition
+            .type_params
+            .iter()
+            .map(|param| (param.clone(), self.new_var()))
+            .collect();
+
+        let fields = fields
+            .iter()
+            .map(|field| field.substitute_names(&names))
+            .collect();
+        let tipo = enum_tipo(&enum_name, &definition.type_params).substitute_names(&names);
+
+        Some((fields, tipo))
+    }
+
+    /// The type of a constructor used as a value, constructors without fields are values of the enum.
+    fn constructor_tipo(&mut self, constructor: &str) -> Option<Tipo> {
+        let (fields, tipo) = self.instantiate_constructor(constructor)?;
+
+        if fields.is_empty() {
+            Some(tipo)
+        } else {
+            Some(Tipo::new_fn(fields, tipo))
+        }
+    }
+
+    /// Every arm has the same type, the arms have to cover every value of the scrutinee
+    /// and every arm has to match something the arms above it don't.
+    fn check_match_expr(
+        &mut self,
+        scrutinee: &Expr,
+        arms: &[MatchArm],
+        location: Span,
+    ) -> TypeResult<Tipo> {
+        let scrutinee_tipo = self.check_expr(scrutinee)?;
+        let result_tipo = self.new_var();
+        let mut first_arm: Option<Span> = None;
+        let mut rows: Vec<Vec<Pat>> = Vec::new();
+        let mut failed = false;
+
+        for arm in arms {
+            self.begin_scope();
+            let checked = self.check_arm(arm, &scrutinee_tipo);
+            self.end_scope();
+            let (pat, body_tipo) = checked?;
+
+            // Patterns that failed to check are left out so they don't cause further errors.
+            if let Some(pat) = pat {
+                if !exhaustiveness::is_useful(&rows, std::slice::from_ref(&pat)) {
+                    self.report(TypeError::UnreachableArm {
+                        location: arm.pattern.location(),
+                    })?;
+                }
+
+                // A guard can fail so it's arm doesn't cover anything.
+                if arm.guard.is_none() {
+                    rows.push(vec![pat]);
+                }
+            } else {
+                failed = true;
+            }
+
+            if body_tipo.is_error() {
+                continue;
+            }
+
+            let body_location = arm.body.location();
+            match &first_arm {
+                None => {
+                    self.unify(&result_tipo, &body_tipo).ok();
+                    first_arm = Some(body_location);
+                }
+                Some(first_location) => {
+                    self.unify_or(
+                        &result_tipo,
+                        &body_tipo,
+                        body_location.clone(),
+                        |expected, got| TypeError::ArmMismatch {
+                            expected,
+                            got,
+                            location: body_location.clone(),
+                            first_arm: first_location.clone(),
+                        },
+                    )?;
+                }
+            }
+        }
+
+        // The arm with a broken pattern might have been meant to cover what's missing.
+        if let Some(witness) = exhaustiveness::missing(&rows, 1).filter(|_| !failed) {
+            self.report(TypeError::NonExhaustive {
+                missing: witness[0].to_string(),
+                location,
+            })?;
+        }
+
+        Ok(self.resolve(&result_tipo))
+    }
+
+    /// Checks an arm in it's own scope, returning it's pattern for the exhaustiveness check
+    /// and the type of it's body.
+    fn check_arm(
+        &mut self,
+        arm: &MatchArm,
+        scrutinee_tipo: &Tipo,
+    ) -> TypeResult<(Option<Pat>, Tipo)> {
+        let pat = self.check_pattern(&arm.pattern, scrutinee_tipo)?;
+
+        if let Some(guard) = &arm.guard {
+            let guard_tipo = self.check_expr(guard)?;
+            if !guard_tipo.is_error() {
+                self.unify_or(
+                    &Tipo::bool_type(),
+                    &guard_tipo,
+                    guard.location(),
+                    |_, got| TypeError::NonBoolCondition {
+                        got,
+                        location: guard.location(),
+                    },
+                )?;
+            }
+        }
+
+        let body_tipo = self.check_expr(&arm.body)?;
+        Ok((pat, body_tipo))
+    }
+
+    /// Checks a pattern matches values of the `expected` type and binds it's variables.
+    /// A name is a constructor pattern if there's a constructor with that name.
+    /// Returns `None` if the pattern doesn't check, it's variables are given the error type.
+    fn check_pattern(&mut self, pattern: &Pattern, expected: &Tipo) -> TypeResult<Option<Pat>> {
+        match pattern {
+            Pattern::Wildcard(_) => Ok(Some(Pat::Wild)),
+            Pattern::Binding { name, location } if self.constructors.contains_key(name) => {
+                self.check_constructor_pattern(name, &[], expected, location.clone())
+            }
+            Pattern::Binding { name, location } => {
+                // The arm's scope only holds the pattern's variables.
+                let bound = self
+                    .scopes
+                    .last()
+                    .is_some_and(|scope| scope.contains_key(name));
+                if bound {
+                    self.report(TypeError::DuplicateBinding {
+                        name: name.clone(),
+                        location: location.clone(),
+                    })?;
+                }
+
+                self.set_var_tipo(name, expected.clone());
+                Ok(Some(Pat::Wild))
+            }
+            Pattern::Literal(literal) => {
+                let tipo = self.check_expr(literal)?;
+                let unified =
+                    self.unify_or(expected, &tipo, literal.location(), |expected, got| {
+                        TypeError::PatternMismatch {
+                            expected,
+                            got,
+                            location: literal.location(),
+                        }
+                    })?;
+
+                Ok(match literal {
+                    _ if !unified => None,
+                    Expr::Bool { value, .. } => Some(Pat::bool(value == "true")),
+                    Expr::Str { value, .. } => Some(Pat::Lit(format!("{value:?}"))),
+                    Expr::Int { value, .. } => Some(Pat::Lit(value.clone())),
+                    _ => Some(Pat::Wild),
+                })
+            }
+            Pattern::Constructor {
+                name,
+                args,
+                location,
+            } => self.check_constructor_pattern(name, args, expected, location.clone()),
+        }
+    }
+
+    fn check_constructor_pattern(
+        &mut self,
+        name: &str,
+        args: &[Pattern],
+        expected: &Tipo,
+        location: Span,
+    ) -> TypeResult<Option<Pat>> {
+        let Some((fields, tipo)) = self.instantiate_constructor(name) else {
+            self.report(TypeError::UnknownConstructor {
+                name: name.to_string(),
+                location,
+            })?;
+            self.bind_error_tipos(args);
+            return Ok(None);
+        };
+
+        let unified = self.unify_or(expected, &tipo, location.clone(), |expected, got| {
+            TypeError::PatternMismatch {
+                expected,
+                got,
+                location: location.clone(),
+            }
+        })?;
+        if !unified {
+            self.bind_error_tipos(args);
+            return Ok(None);
+        }
+
+        if args.len() != fields.len() {
+            self.report(TypeError::ConstructorArity {
+                name: name.to_string(),
+                expected: fields.len(),
+                got: args.len(),
+                location,
+            })?;
+            self.bind_error_tipos(args);
+            return Ok(None);
         }
 
-        Ok(truthy_tipo)
+        let mut arg_pats = Some(Vec::new());
+        for (arg, field) in args.iter().zip(fields.iter()) {
+            let arg_pat = self.check_pattern(arg, field)?;
+            arg_pats = arg_pats.zip(arg_pat).map(|(mut pats, pat)| {
+                pats.push(pat);
+                pats
+            });
+        }
+        let Some(arg_pats) = arg_pats else {
+            return Ok(None);
+        };
+
+        let enum_name = &self.constructors[name];
+        let siblings = self.enums[enum_name]
+            .variants
+            .iter()
+            .map(|(variant, fields)| (variant.clone(), fields.len()))
+            .collect();
+
+        Ok(Some(Pat::Ctor {
+            name: name.to_string(),
+            args: arg_pats,
+            siblings,
+        }))
+    }
+
+    /// Gives the variables of patterns that couldn't be checked the error type.
+    fn bind_error_tipos(&mut self, patterns: &[Pattern]) {
+        for pattern in patterns {
+            match pattern {
+                Pattern::Binding { name, .. } if !self.constructors.contains_key(name) => {
+                    self.set_var_tipo(name, Tipo::error_type());
+                }
+                Pattern::Constructor { args, .. } => self.bind_error_tipos(args),
+                _ => {}
+            }
+        }
+    }
+
+    /// Every field has to be set once, with a value of the declared type.
+    fn check_construct_expr(
+        &mut self,
+        name: &str,
+        fields: &[(String, Expr)],
+        location: Span,
+    ) -> TypeResult<Tipo> {
+        let declared = self
+            .structs
+            .get(name)
+            .cloned()
+            .ok_or_else(|| TypeError::UnknownStruct {
+                name: name.to_string(),
+                location: location.clone(),
+            })?;
+
+        for (index, (field, value)) in fields.iter().enumerate() {
+            let value_tipo = self.check_expr(value)?;
+
+            if fields[..index].iter().any(|(other, _)| other == field) {
+                self.report(TypeError::DuplicateField {
+                    name: name.to_string(),
+                    field: field.clone(),
+                    location: value.location(),
+                })?;
+                continue;
+            }
+
+            let Some((_, field_tipo)) = declared.iter().find(|(declared, _)| declared == field)
+            else {
+                self.report(TypeError::UnknownField {
+                    name: name.to_string(),
+                    field: field.clone(),
+                    location: value.location(),
+                })?;
+                continue;
+            };
+
+            if value_tipo.is_error() {
+                continue;
+            }
+
+            self.unify_or(
+                field_tipo,
+                &value_tipo,
+                value.location(),
+                |expected, got| TypeError::FieldMismatch {
+                    name: name.to_string(),
+                    field: field.clone(),
+                    expected,
+                    got,
+                    location: value.location(),
+                },
+            )?;
+        }
+
+        let missing: Vec<String> = declared
+            .iter()
+            .filter(|(declared, _)| !fields.iter().any(|(field, _)| field == declared))
+            .map(|(declared, _)| declared.clone())
+            .collect();
+
+        if !missing.is_empty() {
+            self.report(TypeError::MissingFields {
+                name: name.to_string(),
+                fields: missing,
+                location,
+            })?;
+        }
+
+        Ok(Tipo::new(name))
+    }
+
+    /// The struct has to be known by the time it's field is read, it isn't inferred from the field.
+    fn check_field_expr(&mut self, expr: &Expr, field: &str, location: Span) -> TypeResult<Tipo> {
+        let tipo = self.check_expr(expr)?;
+        let tipo = self.resolve(&tipo);
+
+        if tipo.is_error() {
+            return Ok(tipo);
+        }
+
+        let declared = match &tipo {
+            Tipo::App { name, args } if args.is_empty() => {
+                self.structs.get(name).map(|fields| (name.clone(), fields))
+            }
+            _ => None,
+        };
+
+        let Some((name, fields)) = declared else {
+            return Err(TypeError::NotAStruct {
+                field: field.to_string(),
+                got: tipo,
+                location,
+            });
+        };
+
+        let Some(index) = fields.iter().position(|(declared, _)| declared == field) else {
+            return Err(TypeError::UnknownField {
+                name,
+                field: field.to_string(),
+                location,
+            });
+        };
+
+        let field_tipo = fields[index].1.clone();
+        self.field_indices.insert(location, index);
+        Ok(field_tipo)
+    }
+
+    /// Every element of a list has the same type, an empty list's element type is unknown.
+    fn check_list_expr(&mut self, elements: &[Expr]) -> TypeResult<Tipo> {
+        let element_tipo = self.new_var();
+
+        for element in elements {
+            let tipo = self.check_expr(element)?;
+            if tipo.is_error() {
+                continue;
+            }
+
+            self.unify_or(&element_tipo, &tipo, element.location(), |expected, got| {
+                TypeError::ListElementMismatch {
+                    expected,
+                    got,
+                    location: element.location(),
+                }
+            })?;
+        }
+
+        Ok(Tipo::list_type(self.resolve(&element_tipo)))
+    }
+
+    fn check_index_expr(&mut self, list: &Expr, index: &Expr) -> TypeResult<Tipo> {
+        let list_tipo = self.check_expr(list)?;
+        let index_tipo = self.check_expr(index)?;
+        let element_tipo = self.new_var();
+
+        if !list_tipo.is_error() {
+            let unified = self.unify_or(
+                &Tipo::list_type(element_tipo.clone()),
+                &list_tipo,
+                list.location(),
+                |_, got| TypeError::NotAList {
+                    got,
+                    location: list.location(),
+                },
+            )?;
+
+            if !unified {
+                return Ok(Tipo::error_type());
+            }
+        }
+
+        if !index_tipo.is_error() {
+            self.unify_or(
+                &Tipo::int_type(),
+                &index_tipo,
+                index.location(),
+                |_, got| TypeError::NonIntIndex {
+                    got,
+                    location: index.location(),
+                },
+            )?;
+        }
+
+        Ok(element_tipo)
     }
 
     fn check_let_expr(
         &mut self,
         name: &str,
-        tipo: &Option<Tipo>,
+        mutable: bool,
+        tipo: &Option<Spanned<Tipo>>,
         initializer: &Expr,
         then: &Expr,
     ) -> TypeResult<Tipo> {
-        let init_tipo = self.check_expr(initializer)?;
-        let tipo = if let Some(t) = tipo {
-            if init_tipo != *t {
-                return Err(TypeError::Basic(format!(
-                    "Var '{name}' was expected to be of type {t}, got {init_tipo}."
-                )));
+        let scheme = self.let_scheme(name, mutable, tipo, initializer)?;
+
+        self.begin_scope();
+        self.set_var(name, scheme, mutable);
+        let then_tipo = self.check_expr(then);
+        self.end_scope();
+
+        then_tipo
+    }
+
+    /// Binds each element of the initializer's tuple, there has to be a name for every element.
+    /// Each variable is generalized on it's own like with `let`.
+    fn check_let_tuple_expr(
+        &mut self,
+        names: &[String],
+        mutable: bool,
+        tipo: &Option<Spanned<Tipo>>,
+        initializer: &Expr,
+        then: &Expr,
+    ) -> TypeResult<Tipo> {
+        let pattern = format!("({})", names.join(", "));
+        let tipo = self.let_tipo(&pattern, tipo, initializer)?;
+        let location = initializer.location();
+
+        let elements = match self.resolve(&tipo) {
+            tipo if tipo.is_error() => None,
+            Tipo::App { name, args } if name == "tuple" && args.len() == names.len() => Some(args),
+            Tipo::App { name, args } if name == "tuple" => {
+                self.report(TypeError::TupleArity {
+                    expected: names.len(),
+                    got: args.len(),
+                    location,
+                })?;
+                None
+            }
+            tipo => {
+                let elements: Vec<Tipo> = names.iter().map(|_| self.new_var()).collect();
+                let expected = Tipo::tuple_type(elements.clone());
+                let unified = self.unify_or(&expected, &tipo, location.clone(), |_, got| {
+                    TypeError::NotATuple {
+                        got,
+                        location: location.clone(),
+                    }
+                })?;
+                unified.then_some(elements)
             }
+        };
+        let elements =
+            elements.unwrap_or_else(|| names.iter().map(|_| Tipo::error_type()).collect());
+
+        self.begin_scope();
+        for (name, element) in names.iter().zip(elements.iter()) {
+            let scheme = if mutable {
+                Scheme::mono(self.resolve(element))
+            } else {
+                self.generalize(element)
+            };
+            self.set_var(name, scheme, mutable);
+        }
+        let then_tipo = self.check_expr(then);
+        self.end_scope();
+
+        then_tipo
+    }
+
+    /// Checks a `let` initializer against it's annotation and returns it's generalized type.
+    /// Mutable variables aren't generalized, every value assigned to them has to have the same type.
+    fn let_scheme(
+        &mut self,
+        name: &str,
+        mutable: bool,
+        tipo: &Option<Spanned<Tipo>>,
+        initializer: &Expr,
+    ) -> TypeResult<Scheme> {
+        let tipo = self.let_tipo(name, tipo, initializer)?;
+
+        if mutable {
+            Ok(Scheme::mono(self.resolve(&tipo)))
+        } else {
+            Ok(self.generalize(&tipo))
+        }
+    }
+
+    /// Checks a `let` initializer against it's annotation, the annotation wins if they differ.
+    fn let_tipo(
+        &mut self,
+        name: &str,
+        tipo: &Option<Spanned<Tipo>>,
+        initializer: &Expr,
+    ) -> TypeResult<Tipo> {
+        if let Some((t, annotation)) = tipo {
+            self.check_tipo_names(t, &[], annotation)?;
+        }
+
+        let init_tipo = self.check_expr(initializer)?;
+        let tipo = if let Some((t, annotation)) = tipo {
+            self.unify_or(t, &init_tipo, initializer.location(), |expected, got| {
+                TypeError::AnnotationMismatch {
+                    name: name.to_string(),
+                    expected,
+                    got,
+                    location: initializer.location(),
+                    annotation: annotation.clone(),
+                }
+            })?;
             t.clone()
         } else {
             init_tipo
         };
 
-        self.set_var_tipo(name, tipo);
-        self.check_expr(then)
+        Ok(tipo)
+    }
+
+    fn check_assign_expr(&mut self, name: &str, value: &Expr, location: Span) -> TypeResult<Tipo> {
+        let binding = self.get_var(name, location.clone())?;
+        let value_tipo = self.check_expr(value)?;
+
+        if !binding.mutable {
+            self.report(TypeError::ImmutableAssignment {
+                name: name.to_string(),
+                location: location.clone(),
+            })?;
+        }
+
+        let var_tipo = self.instantiate(&binding.scheme);
+        if !value_tipo.is_error() {
+            self.unify_or(&var_tipo, &value_tipo, value.location(), |expected, got| {
+                TypeError::AssignmentMismatch {
+                    name: name.to_string(),
+                    expected,
+                    got,
+                    location: value.location(),
+                }
+            })?;
+        }
+
+        Ok(Tipo::unit_type())
     }
 
-    fn check_unary_expr(&mut self, op: Op, rhs: &Expr) -> TypeResult<Tipo> {
+    fn check_unary_expr(&mut self, op: Op, rhs: &Expr, location: Span) -> TypeResult<Tipo> {
         let rhs_tipo = self.check_expr(rhs)?;
 
-        match (op, rhs_tipo) {
+        let operand = match op {
             // - t1: int -> int
-            (Op::Minus, t1) => {
-                if t1.is_int() {
-                    Ok(Tipo::int_type())
-                } else {
-                    Err(TypeError::Unary { op, t1 })
-                }
-            }
+            // - t1: float -> float
+            Op::Minus => self.numeric_operand(&[&rhs_tipo]),
+            // ~ t1: int -> int
+            Op::BitNot => Tipo::int_type(),
             // not t1: bool -> bool
-            (Op::Not, t1) => {
-                if t1.is_int() {
-                    Ok(Tipo::int_type())
-                } else {
-                    Err(TypeError::Unary { op, t1 })
-                }
+            Op::Not => Tipo::bool_type(),
+            _ => {
+                return Err(TypeError::Unary {
+                    op,
+                    t1: rhs_tipo,
+                    location,
+                })
             }
-            (op, t1) => Err(TypeError::Unary { op, t1 }),
+        };
+
+        let unified = self.unify_or(&operand, &rhs_tipo, location.clone(), |_, t1| {
+            TypeError::Unary {
+                op,
+                t1,
+                location: location.clone(),
+            }
+        })?;
+
+        if unified {
+            Ok(operand)
+        } else {
+            Ok(Tipo::error_type())
         }
     }
 
-    fn check_binary_expr(&mut self, op: Op, lhs: &Expr, rhs: &Expr) -> TypeResult<Tipo> {
+    fn check_binary_expr(
+        &mut self,
+        op: Op,
+        lhs: &Expr,
+        rhs: &Expr,
+        location: Span,
+    ) -> TypeResult<Tipo> {
         let lhs_tipo = self.check_expr(lhs)?;
         let rhs_tipo = self.check_expr(rhs)?;
 
-        match (op, lhs_tipo, rhs_tipo) {
+        if lhs_tipo.is_error() || rhs_tipo.is_error() {
+            return Ok(Tipo::error_type());
+        }
+
+        // There's no implicit conversion, `float(n)` or `int(x)` has to be used.
+        let (lhs_resolved, rhs_resolved) = (self.resolve(&lhs_tipo), self.resolve(&rhs_tipo));
+        if (lhs_resolved.is_int() && rhs_resolved.is_float())
+            || (lhs_resolved.is_float() && rhs_resolved.is_int())
+        {
+            self.report(TypeError::MixedNumbers {
+                op,
+                t1: lhs_resolved,
+                t2: rhs_resolved,
+                location,
+            })?;
+            return Ok(Tipo::error_type());
+        }
+
+        let number = self.numeric_operand(&[&lhs_tipo, &rhs_tipo]);
+
+        // The type both operands must have and the type of the result.
+        let (operand, result) = match op {
             // ARITHMETIC OPERATIONS
 
             // t1: int + t2: int -> int
+            // t1: float + t2: float -> float
             // t1: string + t2: string -> string
-            (Op::Plus, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::int_type())
-                } else if t1.is_string() && t2.is_string() {
-                    Ok(Tipo::string_type())
+            // Operands of unknown type default to `int`.
+            Op::Plus => {
+                if lhs_tipo.is_string() || rhs_tipo.is_string() {
+                    (Tipo::string_type(), Tipo::string_type())
                 } else {
-                    Err(TypeError::Binary { op, t1, t2 })
+                    (number.clone(), number)
                 }
             }
 
             // int - int -> int
-            (Op::Minus, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::int_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
-            }
             // int * int -> int
-            (Op::Multiply, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::int_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
-            }
             // int / int -> int
-            (Op::Divide, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::int_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
+            // and the same for floats.
+            Op::Minus | Op::Multiply | Op::Divide => (number.clone(), number),
+
+            // int % int -> int
+            // int ** int -> int
+            // and the same for floats.
+            Op::Modulo | Op::Power => (number.clone(), number),
+
+            // BITWISE OPERATIONS: &, |, ^, <<, >> ;
+
+            // t1: int & t2: int -> int
+            // and the same for the others, there are no bitwise operations on floats.
+            Op::BitAnd | Op::BitOr | Op::BitXor | Op::ShiftLeft | Op::ShiftRight => {
+                (Tipo::int_type(), Tipo::int_type())
             }
 
             // COMPARISON OPERATOR: ==, !=, <, <=, >, >= ;
 
             // t1: T == t2: T -> bool
-            (Op::EqualEqual, t1, t2) => {
-                if t1 == t2 {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
-            }
-
             // t1: T != t2: T -> bool
-            (Op::NotEqual, t1, t2) => {
-                if t1 == t2 {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
-            }
+            Op::EqualEqual | Op::NotEqual => (lhs_tipo.clone(), Tipo::bool_type()),
 
-            // t1: int < t2: int -> int ;
-            (Op::Less, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
-            }
-            // int <= int -> int
-            (Op::LessEqual, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
-            }
-            // t1: int > t2: int -> int
-            (Op::Greater, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
-            }
-            // int <= int -> int
-            (Op::GreaterEqual, t1, t2) => {
-                if t1.is_int() && t2.is_int() {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
-                }
+            // t1: int < t2: int -> bool ;
+            // int <= int -> bool
+            // t1: int > t2: int -> bool
+            // int >= int -> bool
+            // and the same for floats.
+            Op::Less | Op::LessEqual | Op::Greater | Op::GreaterEqual => {
+                (number, Tipo::bool_type())
             }
 
             // BOOLEAN OPERATIONS
             // =====================
 
             // t1: bool && t2: bool -> bool
-            (Op::And, t1, t2) => {
-                if t1.is_bool() && t2.is_bool() {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Basic(format!(
-                        "Can't apply 'logical and' to types '{t1}' and '{t2}'"
-                    )))
-                }
+            // t1: bool || t2: bool -> bool
+            Op::And | Op::Or => (Tipo::bool_type(), Tipo::bool_type()),
+
+            Op::Not | Op::BitNot => {
+                return Err(TypeError::Binary {
+                    op,
+                    t1: lhs_tipo,
+                    t2: rhs_tipo,
+                    location,
+                })
             }
+        };
 
-            // t1: bool || t2: bool -> bool
-            (Op::Or, t1, t2) => {
-                if t1.is_bool() && t2.is_bool() {
-                    Ok(Tipo::bool_type())
-                } else {
-                    Err(TypeError::Binary { op, t1, t2 })
+        let mut unified = true;
+        for operand_tipo in [&lhs_tipo, &rhs_tipo] {
+            unified &= self.unify_or(&operand, operand_tipo, location.clone(), |_, _| {
+                TypeError::Binary {
+                    op,
+                    t1: lhs_tipo.clone(),
+                    t2: rhs_tipo.clone(),
+                    location: location.clone(),
                 }
+            })?;
+
+            // Only report one error for both operands.
+            if !unified {
+                break;
             }
+        }
 
-            (op, t1, t2) => Err(TypeError::Binary { op, t1, t2 }),
+        if unified {
+            Ok(result)
+        } else {
+            Ok(Tipo::error_type())
         }
     }
 
-    fn get_var_tipo(&self, name: &str) -> Result<Tipo, TypeError> {
-        let maybe_tipo = self.scopes.iter().rev().find_map(|s| s.get(name));
-
-        if let Some(var_tipo) = maybe_tipo {
-            Ok(var_tipo.clone())
+    /// Arithmetic works on ints or floats, it's float if any operand is known to be one.
+    fn numeric_operand(&self, operands: &[&Tipo]) -> Tipo {
+        if operands.iter().any(|tipo| self.resolve(tipo).is_float()) {
+            Tipo::float_type()
         } else {
-            Err(TypeError::VarDoesntExist(name.to_string()))
+            Tipo::int_type()
         }
     }
 
+    fn get_var_tipo(&mut self, name: &str, location: Span) -> Result<Tipo, TypeError> {
+        let binding = self.get_var(name, location)?;
+        Ok(self.instantiate(&binding.scheme))
+    }
+
+    fn get_var(&self, name: &str, location: Span) -> Result<Binding, TypeError> {
+        let maybe_binding = self.scopes.iter().rev().find_map(|s| s.get(name)).cloned();
+
+        maybe_binding.ok_or_else(|| TypeError::VarDoesntExist {
+            name: name.to_string(),
+            location,
+        })
+    }
+
     fn set_var_tipo(&mut self, name: &str, tipo: Tipo) {
+        self.set_var_scheme(name, Scheme::mono(tipo))
+    }
+
+    fn set_var_scheme(&mut self, name: &str, scheme: Scheme) {
+        self.set_var(name, scheme, false)
+    }
+
+    fn set_var(&mut self, name: &str, scheme: Scheme, mutable: bool) {
         if let Some(top_scope) = self.scopes.last_mut() {
-            top_scope.insert(name.to_string(), tipo.clone());
+            top_scope.insert(name.to_string(), Binding { scheme, mutable });
+        }
+    }
+
+    // UNIFICATION
+    // =====================
+
+    fn new_var(&mut self) -> Tipo {
+        self.next_var += 1;
+        Tipo::new_var(self.next_var - 1)
+    }
+
+    /// Applies the current substitution to a type.
+    fn resolve(&self, tipo: &Tipo) -> Tipo {
+        tipo.apply(&self.substitution)
+    }
+
+    /// Unifies `expected` with `got`, building the error with `on_mismatch` if they can't be unified.
+    /// Returns whether the types were unified, errors are only returned when not accumulating errors.
+    fn unify_or<F>(
+        &mut self,
+        expected: &Tipo,
+        got: &Tipo,
+        location: Span,
+        on_mismatch: F,
+    ) -> TypeResult<bool>
+    where
+        F: FnOnce(Tipo, Tipo) -> TypeError,
+    {
+        match self.unify(expected, got) {
+            Ok(()) => Ok(true),
+            Err(UnifyError::Mismatch) => {
+                let (expected, got) = (self.resolve(expected), self.resolve(got));
+                self.report(on_mismatch(expected, got))?;
+                Ok(false)
+            }
+            Err(UnifyError::Occurs { var, tipo }) => {
+                self.report(TypeError::InfiniteType {
+                    var: Tipo::new_var(var),
+                    tipo,
+                    location,
+                })?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn unify(&mut self, t1: &Tipo, t2: &Tipo) -> Result<(), UnifyError> {
+        let t1 = self.resolve(t1);
+        let t2 = self.resolve(t2);
+
+        match (t1, t2) {
+            // The error type is compatible with everything.
+            (t1, t2) if t1.is_error() || t2.is_error() => Ok(()),
+            (Tipo::Var { id: id1 }, Tipo::Var { id: id2 }) if id1 == id2 => Ok(()),
+            (Tipo::Var { id }, tipo) | (tipo, Tipo::Var { id }) => {
+                if tipo.occurs(id) {
+                    return Err(UnifyError::Occurs { var: id, tipo });
+                }
+                self.substitution.insert(id, tipo);
+                Ok(())
+            }
+            (
+                Tipo::App {
+                    name: n1,
+                    args: args1,
+                },
+                Tipo::App {
+                    name: n2,
+                    args: args2,
+                },
+            ) if n1 == n2 && args1.len() == args2.len() => {
+                for (a1, a2) in args1.iter().zip(args2.iter()) {
+                    self.unify(a1, a2)?;
+                }
+                Ok(())
+            }
+            (
+                Tipo::Fn {
+                    args: args1,
+                    ret: ret1,
+                },
+                Tipo::Fn {
+                    args: args2,
+                    ret: ret2,
+                },
+            ) if args1.len() == args2.len() => {
+                for (a1, a2) in args1.iter().zip(args2.iter()) {
+                    self.unify(a1, a2)?;
+                }
+                self.unify(&ret1, &ret2)
+            }
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+
+    /// Replaces the quantified variables in a scheme with fresh type variables.
+    fn instantiate(&mut self, scheme: &Scheme) -> Tipo {
+        let fresh: HashMap<usize, Tipo> = scheme
+            .vars
+            .iter()
+            .map(|var| (*var, self.new_var()))
+            .collect();
+
+        self.resolve(&scheme.tipo).apply(&fresh)
+    }
+
+    /// Quantifies the type variables in `tipo` that aren't bound anywhere in the environment.
+    fn generalize(&self, tipo: &Tipo) -> Scheme {
+        let tipo = self.resolve(tipo);
+        let env_vars = self.env_vars();
+        let vars = tipo
+            .free_vars()
+            .into_iter()
+            .filter(|var| !env_vars.contains(var))
+            .collect();
+
+        Scheme { vars, tipo }
+    }
+
+    /// Returns the free type variables of every variable in scope.
+    fn env_vars(&self) -> Vec<usize> {
+        let mut vars = Vec::new();
+
+        for Binding { scheme, .. } in self.scopes.iter().flat_map(|scope| scope.values()) {
+            for var in self.resolve(&scheme.tipo).free_vars() {
+                if !scheme.vars.contains(&var) && !vars.contains(&var) {
+                    vars.push(var);
+                }
+            }
+        }
+
+        vars
+    }
+
+    /// Records an error that checking can recover from.
+    /// Only returns the error when the checker isn't accumulating errors.
+    fn report(&mut self, error: TypeError) -> TypeResult<()> {
+        if let Some(errors) = &mut self.errors {
+            errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
         }
     }
 
@@ -349,45 +1950,393 @@ impl TypeChecker {
     }
 }
 
+enum UnifyError {
+    Mismatch,
+    Occurs { var: usize, tipo: Tipo },
+}
+
 pub type TypeResult<T> = Result<T, TypeError>;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TypeError {
-    Basic(String),
-    VarDoesntExist(String),
+    Basic {
+        message: String,
+        location: Span,
+    },
+    VarDoesntExist {
+        name: String,
+        location: Span,
+    },
     Unary {
         op: Op,
         t1: Tipo,
+        location: Span,
     },
     Binary {
         op: Op,
         t1: Tipo,
         t2: Tipo,
+        location: Span,
     },
     IncorrectArgNo {
         expected: usize,
         got: usize,
+        location: Span,
     },
     IncorrectArgType {
         expected: Tipo,
         got: Tipo,
         index: usize,
+        location: Span,
+    },
+    IncorrectCallee {
+        location: Span,
+    },
+    /// A program doesn't have a `main` funk.
+    MissingMain {
+        location: Span,
+    },
+    /// `main` isn't a funk taking no arguments.
+    InvalidMain {
+        got: Tipo,
+        location: Span,
+    },
+    /// A program defines two top-level items with the same name.
+    DuplicateDefinition {
+        name: String,
+        location: Span,
+        previous: Span,
+    },
+    /// Unifying a type variable with a type containing it would make an infinite type.
+    InfiniteType {
+        var: Tipo,
+        tipo: Tipo,
+        location: Span,
+    },
+    /// A `let` initializer doesn't match the variable's type annotation.
+    AnnotationMismatch {
+        name: String,
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+        annotation: Span,
+    },
+    /// A function body doesn't match its declared return type.
+    ReturnMismatch {
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+    },
+    NonBoolCondition {
+        got: Tipo,
+        location: Span,
+    },
+    /// Assigning to a variable that wasn't declared with `let mut`.
+    ImmutableAssignment {
+        name: String,
+        location: Span,
+    },
+    /// The assigned value doesn't match the variable's type.
+    AssignmentMismatch {
+        name: String,
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+    },
+    /// A `let` destructures a tuple into the wrong number of variables.
+    TupleArity {
+        expected: usize,
+        got: usize,
+        location: Span,
+    },
+    /// A `let` destructures something that isn't a tuple.
+    NotATuple {
+        got: Tipo,
+        location: Span,
+    },
+    /// `?` is used on something other than an `option` or `result`.
+    NotTryable {
+        got: Tipo,
+        location: Span,
+    },
+    /// `?` would return a `None` or `Err` the enclosing function can't return.
+    TryMismatch {
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+    },
+    /// `?` is used outside of a function, there's nothing to return from.
+    TryOutsideFunction {
+        location: Span,
+    },
+    /// An arithmetic or comparison operator mixes an int and a float.
+    MixedNumbers {
+        op: Op,
+        t1: Tipo,
+        t2: Tipo,
+        location: Span,
+    },
+    /// A funk, fn or enum declares the same type parameter twice.
+    DuplicateTypeParam {
+        name: String,
+        location: Span,
+    },
+    /// An enum declares the same variant twice.
+    DuplicateVariant {
+        name: String,
+        variant: String,
+        location: Span,
+    },
+    /// An enum declares a constructor that another enum already has.
+    DuplicateConstructor {
+        name: String,
+        enum_name: String,
+        location: Span,
+        /// `None` when the other enum is builtin.
+        previous: Option<Span>,
+    },
+    UnknownConstructor {
+        name: String,
+        location: Span,
+    },
+    /// A pattern binds the same variable more than once.
+    DuplicateBinding {
+        name: String,
+        location: Span,
+    },
+    /// A constructor pattern with the wrong number of fields.
+    ConstructorArity {
+        name: String,
+        expected: usize,
+        got: usize,
+        location: Span,
+    },
+    /// A pattern that can't match the scrutinee's type.
+    PatternMismatch {
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+    },
+    /// A match arm's body doesn't have the type of the first arm's body.
+    ArmMismatch {
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+        first_arm: Span,
+    },
+    /// A match arm that only matches values the arms above it already match.
+    UnreachableArm {
+        location: Span,
+    },
+    /// A match that doesn't cover every value, `missing` is an example of one it doesn't.
+    NonExhaustive {
+        missing: String,
+        location: Span,
+    },
+    /// A struct or enum is declared with the name of a type that already exists.
+    DuplicateType {
+        name: String,
+        location: Span,
+        /// `None` when the existing type is builtin.
+        previous: Option<Span>,
+    },
+    /// A type annotation names a type that hasn't been declared.
+    UnknownType {
+        name: String,
+        location: Span,
+    },
+    /// A struct literal names a struct that hasn't been declared.
+    UnknownStruct {
+        name: String,
+        location: Span,
+    },
+    /// A field that the struct doesn't declare.
+    UnknownField {
+        name: String,
+        field: String,
+        location: Span,
+    },
+    /// A struct literal that doesn't set every field.
+    MissingFields {
+        name: String,
+        fields: Vec<String>,
+        location: Span,
+    },
+    /// A field declared or set more than once.
+    DuplicateField {
+        name: String,
+        field: String,
+        location: Span,
+    },
+    FieldMismatch {
+        name: String,
+        field: String,
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+    },
+    /// Reading a field of something that isn't a struct.
+    NotAStruct {
+        field: String,
+        got: Tipo,
+        location: Span,
+    },
+    /// A list literal's elements don't all have the same type.
+    ListElementMismatch {
+        expected: Tipo,
+        got: Tipo,
+        location: Span,
+    },
+    /// Indexing something that isn't a list.
+    NotAList {
+        got: Tipo,
+        location: Span,
+    },
+    NonIntIndex {
+        got: Tipo,
+        location: Span,
     },
-    IncorrectCallee,
+    /// A `break` or `continue` isn't inside a loop of the same function.
+    OutsideLoop {
+        keyword: String,
+        location: Span,
+    },
+    /// The falsy branch of an if/else doesn't match the truthy branch.
+    BranchMismatch {
+        truthy: Tipo,
+        falsy: Tipo,
+        location: Span,
+        truthy_location: Span,
+    },
+}
+
+impl TypeError {
+    /// Returns the primary span of the error.
+    pub fn location(&self) -> Span {
+        use TypeError::*;
+        match self {
+            Basic { location, .. }
+            | VarDoesntExist { location, .. }
+            | Unary { location, .. }
+            | Binary { location, .. }
+            | IncorrectArgNo { location, .. }
+            | IncorrectArgType { location, .. }
+            | IncorrectCallee { location }
+            | InfiniteType { location, .. }
+            | MissingMain { location }
+            | InvalidMain { location, .. }
+            | DuplicateDefinition { location, .. }
+            | AnnotationMismatch { location, .. }
+            | ReturnMismatch { location, .. }
+            | NonBoolCondition { location, .. }
+            | OutsideLoop { location, .. }
+            | MixedNumbers { location, .. }
+            | TupleArity { location, .. }
+            | NotATuple { location, .. }
+            | NotTryable { location, .. }
+            | TryMismatch { location, .. }
+            | TryOutsideFunction { location }
+            | DuplicateTypeParam { location, .. }
+            | DuplicateVariant { location, .. }
+            | DuplicateConstructor { location, .. }
+            | UnknownConstructor { location, .. }
+            | DuplicateBinding { location, .. }
+            | ConstructorArity { location, .. }
+            | PatternMismatch { location, .. }
+            | ArmMismatch { location, .. }
+            | UnreachableArm { location }
+            | NonExhaustive { location, .. }
+            | DuplicateType { location, .. }
+            | UnknownType { location, .. }
+            | UnknownStruct { location, .. }
+            | UnknownField { location, .. }
+            | MissingFields { location, .. }
+            | DuplicateField { location, .. }
+            | FieldMismatch { location, .. }
+            | NotAStruct { location, .. }
+            | ListElementMismatch { location, .. }
+            | NotAList { location, .. }
+            | NonIntIndex { location, .. }
+            | ImmutableAssignment { location, .. }
+            | AssignmentMismatch { location, .. }
+            | BranchMismatch { location, .. } => location.clone(),
+        }
+    }
+
+    /// Returns the secondary labels pointing at other related code.
+    pub fn labels(&self) -> Vec<Label> {
+        use TypeError::*;
+        match self {
+            AnnotationMismatch {
+                expected,
+                annotation,
+                ..
+            } => vec![Label::new(
+                annotation.clone(),
+                &format!("annotation declared here as '{expected}'"),
+            )],
+            BranchMismatch {
+                truthy,
+                truthy_location,
+                ..
+            } => vec![Label::new(
+                truthy_location.clone(),
+                &format!("truthy branch has type '{truthy}'"),
+            )],
+            ArmMismatch {
+                expected,
+                first_arm,
+                ..
+            } => vec![Label::new(
+                first_arm.clone(),
+                &format!("the first arm has type '{expected}'"),
+            )],
+            DuplicateDefinition { name, previous, .. } => vec![Label::new(
+                previous.clone(),
+                &format!("'{name}' is first defined here"),
+            )],
+            DuplicateType {
+                name,
+                previous: Some(previous),
+                ..
+            } => vec![Label::new(
+                previous.clone(),
+                &format!("'{name}' is first declared here"),
+            )],
+            DuplicateConstructor {
+                name,
+                enum_name,
+                previous: Some(previous),
+                ..
+            } => vec![Label::new(
+                previous.clone(),
+                &format!("'{name}' is first declared by {enum_name} here"),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl ToDiagnostic for TypeError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(self.to_string(), self.location());
+        diagnostic.labels = self.labels();
+        diagnostic
+    }
 }
 
 impl std::fmt::Display for TypeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use TypeError::*;
         match self {
-            Basic(s) => write!(f, "{s}"),
-            VarDoesntExist(name) => write!(f, "Variable '{name}' doesn't exist"),
-            Unary { op, t1 } => write!(f, "Can't apply unary operation '{op}' to type '{t1}'"),
-            Binary { op, t1, t2 } => write!(
+            Basic { message, .. } => write!(f, "{message}"),
+            VarDoesntExist { name, .. } => write!(f, "Variable '{name}' doesn't exist"),
+            Unary { op, t1, .. } => write!(f, "Can't apply unary operation '{op}' to type '{t1}'"),
+            Binary { op, t1, t2, .. } => write!(
                 f,
                 "Can't apply binary operation '{op}' to types '{t1}' and '{t2}'"
             ),
-            IncorrectArgNo { expected, got } => write!(
+            IncorrectArgNo { expected, got, .. } => write!(
                 f,
                 "Incorrect number of arguments, expected {expected} but got {got}."
             ),
@@ -395,12 +2344,151 @@ impl std::fmt::Display for TypeError {
                 expected,
                 got,
                 index,
+                ..
             } => write!(
                 f,
                 "Expected the {index}th argument to be of type {expected} but got {got}."
             ),
-            // TODO FIXME Makee printing better using Araidne
-            IncorrectCallee => write!(f, "Callee is not callable."),
+            IncorrectCallee { .. } => write!(f, "Callee is not callable."),
+            MissingMain { .. } => write!(f, "Programs need a `funk main()` entry point."),
+            InvalidMain { got, .. } => {
+                write!(f, "`main` must be a funk taking no arguments, got {got}.")
+            }
+            DuplicateDefinition { name, .. } => write!(f, "'{name}' is defined more than once."),
+            InfiniteType { var, tipo, .. } => {
+                write!(f, "Can't construct the infinite type {var} = {tipo}.")
+            }
+            AnnotationMismatch {
+                name,
+                expected,
+                got,
+                ..
+            } => write!(
+                f,
+                "Var '{name}' was expected to be of type {expected}, got {got}."
+            ),
+            ReturnMismatch { expected, got, .. } => {
+                write!(f, "Expected return type of {expected}, got {got}.")
+            }
+            NonBoolCondition { got, .. } => {
+                write!(f, "Conditions must be boolean expressions, got {got}.")
+            }
+            ImmutableAssignment { name, .. } => write!(
+                f,
+                "Can't assign to '{name}', it wasn't declared with `let mut`."
+            ),
+            AssignmentMismatch {
+                name,
+                expected,
+                got,
+                ..
+            } => write!(
+                f,
+                "Var '{name}' has type {expected}, it can't be assigned a {got}."
+            ),
+            MixedNumbers { op, t1, t2, .. } => write!(
+                f,
+                "Can't apply '{op}' to '{t1}' and '{t2}', convert one with `float` or `int` first."
+            ),
+            TupleArity { expected, got, .. } => write!(
+                f,
+                "Expected a tuple of {expected} element(s) but got one of {got}."
+            ),
+            NotATuple { got, .. } => write!(f, "Expected a tuple but got {got}."),
+            NotTryable { got, .. } => write!(
+                f,
+                "`?` can only be used on an option or a result but got {got}."
+            ),
+            TryMismatch { expected, got, .. } => write!(
+                f,
+                "`?` can return {got} but the function returns {expected}."
+            ),
+            TryOutsideFunction { .. } => write!(f, "`?` can only be used inside a function."),
+            DuplicateTypeParam { name, .. } => {
+                write!(f, "The type parameter '{name}' is declared more than once.")
+            }
+            DuplicateVariant { name, variant, .. } => {
+                write!(f, "{name} has the variant '{variant}' more than once.")
+            }
+            DuplicateConstructor {
+                name, enum_name, ..
+            } => write!(f, "The constructor '{name}' already belongs to {enum_name}."),
+            UnknownConstructor { name, .. } => write!(f, "Constructor '{name}' doesn't exist."),
+            DuplicateBinding { name, .. } => {
+                write!(f, "'{name}' is bound more than once in the pattern.")
+            }
+            ConstructorArity {
+                name,
+                expected,
+                got,
+                ..
+            } => write!(f, "{name} has {expected} field(s) but the pattern has {got}."),
+            PatternMismatch { expected, got, .. } => write!(
+                f,
+                "The pattern has type {got} but the value being matched has type {expected}."
+            ),
+            ArmMismatch { expected, got, .. } => write!(
+                f,
+                "Every match arm must have the same type, expected {expected} but got {got}."
+            ),
+            UnreachableArm { .. } => write!(
+                f,
+                "This arm is unreachable, the arms above it match everything it does."
+            ),
+            NonExhaustive { missing, .. } => write!(
+                f,
+                "The match doesn't cover every value, '{missing}' isn't matched."
+            ),
+            DuplicateType { name, .. } => write!(f, "The type '{name}' is already declared."),
+            UnknownType { name, .. } => write!(f, "Type '{name}' doesn't exist."),
+            UnknownStruct { name, .. } => write!(f, "Struct '{name}' doesn't exist."),
+            UnknownField { name, field, .. } => write!(f, "{name} doesn't have a field '{field}'."),
+            MissingFields { name, fields, .. } => {
+                write!(f, "{name} is missing the field(s) ")?;
+                for (index, field) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "'{field}'")?;
+                }
+                write!(f, ".")
+            }
+            DuplicateField { name, field, .. } => {
+                write!(f, "{name} has the field '{field}' more than once.")
+            }
+            FieldMismatch {
+                name,
+                field,
+                expected,
+                got,
+                ..
+            } => write!(
+                f,
+                "{name}'s field '{field}' has type {expected} but got {got}."
+            ),
+            NotAStruct { field, got, .. } => write!(
+                f,
+                "Can't read the field '{field}' of {got}, only structs have fields."
+            ),
+            ListElementMismatch { expected, got, .. } => write!(
+                f,
+                "List elements must have the same type, expected {expected} but got {got}."
+            ),
+            NotAList { got, .. } => write!(f, "Only lists can be indexed, got {got}."),
+            NonIntIndex { got, .. } => write!(f, "List indices must be ints, got {got}."),
+            OutsideLoop { keyword, .. } => {
+                write!(f, "`{keyword}` can only be used inside a loop.")
+            }
+            BranchMismatch { truthy, falsy, .. } => write!(
+                f,
+                "Truthy and falsy branch in an if/else expression must have the same type, got {truthy} and {falsy}."
+            ),
         }
     }
 }
+
+/// The type of an enum with it's type parameters as named types, for `substitute_names`.
+fn enum_tipo(name: &str, type_params: &[String]) -> Tipo {
+    let args = type_params.iter().map(|param| Tipo::new(param)).collect();
+    Tipo::new_app(name, args)
+}
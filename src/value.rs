@@ -1,24 +1,146 @@
-use crate::function::Function;
+use std::rc::Rc;
+
+use crate::function::{Closure, Function};
 use crate::tipo::Tipo;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default)]
 pub enum Value {
     Int(i64),
+    Float(f64),
     Str(Box<String>),
     Bool(bool),
+    #[default]
     Unit,
-    Fn(Box<Function>),
+    Fn(Rc<Function>),
+    Closure(Rc<Closure>),
+    /// Lists are immutable, pushing to a list that isn't shared updates it in place.
+    List(Rc<Vec<Value>>),
+    /// A tuple of one or more values, the empty tuple is `Value::Unit`.
+    Tuple(Rc<Vec<Value>>),
+    /// The names of a struct's fields, kept as a constant for building it's records.
+    Layout(Rc<Layout>),
+    /// An instance of a struct, it's fields are stored in the order they're declared in.
+    Record(Rc<Record>),
+    /// A value of an enum, built by one of it's constructors.
+    Variant(Rc<Variant>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl Layout {
+    /// Returns the index of a field in the struct's records.
+    pub fn field_index(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|name| name == field)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub layout: Rc<Layout>,
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    /// The index of the constructor in the enum's declaration, `match` tests it.
+    pub tag: u8,
+    pub values: Vec<Value>,
+}
+
+// Long chains of records and variants, like a list built from an enum, would overflow the stack
+// if they were dropped recursively. Their values are moved onto a worklist instead.
+impl Drop for Record {
+    fn drop(&mut self) {
+        drop_values(std::mem::take(&mut self.values));
+    }
+}
+
+impl Drop for Variant {
+    fn drop(&mut self) {
+        drop_values(std::mem::take(&mut self.values));
+    }
+}
+
+/// Drops values without recursing, the children of values that aren't shared are dropped
+/// from the worklist so lists and tuples in a chain don't recurse either.
+fn drop_values(mut worklist: Vec<Value>) {
+    while let Some(value) = worklist.pop() {
+        match value {
+            Value::Record(record) => {
+                if let Ok(mut record) = Rc::try_unwrap(record) {
+                    worklist.append(&mut record.values);
+                }
+            }
+            Value::Variant(variant) => {
+                if let Ok(mut variant) = Rc::try_unwrap(variant) {
+                    worklist.append(&mut variant.values);
+                }
+            }
+            Value::List(values) | Value::Tuple(values) => {
+                if let Ok(mut values) = Rc::try_unwrap(values) {
+                    worklist.append(&mut values);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Values aren't `Eq` as `NaN` isn't equal to itself, the language's `==` works the same way.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        use Value::*;
+
+        match (self, other) {
+            (Int(a), Int(b)) => a == b,
+            (Float(a), Float(b)) => a == b,
+            (Str(a), Str(b)) => a == b,
+            (Bool(a), Bool(b)) => a == b,
+            (Unit, Unit) => true,
+            (Fn(a), Fn(b)) => a == b,
+            (Closure(a), Closure(b)) => a == b,
+            (List(a), List(b)) | (Tuple(a), Tuple(b)) => a == b,
+            (Layout(a), Layout(b)) => a == b,
+            (Record(a), Record(b)) => a == b,
+            (Variant(a), Variant(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl Value {
-    pub fn get_tipo(&self) -> Tipo {
+    /// Returns the type of the value, compiled functions don't keep their types so they have none.
+    pub fn get_tipo(&self) -> Option<Tipo> {
         use Value::*;
         match self {
-            Int(_) => Tipo::int_type(),
-            Str(_) => Tipo::string_type(),
-            Bool(_) => Tipo::bool_type(),
-            Unit => Tipo::unit_type(),
-            Fn(f) => f.get_tipo(),
+            Int(_) => Some(Tipo::int_type()),
+            Float(_) => Some(Tipo::float_type()),
+            Str(_) => Some(Tipo::string_type()),
+            Bool(_) => Some(Tipo::bool_type()),
+            Unit => Some(Tipo::unit_type()),
+            Fn(_) | Closure(_) | List(_) | Layout(_) | Variant(_) => None,
+            Record(record) => Some(Tipo::new(&record.layout.name)),
+            Tuple(elements) => elements
+                .iter()
+                .map(Value::get_tipo)
+                .collect::<Option<Vec<_>>>()
+                .map(Tipo::tuple_type),
+        }
+    }
+
+    /// Floats take any exponent, an int's exponent can't be negative.
+    pub fn pow(self, rhs: Self) -> Value {
+        use Value::*;
+
+        match (&self, rhs) {
+            (Int(n1), Int(n2)) if n2 >= 0 => Value::Int(n1.wrapping_pow(n2 as u32)),
+            (Float(n1), Float(n2)) => Value::Float(n1.powf(n2)),
+            _ => self.clone(),
         }
     }
 
@@ -48,6 +170,7 @@ impl Value {
     }
 }
 
+// The VM checks integer arithmetic for overflow itself, these wrap so they never panic.
 impl std::ops::Add for Value {
     type Output = Self;
 
@@ -55,13 +178,28 @@ impl std::ops::Add for Value {
         use Value::*;
 
         match (&self, rhs) {
-            (Int(n1), Int(n2)) => Value::Int(n1 + n2),
+            (Int(n1), Int(n2)) => Value::Int(n1.wrapping_add(n2)),
+            (Float(n1), Float(n2)) => Value::Float(n1 + n2),
             (Str(s1), Str(s2)) => Value::Str(Box::new(format!("{}{}", *s1, *s2))),
             _ => self.clone(),
         }
     }
 }
 
+impl std::ops::Neg for Value {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        use Value::*;
+
+        match self {
+            Int(n) => Value::Int(n.wrapping_neg()),
+            Float(n) => Value::Float(-n),
+            _ => self,
+        }
+    }
+}
+
 impl std::ops::Sub for Value {
     type Output = Self;
 
@@ -69,7 +207,8 @@ impl std::ops::Sub for Value {
         use Value::*;
 
         match (&self, rhs) {
-            (Int(n1), Int(n2)) => Value::Int(n1 - n2),
+            (Int(n1), Int(n2)) => Value::Int(n1.wrapping_sub(n2)),
+            (Float(n1), Float(n2)) => Value::Float(n1 - n2),
             _ => self.clone(),
         }
     }
@@ -82,7 +221,8 @@ impl std::ops::Div for Value {
         use Value::*;
 
         match (&self, rhs) {
-            (Int(n1), Int(n2)) => Value::Int(n1 / n2),
+            (Int(n1), Int(n2)) if n2 != 0 => Value::Int(n1.wrapping_div(n2)),
+            (Float(n1), Float(n2)) => Value::Float(n1 / n2),
             _ => self.clone(),
         }
     }
@@ -95,17 +235,111 @@ impl std::ops::Mul for Value {
         use Value::*;
 
         match (&self, rhs) {
-            (Int(n1), Int(n2)) => Value::Int(n1 * n2),
+            (Int(n1), Int(n2)) => Value::Int(n1.wrapping_mul(n2)),
+            (Float(n1), Float(n2)) => Value::Float(n1 * n2),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl std::ops::Rem for Value {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        use Value::*;
+
+        match (&self, rhs) {
+            (Int(n1), Int(n2)) if n2 != 0 => Value::Int(n1.wrapping_rem(n2)),
+            (Float(n1), Float(n2)) => Value::Float(n1 % n2),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl std::ops::BitAnd for Value {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        use Value::*;
+
+        match (&self, rhs) {
+            (Int(n1), Int(n2)) => Value::Int(n1 & n2),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl std::ops::BitOr for Value {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        use Value::*;
+
+        match (&self, rhs) {
+            (Int(n1), Int(n2)) => Value::Int(n1 | n2),
             _ => self.clone(),
         }
     }
 }
 
+impl std::ops::BitXor for Value {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        use Value::*;
+
+        match (&self, rhs) {
+            (Int(n1), Int(n2)) => Value::Int(n1 ^ n2),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl std::ops::Shl for Value {
+    type Output = Self;
+
+    fn shl(self, rhs: Self) -> Self::Output {
+        use Value::*;
+
+        match (&self, rhs) {
+            (Int(n1), Int(n2)) => Value::Int(n1.wrapping_shl(n2 as u32)),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl std::ops::Shr for Value {
+    type Output = Self;
+
+    fn shr(self, rhs: Self) -> Self::Output {
+        use Value::*;
+
+        match (&self, rhs) {
+            (Int(n1), Int(n2)) => Value::Int(n1.wrapping_shr(n2 as u32)),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl std::ops::Not for Value {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        use Value::*;
+
+        match self {
+            Int(n) => Value::Int(!n),
+            _ => self,
+        }
+    }
+}
+
 impl std::cmp::PartialOrd for Value {
     fn lt(&self, other: &Self) -> bool {
         use Value::*;
         match (self, other) {
             (Int(num_1), Int(num_2)) => num_1 < num_2,
+            (Float(num_1), Float(num_2)) => num_1 < num_2,
             _ => false,
         }
     }
@@ -114,6 +348,7 @@ impl std::cmp::PartialOrd for Value {
         use Value::*;
         match (self, other) {
             (Int(num_1), Int(num_2)) => num_1 <= num_2,
+            (Float(num_1), Float(num_2)) => num_1 <= num_2,
             _ => false,
         }
     }
@@ -122,6 +357,7 @@ impl std::cmp::PartialOrd for Value {
         use Value::*;
         match (self, other) {
             (Int(num_1), Int(num_2)) => num_1 > num_2,
+            (Float(num_1), Float(num_2)) => num_1 > num_2,
             _ => false,
         }
     }
@@ -130,6 +366,7 @@ impl std::cmp::PartialOrd for Value {
         use Value::*;
         match (self, other) {
             (Int(num_1), Int(num_2)) => num_1 >= num_2,
+            (Float(num_1), Float(num_2)) => num_1 >= num_2,
             _ => false,
         }
     }
@@ -145,16 +382,64 @@ impl std::fmt::Display for Value {
 
         match self {
             Int(n) => write!(f, "{n}"),
+            // Debug keeps the `.0` of whole numbers so they don't look like ints.
+            Float(n) => write!(f, "{n:?}"),
             Str(s) => write!(f, "{s}"),
             Bool(b) => write!(f, "{b}"),
             Unit => write!(f, "()"),
-            Fn(_f) => todo!(),
+            Fn(function) => write!(f, "{function}"),
+            Closure(closure) => write!(f, "{closure}"),
+            List(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Tuple(elements) => {
+                write!(f, "(")?;
+                for (index, value) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Layout(layout) => write!(f, "<struct {}>", layout.name),
+            Record(record) => {
+                write!(f, "{} {{", record.layout.name)?;
+                for (index, (name, value)) in record
+                    .layout
+                    .fields
+                    .iter()
+                    .zip(record.values.iter())
+                    .enumerate()
+                {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {name}: {value}")?;
+                }
+                write!(f, " }}")
+            }
+            Variant(variant) if variant.values.is_empty() => write!(f, "{}", variant.name),
+            Variant(variant) => {
+                write!(f, "{}(", variant.name)?;
+                for (index, value) in variant.values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
-
-impl std::default::Default for Value {
-    fn default() -> Self {
-        Self::Unit
-    }
-}
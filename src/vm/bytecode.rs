@@ -0,0 +1,385 @@
+//! The `.jkc` bytecode file format, lets a program be compiled once and run later.
+//!
+//! ```text
+//! file     ::= MAGIC version:u16 checksum:u32 chunk ;
+//! chunk    ::= code:bytes constants:[constant] lines:[line] ;
+//! constant ::= INT i64 | FLOAT f64 | STR bytes | BOOL u8 | UNIT | FN name:bytes arity:u32 [capture] chunk
+//!            | LAYOUT name:bytes [field:bytes] ;
+//! capture  ::= is_local:u8 index:u32 ;
+//! line     ::= start:u32 end:u32 count:u32 ;
+//! ```
+//!
+//! Integers are little endian, `[x]` and `bytes` are prefixed with their `u32` length
+//! and the checksum is an FNV-1a hash of everything after the header.
+use std::{path::Path, rc::Rc};
+
+use crate::{
+    function::{Capture, Function},
+    value::{Layout, Value},
+};
+
+use super::{
+    chunk::Chunk,
+    verifier::{self, VerifyError},
+    VM,
+};
+
+/// The first bytes of every `.jkc` file.
+pub const MAGIC: &[u8; 4] = b"JKC\0";
+
+/// Bumped whenever the format or the meaning of an opcode changes.
+pub const VERSION: u16 = 4;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+
+/// How deeply function constants can nest, deeper files are rejected rather than overflowing the stack.
+pub const MAX_NESTING: usize = 64;
+
+const INT_TAG: u8 = 0;
+const STR_TAG: u8 = 1;
+const BOOL_TAG: u8 = 2;
+const UNIT_TAG: u8 = 3;
+const FN_TAG: u8 = 4;
+const LAYOUT_TAG: u8 = 5;
+const FLOAT_TAG: u8 = 6;
+
+#[derive(Debug)]
+pub enum BytecodeError {
+    Io(std::io::Error),
+    /// The file doesn't start with `MAGIC`.
+    BadMagic,
+    /// The file was written by an incompatible version.
+    UnsupportedVersion {
+        found: u16,
+    },
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// The file ended in the middle of a chunk.
+    Truncated,
+    /// There's data left after the chunk.
+    TrailingBytes,
+    InvalidConstantTag(u8),
+    InvalidUtf8,
+    /// Function constants are nested more than `MAX_NESTING` deep.
+    TooDeep,
+    /// Runtime only values like closures can't be written to a file.
+    UnsupportedConstant(String),
+    /// The chunk decoded but isn't safe to run.
+    Invalid(VerifyError),
+}
+
+impl std::fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use BytecodeError::*;
+
+        match self {
+            Io(err) => write!(f, "{err}"),
+            BadMagic => write!(f, "Not a bytecode file, the header is missing."),
+            UnsupportedVersion { found } => write!(
+                f,
+                "Unsupported bytecode version {found}, expected version {VERSION}."
+            ),
+            ChecksumMismatch { expected, found } => write!(
+                f,
+                "The bytecode is corrupt, expected checksum {expected:#010x} but found {found:#010x}."
+            ),
+            Truncated => write!(f, "The bytecode is corrupt, it ends unexpectedly."),
+            TrailingBytes => write!(f, "The bytecode is corrupt, there's data after the chunk."),
+            InvalidConstantTag(tag) => write!(f, "The bytecode is corrupt, unknown constant tag {tag}."),
+            InvalidUtf8 => write!(f, "The bytecode is corrupt, a string isn't valid UTF-8."),
+            TooDeep => write!(
+                f,
+                "The bytecode nests functions more than {MAX_NESTING} deep."
+            ),
+            UnsupportedConstant(value) => {
+                write!(f, "The constant '{value}' can't be written as bytecode.")
+            }
+            Invalid(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for BytecodeError {
+    fn from(err: std::io::Error) -> Self {
+        BytecodeError::Io(err)
+    }
+}
+
+pub type BytecodeResult<T> = Result<T, BytecodeError>;
+
+/// Encodes a chunk with the `.jkc` header.
+pub fn serialize(chunk: &Chunk) -> BytecodeResult<Vec<u8>> {
+    let mut payload = Vec::new();
+    write_chunk(&mut payload, chunk)?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+
+    Ok(bytes)
+}
+
+/// Decodes a chunk, checking the header and checksum first.
+pub fn deserialize(bytes: &[u8]) -> BytecodeResult<Chunk> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(BytecodeError::BadMagic);
+    }
+
+    let mut reader = Reader {
+        bytes,
+        offset: MAGIC.len(),
+        depth: 0,
+    };
+
+    let version = reader.read_u16()?;
+    if version != VERSION {
+        return Err(BytecodeError::UnsupportedVersion { found: version });
+    }
+
+    let expected = reader.read_u32()?;
+    let found = checksum(&bytes[HEADER_LEN..]);
+    if expected != found {
+        return Err(BytecodeError::ChecksumMismatch { expected, found });
+    }
+
+    let chunk = reader.read_chunk()?;
+
+    if reader.offset != bytes.len() {
+        return Err(BytecodeError::TrailingBytes);
+    }
+
+    Ok(chunk)
+}
+
+/// Writes a chunk to a `.jkc` file.
+pub fn write_file(path: impl AsRef<Path>, chunk: &Chunk) -> BytecodeResult<()> {
+    std::fs::write(path, serialize(chunk)?)?;
+    Ok(())
+}
+
+/// Reads a chunk from a `.jkc` file.
+pub fn read_file(path: impl AsRef<Path>) -> BytecodeResult<Chunk> {
+    deserialize(&std::fs::read(path)?)
+}
+
+/// Loads and verifies a `.jkc` file, returning a VM that's ready to run.
+pub fn load(path: impl AsRef<Path>) -> BytecodeResult<VM> {
+    let chunk = read_file(path)?;
+    verifier::verify(&chunk).map_err(BytecodeError::Invalid)?;
+
+    Ok(VM::new(chunk))
+}
+
+/// 32 bit FNV-1a hash.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash: u32, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+fn write_u32(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk: &Chunk) -> BytecodeResult<()> {
+    write_bytes(out, &chunk.code);
+
+    write_u32(out, chunk.constants.len());
+    for constant in &chunk.constants {
+        write_constant(out, constant)?;
+    }
+
+    write_u32(out, chunk.lines.len());
+    for (span, count) in &chunk.lines {
+        write_u32(out, span.start);
+        write_u32(out, span.end);
+        write_u32(out, *count);
+    }
+
+    Ok(())
+}
+
+fn write_constant(out: &mut Vec<u8>, constant: &Value) -> BytecodeResult<()> {
+    match constant {
+        Value::Int(n) => {
+            out.push(INT_TAG);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        Value::Float(n) => {
+            out.push(FLOAT_TAG);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        Value::Str(s) => {
+            out.push(STR_TAG);
+            write_bytes(out, s.as_bytes());
+        }
+        Value::Bool(b) => {
+            out.push(BOOL_TAG);
+            out.push(*b as u8);
+        }
+        Value::Unit => out.push(UNIT_TAG),
+        Value::Fn(function) => {
+            out.push(FN_TAG);
+            write_bytes(out, function.name.as_bytes());
+            write_u32(out, function.arity);
+
+            write_u32(out, function.captures.len());
+            for capture in &function.captures {
+                out.push(capture.is_local as u8);
+                write_u32(out, capture.index);
+            }
+
+            write_chunk(out, &function.chunk)?;
+        }
+        Value::Layout(layout) => {
+            out.push(LAYOUT_TAG);
+            write_bytes(out, layout.name.as_bytes());
+
+            write_u32(out, layout.fields.len());
+            for field in &layout.fields {
+                write_bytes(out, field.as_bytes());
+            }
+        }
+        value => return Err(BytecodeError::UnsupportedConstant(value.to_string())),
+    }
+
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    /// The number of function constants being read.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> BytecodeResult<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(BytecodeError::Truncated)?;
+
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> BytecodeResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> BytecodeResult<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> BytecodeResult<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_usize(&mut self) -> BytecodeResult<usize> {
+        Ok(self.read_u32()? as usize)
+    }
+
+    fn read_bytes(&mut self) -> BytecodeResult<&'a [u8]> {
+        let len = self.read_usize()?;
+        self.take(len)
+    }
+
+    fn read_string(&mut self) -> BytecodeResult<String> {
+        let bytes = self.read_bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| BytecodeError::InvalidUtf8)
+    }
+
+    fn read_chunk(&mut self) -> BytecodeResult<Chunk> {
+        let mut chunk = Chunk::new();
+        chunk.code = self.read_bytes()?.to_vec();
+
+        // Lengths come from the file so they aren't trusted to preallocate.
+        let constant_count = self.read_usize()?;
+        for _ in 0..constant_count {
+            let constant = self.read_constant()?;
+            chunk.constants.push(constant);
+        }
+
+        let line_count = self.read_usize()?;
+        for _ in 0..line_count {
+            let start = self.read_usize()?;
+            let end = self.read_usize()?;
+            let count = self.read_usize()?;
+            chunk.lines.push((start..end, count));
+        }
+
+        Ok(chunk)
+    }
+
+    fn read_constant(&mut self) -> BytecodeResult<Value> {
+        let constant = match self.read_u8()? {
+            INT_TAG => {
+                let bytes = self.take(8)?;
+                let mut n = [0; 8];
+                n.copy_from_slice(bytes);
+                Value::Int(i64::from_le_bytes(n))
+            }
+            FLOAT_TAG => {
+                let bytes = self.take(8)?;
+                let mut n = [0; 8];
+                n.copy_from_slice(bytes);
+                Value::Float(f64::from_le_bytes(n))
+            }
+            STR_TAG => Value::Str(Box::new(self.read_string()?)),
+            BOOL_TAG => Value::Bool(self.read_u8()? != 0),
+            UNIT_TAG => Value::Unit,
+            FN_TAG => {
+                let name = self.read_string()?;
+                let arity = self.read_usize()?;
+
+                let capture_count = self.read_usize()?;
+                let mut captures = Vec::new();
+                for _ in 0..capture_count {
+                    let is_local = self.read_u8()? != 0;
+                    let index = self.read_usize()?;
+                    captures.push(Capture { is_local, index });
+                }
+
+                if self.depth == MAX_NESTING {
+                    return Err(BytecodeError::TooDeep);
+                }
+
+                self.depth += 1;
+                let chunk = self.read_chunk()?;
+                self.depth -= 1;
+
+                let mut function = Function::new(&name, arity, chunk);
+                function.captures = captures;
+                Value::Fn(Rc::new(function))
+            }
+            LAYOUT_TAG => {
+                let name = self.read_string()?;
+
+                let field_count = self.read_usize()?;
+                let mut fields = Vec::new();
+                for _ in 0..field_count {
+                    fields.push(self.read_string()?);
+                }
+
+                Value::Layout(Rc::new(Layout { name, fields }))
+            }
+            tag => return Err(BytecodeError::InvalidConstantTag(tag)),
+        };
+
+        Ok(constant)
+    }
+}
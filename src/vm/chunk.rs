@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use crate::{lexer::Span, value::Value, vm::opcode::OpCode};
 
 pub const SEP: &str = "׀";
@@ -7,6 +9,29 @@ pub struct Chunk {
     pub code: Vec<u8>,
     pub constants: Vec<Value>,
     pub lines: Vec<(Span, usize)>,
+    /// The index of every constant `add_constant` interned.
+    interned: HashMap<Interned, usize>,
+}
+
+/// The constants that are interned, floats aren't as `NaN` isn't equal to itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Interned {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+}
+
+// The interned indices are left out, they only speed up `add_constant`.
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code && self.constants == other.constants && self.lines == other.lines
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunk {
@@ -15,6 +40,7 @@ impl Chunk {
             code: Vec::new(),
             constants: Vec::new(),
             lines: Vec::new(),
+            interned: HashMap::new(),
         }
     }
 
@@ -22,6 +48,13 @@ impl Chunk {
         self.code[index] = instruction;
     }
 
+    /// Overwrites the two bytes starting at `index` with a big endian `u16`, used to patch jumps.
+    pub fn patch_short(&mut self, index: usize, short: u16) {
+        let [high, low] = short.to_be_bytes();
+        self.code[index] = high;
+        self.code[index + 1] = low;
+    }
+
     /// Gets the instruction at a given index.
     // Returns `None` if thr index is out of bounds
     pub fn get_instruction(&self, index: usize) -> Option<u8> {
@@ -73,9 +106,28 @@ impl Chunk {
         }
     }
 
-    /// Adds a constant to the chunk.
-    pub fn add_constant(&mut self, value: Value) {
-        self.constants.push(value)
+    /// Adds a constant to the chunk and returns it's index.
+    /// Ints, bools and strings are interned, adding one that's already in the pool returns the existing index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        let key = match &value {
+            Value::Int(n) => Some(Interned::Int(*n)),
+            Value::Bool(b) => Some(Interned::Bool(*b)),
+            Value::Str(s) => Some(Interned::Str(s.to_string())),
+            _ => None,
+        };
+
+        if let Some(index) = key.as_ref().and_then(|key| self.interned.get(key)) {
+            return *index;
+        }
+
+        self.constants.push(value);
+        let index = self.constants.len() - 1;
+
+        if let Some(key) = key {
+            self.interned.insert(key, index);
+        }
+
+        index
     }
 
     /// Returns the line number of an instruction if it's at the beginning of the line.
@@ -98,6 +150,21 @@ impl Chunk {
         }
     }
 
+    /// Returns the span of the source code that produced the instruction at `index`.
+    pub fn span_at(&self, index: usize) -> Option<Span> {
+        let mut acc = 0;
+
+        for (line, count) in &self.lines {
+            acc += count;
+
+            if index < acc {
+                return Some(line.clone());
+            }
+        }
+
+        None
+    }
+
     /// Prints debug information about the chunk.
     pub fn disassemble(&self, name: &str) {
         let top = format!("============== {name} ==============");
@@ -111,8 +178,13 @@ impl Chunk {
         let mut offset = 0;
 
         while offset < self.code.len() {
-            let op: OpCode = self.code[offset].try_into().expect("Unknown OpCode.");
-            offset = op.disassemble(&self, offset);
+            match OpCode::try_from(self.code[offset]) {
+                Ok(op) => offset = op.disassemble(self, offset),
+                Err(_) => {
+                    println!("Unknown OpCode {}", self.code[offset]);
+                    offset += 1;
+                }
+            }
         }
 
         println!("{}", h_line_thick);
@@ -123,6 +195,14 @@ impl Chunk {
         for (i, con) in self.constants.iter().enumerate() {
             println!("{i:04}  {SEP} {con}")
         }
+
+        // Functions have their own chunks.
+        for con in &self.constants {
+            if let Value::Fn(function) = con {
+                println!();
+                function.chunk.disassemble(&function.to_string());
+            }
+        }
     }
 }
 
@@ -138,7 +218,7 @@ impl std::fmt::Display for Chunk {
 
 #[cfg(test)]
 mod tests {
-    use crate::vm::opcode::OpCode;
+    use crate::{value::Value, vm::opcode::OpCode};
 
     use super::Chunk;
     // TODO FIX
@@ -151,6 +231,48 @@ mod tests {
         assert_eq!(none_ins, None)
     }
 
+    #[test]
+    fn add_constant_interns_literals() {
+        let mut chunky = Chunk::new();
+
+        assert_eq!(chunky.add_constant(Value::Int(1)), 0);
+        assert_eq!(
+            chunky.add_constant(Value::Str(Box::new("hi".to_string()))),
+            1
+        );
+        assert_eq!(chunky.add_constant(Value::Int(1)), 0);
+        assert_eq!(chunky.add_constant(Value::Bool(true)), 2);
+        assert_eq!(
+            chunky.add_constant(Value::Str(Box::new("hi".to_string()))),
+            1
+        );
+        assert_eq!(chunky.constants.len(), 3);
+    }
+
+    #[test]
+    fn add_constant_interns_large_pools() {
+        let mut chunky = Chunk::new();
+
+        for round in 0..2 {
+            for n in 0..100_000 {
+                assert_eq!(chunky.add_constant(Value::Int(n)), n as usize, "{round}");
+            }
+        }
+        assert_eq!(chunky.add_constant(Value::Float(0.5)), 100_000);
+        assert_eq!(chunky.add_constant(Value::Float(0.5)), 100_001);
+    }
+
+    #[test]
+    fn span_at_finds_the_instructions_span() {
+        let mut chunky = Chunk::new();
+        chunky.write_opcode(OpCode::GetConstant, &[0], 0..1);
+        chunky.write_opcode(OpCode::Negate, &[], 2..4);
+
+        assert_eq!(chunky.span_at(1), Some(0..1));
+        assert_eq!(chunky.span_at(2), Some(2..4));
+        assert_eq!(chunky.span_at(3), None);
+    }
+
     #[test]
     fn get_ins_returns_some_when_in_bounds() {
         let mut chunky = Chunk::new();
@@ -1,41 +1,207 @@
+pub mod bytecode;
 pub mod chunk;
 pub mod opcode;
+pub mod verifier;
 
-use crate::value::Value;
+use std::{cell::RefCell, rc::Rc};
 
-use self::{chunk::Chunk, opcode::OpCode};
+use crate::{
+    diagnostic::{Diagnostic, Label, ToDiagnostic},
+    function::{Closure, Function, Upvalue},
+    lexer::Span,
+    value::{Record, Value, Variant},
+};
+
+use self::{chunk::Chunk, opcode::OpCode, verifier::VerifyError};
+
+/// The maximum depth of nested function calls.
+pub const FRAMES_MAX: usize = 1024;
 
 #[derive(Debug)]
 pub struct VM {
-    pub chunk: Chunk,
-    pub ip: usize,
+    pub frames: Vec<CallFrame>,
     pub values: Vec<Value>,
+    /// Upvalues still pointing at a slot in `values`.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    /// What happens when integer arithmetic overflows.
+    pub overflow: Overflow,
+    /// Set once the script passes the verifier.
+    verified: bool,
+}
+
+/// How the VM handles integer arithmetic that overflows an `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Raise a `RuntimeErr::IntegerOverflow`.
+    #[default]
+    Checked,
+    /// Wrap around at the boundary of the type.
+    Wrapping,
+    /// Clamp to `i64::MIN` or `i64::MAX`.
+    Saturating,
+}
+
+/// The variants of an integer operation for each `Overflow` mode.
+struct IntOp {
+    checked: fn(i64, i64) -> Option<i64>,
+    wrapping: fn(i64, i64) -> i64,
+    saturating: fn(i64, i64) -> i64,
+}
+
+const ADD: IntOp = IntOp {
+    checked: i64::checked_add,
+    wrapping: i64::wrapping_add,
+    saturating: i64::saturating_add,
+};
+
+const SUBTRACT: IntOp = IntOp {
+    checked: i64::checked_sub,
+    wrapping: i64::wrapping_sub,
+    saturating: i64::saturating_sub,
+};
+
+const MULTIPLY: IntOp = IntOp {
+    checked: i64::checked_mul,
+    wrapping: i64::wrapping_mul,
+    saturating: i64::saturating_mul,
+};
+
+// Dividing by zero is caught before any of these run.
+const DIVIDE: IntOp = IntOp {
+    checked: i64::checked_div,
+    wrapping: i64::wrapping_div,
+    saturating: i64::saturating_div,
+};
+
+// Only `i64::MIN % -1` overflows, it's remainder is zero.
+const MODULO: IntOp = IntOp {
+    checked: i64::checked_rem,
+    wrapping: i64::wrapping_rem,
+    saturating: i64::wrapping_rem,
+};
+
+// Negative exponents are caught before any of these run.
+const POWER: IntOp = IntOp {
+    checked: |a, b| a.checked_pow(exponent(b)),
+    wrapping: wrapping_pow,
+    saturating: |a, b| a.saturating_pow(exponent(b)),
+};
+
+/// Fits an exponent in the `u32` `i64::pow` takes. Past `u32::MAX` only bases of
+/// -1, 0 and 1 don't overflow, their result only depends on the exponent being odd.
+fn exponent(exp: i64) -> u32 {
+    u32::try_from(exp).unwrap_or((u32::MAX - 1) | (exp & 1) as u32)
+}
+
+/// Exponentiation by squaring, wrapping every step so exponents past `u32::MAX` work too.
+fn wrapping_pow(mut base: i64, mut exp: i64) -> i64 {
+    let mut result: i64 = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+    result
+}
+
+impl Overflow {
+    fn apply(self, op: &IntOp, a: i64, b: i64) -> RuntimeResult<i64> {
+        match self {
+            Overflow::Checked => (op.checked)(a, b).ok_or(RuntimeErr::IntegerOverflow),
+            Overflow::Wrapping => Ok((op.wrapping)(a, b)),
+            Overflow::Saturating => Ok((op.saturating)(a, b)),
+        }
+    }
+}
+
+/// A function that's being executed.
+#[derive(Debug)]
+pub struct CallFrame {
+    pub closure: Rc<Closure>,
+    pub ip: usize,
+    /// The index in the value stack of the frame's first slot.
+    pub base: usize,
 }
 
 type BinaryStackOp = fn(Value, Value) -> Value;
 type UnaryStackOp = fn(Value) -> Value;
 
 impl VM {
-    /// Set's a chunks as the VM's chunk field
+    /// Set's a chunks as the VM's top level script
     pub fn new(chunk: Chunk) -> VM {
+        let script = Function::new("script", 0, chunk);
+
         VM {
-            chunk,
-            ip: 0,
+            frames: vec![CallFrame {
+                closure: Rc::new(Closure::new(Rc::new(script))),
+                ip: 0,
+                base: 0,
+            }],
             values: Vec::new(),
+            open_upvalues: Vec::new(),
+            overflow: Overflow::default(),
+            verified: false,
+        }
+    }
+
+    /// Sets how integer overflow is handled, it's an error by default.
+    pub fn with_overflow(mut self, overflow: Overflow) -> VM {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Runs the script, returning the value left on top of the stack when it returns.
+    /// Errors point at the instruction that failed along with the calls that led to it.
+    /// The script is verified before any of it runs.
+    pub fn run(&mut self) -> Result<Value, RuntimeError> {
+        if !self.verified {
+            let chunk = &self.frame().closure.function.chunk;
+            verifier::verify_script(chunk, self.values.len()).map_err(|err| RuntimeError {
+                location: err.location.clone().unwrap_or(0..0),
+                kind: RuntimeErr::InvalidBytecode(err),
+                trace: Vec::new(),
+            })?;
+            self.verified = true;
         }
+
+        self.execute(0).map_err(|kind| self.runtime_error(kind))
+    }
+
+    /// Runs another script on top of the current stack, it's values are the new script's locals.
+    pub fn run_chunk(&mut self, chunk: Chunk) -> Result<Value, RuntimeError> {
+        let script = Function::new("script", 0, chunk);
+
+        self.frames = vec![CallFrame {
+            closure: Rc::new(Closure::new(Rc::new(script))),
+            ip: 0,
+            base: 0,
+        }];
+        self.verified = false;
+
+        self.run()
+    }
+
+    /// Shrinks the stack to `len` values, closing any upvalues pointing above it.
+    pub fn reset_stack(&mut self, len: usize) {
+        self.truncate(len);
     }
 
     /// Reads a byte from the `chunk` as the instruction
     /// Converts that byte into an `OpCode` and dispatches it.
-    pub fn run(&mut self) -> Result<(), RuntimeErr> {
+    /// Stops once the script returns, or once a call returns to `depth` frames.
+    fn execute(&mut self, depth: usize) -> RuntimeResult<Value> {
         use OpCode::*;
         loop {
             let opcode: OpCode = self.read_opcode()?;
 
             match opcode {
                 Return => {
-                    println!("Returned: {}", self.values[0]);
-                    return Ok(());
+                    if let Some(result) = self.op_return(depth)? {
+                        return Ok(result);
+                    }
+                    Ok(())
                 }
                 GetConstant => self.op_constant(),
                 GetConstantLong => self.op_constant_long(),
@@ -46,6 +212,7 @@ impl VM {
                     Ok(())
                 }
                 PopN => self.pop_n(),
+                EndScope => self.end_scope(),
 
                 // Constant OpCodes
                 Unit => self.push(Value::Unit),
@@ -53,11 +220,36 @@ impl VM {
                 False => self.push(Value::Bool(false)),
 
                 // Arithmetic OpCodes
-                Negate => self.binary_stack_op(|a, b| a + b),
-                Add => self.binary_stack_op(|a, b| a + b),
-                Subtract => self.binary_stack_op(|a, b| a - b),
-                Multiply => self.binary_stack_op(|a, b| a * b),
-                Divide => self.binary_stack_op(|a, b| a / b),
+                Negate => self.negate(),
+                Add => self.int_stack_op(&ADD, |a, b| a + b),
+                Subtract => self.int_stack_op(&SUBTRACT, |a, b| a - b),
+                Multiply => self.int_stack_op(&MULTIPLY, |a, b| a * b),
+                Divide => {
+                    if let Some(Value::Int(0)) = self.values.last() {
+                        return Err(RuntimeErr::DivisionByZero);
+                    }
+                    self.int_stack_op(&DIVIDE, |a, b| a / b)
+                }
+                Modulo => {
+                    if let Some(Value::Int(0)) = self.values.last() {
+                        return Err(RuntimeErr::DivisionByZero);
+                    }
+                    self.int_stack_op(&MODULO, |a, b| a % b)
+                }
+                Power => {
+                    if let Some(Value::Int(exp @ ..0)) = self.values.last() {
+                        return Err(RuntimeErr::NegativeExponent(*exp));
+                    }
+                    self.int_stack_op(&POWER, Value::pow)
+                }
+
+                // Bitwise OpCodes
+                BitAnd => self.binary_stack_op(|a, b| a & b),
+                BitOr => self.binary_stack_op(|a, b| a | b),
+                BitXor => self.binary_stack_op(|a, b| a ^ b),
+                ShiftLeft => self.shift(|a, b| a << b),
+                ShiftRight => self.shift(|a, b| a >> b),
+                BitNot => self.unary_stack_op(|a| !a),
 
                 // Comparison OpCodes
                 Equal => self.binary_stack_op(|a, b| Value::Bool(a == b)),
@@ -76,10 +268,545 @@ impl VM {
                 Jump => self.jump(),
                 JumpIfTrue => self.jump_if_true(),
                 JumpIfFalse => self.jump_if_false(),
+                Loop => self.loop_(),
+
+                // Function OpCodes
+                Call => self.call(),
+                Closure => self.closure(),
+                GetUpvalue => self.get_upvalue(),
+                SetUpvalue => self.set_upvalue(),
+
+                // List OpCodes
+                BuildList => self.build_list(),
+                Index => self.index(),
+                Len => self.len(),
+                Push => self.push_element(),
+                Map => self.map(),
+                Filter => self.filter(),
+                Fold => self.fold(),
+
+                // Struct OpCodes
+                BuildRecord => self.build_record(),
+                GetField => self.get_field(),
+
+                // Tuple OpCodes
+                BuildTuple => self.build_tuple(),
+                Unpack => self.unpack(),
+
+                // Conversion OpCodes
+                ToFloat => self.int_to_float(),
+                ToInt => self.float_to_int(),
+
+                // Enum OpCodes
+                BuildVariant => self.build_variant(),
+                TestTag => self.test_tag(),
+                VariantField => self.variant_field(),
+                NoMatch => Err(RuntimeErr::NoMatch),
+                Try => {
+                    if let Some(result) = self.op_try(depth)? {
+                        return Ok(result);
+                    }
+                    Ok(())
+                }
             }?;
         }
     }
 
+    /// Attaches the location of the current instruction and the call stack to an error.
+    fn runtime_error(&self, kind: RuntimeErr) -> RuntimeError {
+        // Every byte of an instruction shares it's span so the last byte read is good enough.
+        let trace: Vec<TraceFrame> = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| TraceFrame {
+                function: frame.closure.function.to_string(),
+                location: frame
+                    .closure
+                    .function
+                    .chunk
+                    .span_at(frame.ip.saturating_sub(1)),
+            })
+            .collect();
+
+        let location = trace
+            .first()
+            .and_then(|frame| frame.location.clone())
+            .unwrap_or(0..0);
+
+        RuntimeError {
+            kind,
+            location,
+            trace,
+        }
+    }
+
+    /// Returns from the current function, the returned value replaces the callee and it's arguments.
+    /// Returns the result when the top level script returns, or when the call returns to `depth` frames.
+    fn op_return(&mut self, depth: usize) -> RuntimeResult<Option<Value>> {
+        if self.frames.len() == 1 {
+            return Ok(Some(self.values.last().cloned().unwrap_or_default()));
+        }
+
+        let result = self.pop()?;
+        let frame = self.frames.pop().ok_or(RuntimeErr::StackTooShort)?;
+        self.truncate(frame.base);
+
+        if self.frames.len() == depth {
+            return Ok(Some(result));
+        }
+
+        self.push(result)?;
+
+        Ok(None)
+    }
+
+    /// Unwraps the `Some` or `Ok` on top of the stack, a `None` or `Err` is returned instead.
+    fn op_try(&mut self, depth: usize) -> RuntimeResult<Option<Value>> {
+        let variant = match self.values.last() {
+            Some(Value::Variant(variant)) => variant.clone(),
+            Some(value) => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Expected an option or a result but got '{value}'."
+                )))
+            }
+            None => return Err(RuntimeErr::StackTooShort),
+        };
+
+        if variant.tag != 0 {
+            return self.op_return(depth);
+        }
+
+        self.pop()?;
+        let value = variant.values.first().cloned().ok_or_else(|| {
+            RuntimeErr::RuntimeErr(format!("{} has nothing to unwrap.", variant.name))
+        })?;
+        self.push(value)?;
+
+        Ok(None)
+    }
+
+    /// CALL arg_count
+    fn call(&mut self) -> RuntimeResult<()> {
+        let arg_count = self.read_byte()? as usize;
+        self.call_value(arg_count)
+    }
+
+    /// Calls `callee` from inside an instruction, running it until it returns.
+    fn call_closure(&mut self, callee: &Value, args: &[Value]) -> RuntimeResult<Value> {
+        let depth = self.frames.len();
+
+        self.push(callee.clone())?;
+        for arg in args {
+            self.push(arg.clone())?;
+        }
+        self.call_value(args.len())?;
+
+        self.execute(depth)
+    }
+
+    /// Pushes a frame for the callee under the `arg_count` arguments on top of the stack.
+    fn call_value(&mut self, arg_count: usize) -> RuntimeResult<()> {
+        if self.values.len() < arg_count + 1 {
+            return Err(RuntimeErr::StackTooShort);
+        }
+        let base = self.values.len() - arg_count - 1;
+
+        let closure = match &self.values[base] {
+            Value::Closure(closure) => closure.clone(),
+            Value::Fn(function) => Rc::new(Closure::new(function.clone())),
+            value => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Can't call the value '{value}'."
+                )))
+            }
+        };
+
+        if closure.function.arity != arg_count {
+            return Err(RuntimeErr::RuntimeErr(format!(
+                "{closure} expected {} arguments but got {arg_count}.",
+                closure.function.arity
+            )));
+        }
+
+        if self.frames.len() >= FRAMES_MAX {
+            return Err(RuntimeErr::StackOverflow);
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base,
+        });
+
+        Ok(())
+    }
+
+    /// CLOSURE
+    fn closure(&mut self) -> RuntimeResult<()> {
+        let function = match self.pop()? {
+            Value::Fn(function) => function,
+            value => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Can't make a closure out of '{value}'."
+                )))
+            }
+        };
+
+        let mut upvalues = Vec::with_capacity(function.captures.len());
+        for capture in &function.captures {
+            let upvalue = if capture.is_local {
+                let slot = self.local_slot(capture.index)?;
+                self.capture_upvalue(slot)
+            } else {
+                self.frame()
+                    .closure
+                    .upvalues
+                    .get(capture.index)
+                    .cloned()
+                    .ok_or_else(|| {
+                        RuntimeErr::RuntimeErr(format!("Upvalue {} out of bounds", capture.index))
+                    })?
+            };
+
+            upvalues.push(upvalue);
+        }
+
+        self.push(Value::Closure(Rc::new(Closure { function, upvalues })))
+    }
+
+    /// Returns the open upvalue for the stack slot, creating it if the slot isn't captured yet.
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let open = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| *upvalue.borrow() == Upvalue::Open(slot));
+
+        if let Some(upvalue) = open {
+            return upvalue.clone();
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// GET_UPVALUE index
+    fn get_upvalue(&mut self) -> RuntimeResult<()> {
+        let index = self.read_byte()? as usize;
+
+        let upvalue = self.frame().closure.upvalues.get(index).cloned();
+        let value = match upvalue.as_ref().map(|upvalue| upvalue.borrow().clone()) {
+            Some(Upvalue::Open(slot)) => self.values[slot].clone(),
+            Some(Upvalue::Closed(value)) => value,
+            None => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Upvalue {index} out of bounds"
+                )))
+            }
+        };
+
+        self.push(value)
+    }
+
+    /// SET_UPVALUE index
+    fn set_upvalue(&mut self) -> RuntimeResult<()> {
+        let index = self.read_byte()? as usize;
+        let value = self.pop()?;
+
+        let upvalue = self
+            .frame()
+            .closure
+            .upvalues
+            .get(index)
+            .cloned()
+            .ok_or_else(|| RuntimeErr::RuntimeErr(format!("Upvalue {index} out of bounds")))?;
+
+        // Open upvalues still live on the stack, closed ones hold their own value.
+        let mut upvalue = upvalue.borrow_mut();
+        match &mut *upvalue {
+            Upvalue::Open(slot) => self.values[*slot] = value,
+            Upvalue::Closed(closed) => *closed = value,
+        }
+
+        Ok(())
+    }
+
+    /// BUILD_LIST count
+    fn build_list(&mut self) -> RuntimeResult<()> {
+        let count = self.read_byte()? as usize;
+
+        if self.values.len() < count {
+            return Err(RuntimeErr::StackTooShort);
+        }
+        let elements = self.values.split_off(self.values.len() - count);
+
+        self.push(Value::List(Rc::new(elements)))
+    }
+
+    /// INDEX
+    fn index(&mut self) -> RuntimeResult<()> {
+        let index = self.pop()?;
+        let list = self.pop_list()?;
+
+        let element = match index {
+            Value::Int(index) => usize::try_from(index)
+                .ok()
+                .and_then(|i| list.get(i))
+                .cloned()
+                .ok_or(RuntimeErr::IndexOutOfBounds {
+                    index,
+                    len: list.len(),
+                })?,
+            value => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Can't index a list with '{value}'."
+                )))
+            }
+        };
+
+        self.push(element)
+    }
+
+    /// LEN
+    fn len(&mut self) -> RuntimeResult<()> {
+        let list = self.pop_list()?;
+        self.push(Value::Int(list.len() as i64))
+    }
+
+    /// PUSH
+    fn push_element(&mut self) -> RuntimeResult<()> {
+        let element = self.pop()?;
+        let mut list = self.pop_list()?;
+
+        Rc::make_mut(&mut list).push(element);
+
+        self.push(Value::List(list))
+    }
+
+    /// MAP
+    fn map(&mut self) -> RuntimeResult<()> {
+        let f = self.pop()?;
+        let list = self.pop_list()?;
+
+        let mut mapped = Vec::with_capacity(list.len());
+        for element in list.iter() {
+            mapped.push(self.call_closure(&f, std::slice::from_ref(element))?);
+        }
+
+        self.push(Value::List(Rc::new(mapped)))
+    }
+
+    /// FILTER
+    fn filter(&mut self) -> RuntimeResult<()> {
+        let predicate = self.pop()?;
+        let list = self.pop_list()?;
+
+        let mut kept = Vec::new();
+        for element in list.iter() {
+            match self.call_closure(&predicate, std::slice::from_ref(element))? {
+                Value::Bool(true) => kept.push(element.clone()),
+                Value::Bool(false) => {}
+                value => {
+                    return Err(RuntimeErr::RuntimeErr(format!(
+                        "filter expected a bool from it's predicate but got '{value}'."
+                    )))
+                }
+            }
+        }
+
+        self.push(Value::List(Rc::new(kept)))
+    }
+
+    /// FOLD
+    fn fold(&mut self) -> RuntimeResult<()> {
+        let f = self.pop()?;
+        let mut acc = self.pop()?;
+        let list = self.pop_list()?;
+
+        for element in list.iter() {
+            acc = self.call_closure(&f, &[acc, element.clone()])?;
+        }
+
+        self.push(acc)
+    }
+
+    /// BUILD_RECORD count
+    fn build_record(&mut self) -> RuntimeResult<()> {
+        let count = self.read_byte()? as usize;
+
+        if self.values.len() < count + 1 {
+            return Err(RuntimeErr::StackTooShort);
+        }
+        let values = self.values.split_off(self.values.len() - count);
+
+        let layout = match self.pop()? {
+            Value::Layout(layout) if layout.fields.len() == count => layout,
+            value => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Can't build a record with {count} fields out of '{value}'."
+                )))
+            }
+        };
+
+        self.push(Value::Record(Rc::new(Record { layout, values })))
+    }
+
+    /// GET_FIELD name_index
+    fn get_field(&mut self) -> RuntimeResult<()> {
+        let index = self.read_byte()? as usize;
+
+        let value = match self.pop()? {
+            Value::Record(record) => record.values.get(index).cloned().ok_or_else(|| {
+                RuntimeErr::RuntimeErr(format!(
+                    "{} has no field at position {index}.",
+                    record.layout.name
+                ))
+            })?,
+            value => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Can't read field {index} of '{value}'."
+                )))
+            }
+        };
+
+        self.push(value)
+    }
+
+    fn int_to_float(&mut self) -> RuntimeResult<()> {
+        match self.pop()? {
+            Value::Int(n) => self.push(Value::Float(n as f64)),
+            value => Err(RuntimeErr::RuntimeErr(format!(
+                "Expected an int but got '{value}'."
+            ))),
+        }
+    }
+
+    /// Floats that don't fit in an int, or aren't numbers at all, overflow.
+    fn float_to_int(&mut self) -> RuntimeResult<()> {
+        match self.pop()? {
+            // `i64::MAX as f64` rounds up to 2^63 so the upper bound is exclusive.
+            Value::Float(n) if n.is_finite() && n >= i64::MIN as f64 && n < i64::MAX as f64 => {
+                self.push(Value::Int(n as i64))
+            }
+            Value::Float(_) => Err(RuntimeErr::IntegerOverflow),
+            value => Err(RuntimeErr::RuntimeErr(format!(
+                "Expected a float but got '{value}'."
+            ))),
+        }
+    }
+
+    /// BUILD_TUPLE count
+    fn build_tuple(&mut self) -> RuntimeResult<()> {
+        let count = self.read_byte()? as usize;
+
+        if self.values.len() < count {
+            return Err(RuntimeErr::StackTooShort);
+        }
+        let elements = self.values.split_off(self.values.len() - count);
+
+        self.push(Value::Tuple(Rc::new(elements)))
+    }
+
+    /// UNPACK count
+    fn unpack(&mut self) -> RuntimeResult<()> {
+        let count = self.read_byte()? as usize;
+
+        let elements = match self.pop()? {
+            Value::Tuple(elements) if elements.len() == count => elements,
+            value => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Can't unpack '{value}' into {count} value(s)."
+                )))
+            }
+        };
+
+        for element in elements.iter() {
+            self.push(element.clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// BUILD_VARIANT name_index tag count
+    fn build_variant(&mut self) -> RuntimeResult<()> {
+        let tag = self.read_byte()?;
+        let count = self.read_byte()? as usize;
+
+        let name = match self.pop()? {
+            Value::Str(name) => *name,
+            value => {
+                return Err(RuntimeErr::RuntimeErr(format!(
+                    "Expected a constructor name but got '{value}'."
+                )))
+            }
+        };
+
+        if self.values.len() < count {
+            return Err(RuntimeErr::StackTooShort);
+        }
+        let values = self.values.split_off(self.values.len() - count);
+
+        self.push(Value::Variant(Rc::new(Variant { name, tag, values })))
+    }
+
+    /// TEST_TAG tag
+    fn test_tag(&mut self) -> RuntimeResult<()> {
+        let tag = self.read_byte()?;
+        let variant = self.pop_variant()?;
+
+        self.push(Value::Bool(variant.tag == tag))
+    }
+
+    /// VARIANT_FIELD index
+    fn variant_field(&mut self) -> RuntimeResult<()> {
+        let index = self.read_byte()? as usize;
+        let variant = self.pop_variant()?;
+
+        let value = variant.values.get(index).cloned().ok_or_else(|| {
+            RuntimeErr::RuntimeErr(format!("{} has no field {index}.", variant.name))
+        })?;
+
+        self.push(value)
+    }
+
+    fn pop_variant(&mut self) -> RuntimeResult<Rc<Variant>> {
+        match self.pop()? {
+            Value::Variant(variant) => Ok(variant),
+            value => Err(RuntimeErr::RuntimeErr(format!(
+                "Expected an enum but got '{value}'."
+            ))),
+        }
+    }
+
+    fn pop_list(&mut self) -> RuntimeResult<Rc<Vec<Value>>> {
+        match self.pop()? {
+            Value::List(list) => Ok(list),
+            value => Err(RuntimeErr::RuntimeErr(format!(
+                "Expected a list but got '{value}'."
+            ))),
+        }
+    }
+
+    /// Shrinks the value stack to `len`, closing the upvalues pointing at the removed slots.
+    fn truncate(&mut self, len: usize) {
+        let values = &self.values;
+
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= len => {
+                    *upvalue = Upvalue::Closed(values[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        self.values.truncate(len);
+    }
+
     fn op_constant(&mut self) -> Result<(), RuntimeErr> {
         let index: usize = self.read_byte()? as usize;
         let constant = self.get_constant(index)?;
@@ -110,7 +837,8 @@ impl VM {
         let popped_stack_top = self.pop()?;
 
         // Set the value popped from the top of the stack to the local.
-        self.values[local_index] = popped_stack_top;
+        let slot = self.local_slot(local_index)?;
+        self.values[slot] = popped_stack_top;
 
         Ok(())
     }
@@ -119,21 +847,58 @@ impl VM {
     fn get_local(&mut self) -> Result<(), RuntimeErr> {
         // Read the next byte as the index
         let local_index = self.read_byte()? as usize;
+        let slot = self.local_slot(local_index)?;
 
         // Push the local at the given index to the top of the value stack
-        self.push(self.values[local_index].clone())
+        self.push(self.values[slot].clone())
+    }
+
+    /// Returns the index in the value stack of a local in the current frame.
+    fn local_slot(&self, local_index: usize) -> RuntimeResult<usize> {
+        let slot = self.frame().base + local_index;
+
+        if slot < self.values.len() {
+            Ok(slot)
+        } else {
+            Err(RuntimeErr::RuntimeErr(format!(
+                "Local {local_index} out of bounds"
+            )))
+        }
+    }
+
+    /// END_SCOPE count
+    /// Removes (count) locals from under the value on top of the stack.
+    fn end_scope(&mut self) -> RuntimeResult<()> {
+        let count = self.read_byte()? as usize;
+        let result = self.pop()?;
+
+        if self.values.len() >= count {
+            self.truncate(self.values.len() - count);
+            self.push(result)
+        } else {
+            Err(RuntimeErr::StackTooShort)
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("The VM always has a frame.")
     }
 
-    /// Reads the byte at the index pointed at by the VM's `ip` field.
-    /// Increments the VM instruction pointer.
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("The VM always has a frame.")
+    }
+
+    /// Reads the byte at the index pointed at by the current frame's `ip` field.
+    /// Increments the frame's instruction pointer.
     fn read_byte(&mut self) -> RuntimeResult<u8> {
-        let byte = self.chunk.get_instruction(self.ip);
-        self.ip += 1;
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.get_instruction(frame.ip);
+        frame.ip += 1;
 
         if let Some(b) = byte {
             Ok(b)
         } else {
-            Err(RuntimeErr::OutOfInstructions(self.ip))
+            Err(RuntimeErr::OutOfInstructions(frame.ip))
         }
     }
 
@@ -149,7 +914,7 @@ impl VM {
 
     // TODO Change 'get_constant' to return an Option<Value> and map to an error
     fn get_constant(&self, index: usize) -> RuntimeResult<Value> {
-        if let Some(c) = self.chunk.get_constant(index) {
+        if let Some(c) = self.frame().closure.function.chunk.get_constant(index) {
             Ok(c.clone())
         } else {
             Err(RuntimeErr::RuntimeErr("Constant out of bounds".to_string()))
@@ -162,35 +927,53 @@ impl VM {
         let to_be_popped = self.read_byte()? as usize;
 
         if self.values.len() >= to_be_popped {
-            self.values.truncate(self.values.len() - to_be_popped);
+            self.truncate(self.values.len() - to_be_popped);
             Ok(())
         } else {
             Err(RuntimeErr::StackTooShort)
         }
     }
 
+    /// Reads the next two bytes as a big endian `u16`.
+    fn read_short(&mut self) -> RuntimeResult<u16> {
+        let high = self.read_byte()?;
+        let low = self.read_byte()?;
+
+        Ok(u16::from_be_bytes([high, low]))
+    }
+
     fn jump(&mut self) -> RuntimeResult<()> {
-        let destination = self.read_byte()? as usize;
-        self.ip = destination;
+        let destination = self.read_short()? as usize;
+        self.frame_mut().ip = destination;
         Ok(())
     }
 
     fn jump_if_true(&mut self) -> RuntimeResult<()> {
-        let destination = self.read_byte()? as usize;
+        let destination = self.read_short()? as usize;
         if let Value::Bool(true) = self.pop()? {
-            self.ip = destination;
+            self.frame_mut().ip = destination;
         };
         Ok(())
     }
 
     fn jump_if_false(&mut self) -> RuntimeResult<()> {
-        let destination = self.read_byte()? as usize;
-        if let Value::Bool(true) = self.pop()? {
-            self.ip = destination;
+        let destination = self.read_short()? as usize;
+        if let Value::Bool(false) = self.pop()? {
+            self.frame_mut().ip = destination;
         };
         Ok(())
     }
 
+    fn loop_(&mut self) -> RuntimeResult<()> {
+        let distance = self.read_short()? as usize;
+        let frame = self.frame_mut();
+        frame.ip = frame
+            .ip
+            .checked_sub(distance)
+            .ok_or(RuntimeErr::OutOfInstructions(frame.ip))?;
+        Ok(())
+    }
+
     fn unary_stack_op(&mut self, f: UnaryStackOp) -> RuntimeResult<()> {
         let a = self.pop()?;
 
@@ -199,9 +982,46 @@ impl VM {
         Ok(())
     }
 
-    fn binary_stack_op(&mut self, f: BinaryStackOp) -> RuntimeResult<()> {
+    /// Applies an integer operation following the VM's `Overflow` mode,
+    /// other values are handled by `f`.
+    fn int_stack_op(&mut self, op: &IntOp, f: BinaryStackOp) -> RuntimeResult<()> {
+        let b = self.pop()?;
         let a = self.pop()?;
+
+        let result = match (&a, &b) {
+            (Value::Int(a), Value::Int(b)) => Value::Int(self.overflow.apply(op, *a, *b)?),
+            _ => f(a, b),
+        };
+
+        self.push(result)
+    }
+
+    /// Shifting by a negative amount, or by all 64 bits or more, is an error.
+    fn shift(&mut self, f: BinaryStackOp) -> RuntimeResult<()> {
+        if let Some(Value::Int(amount)) = self.values.last() {
+            if !(0..64).contains(amount) {
+                return Err(RuntimeErr::InvalidShift(*amount));
+            }
+        }
+
+        self.binary_stack_op(f)
+    }
+
+    fn negate(&mut self) -> RuntimeResult<()> {
+        let result = match self.pop()? {
+            // Negating is subtracting from zero, only `i64::MIN` overflows.
+            Value::Int(n) => Value::Int(self.overflow.apply(&SUBTRACT, 0, n)?),
+            Value::Float(n) => Value::Float(-n),
+            value => -value,
+        };
+
+        self.push(result)
+    }
+
+    fn binary_stack_op(&mut self, f: BinaryStackOp) -> RuntimeResult<()> {
+        // The right hand side is on top of the stack.
         let b = self.pop()?;
+        let a = self.pop()?;
 
         self.push(f(a, b))?;
 
@@ -232,10 +1052,114 @@ pub enum RuntimeErr {
     StackTooShort,
     OutOfInstructions(usize),
     InvalidOpCode(u8),
+    IntegerOverflow,
+    DivisionByZero,
+    NegativeExponent(i64),
+    InvalidShift(i64),
+    IndexOutOfBounds {
+        index: i64,
+        len: usize,
+    },
+    /// A `match` without an arm for the value, the typechecker rules this out.
+    NoMatch,
+    InvalidBytecode(VerifyError),
+}
+
+impl std::fmt::Display for RuntimeErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CompileErr(message) | Self::RuntimeErr(message) => write!(f, "{message}"),
+            Self::StackOverflow => write!(f, "Stack overflow, too many nested calls."),
+            Self::StackTooShort => write!(f, "Tried to pop a value off an empty stack."),
+            Self::OutOfInstructions(ip) => write!(f, "Ran out of instructions at {ip}."),
+            Self::InvalidOpCode(byte) => write!(f, "Invalid opcode {byte}."),
+            Self::IntegerOverflow => write!(f, "Integer overflow."),
+            Self::DivisionByZero => write!(f, "Division by zero."),
+            Self::NegativeExponent(exp) => write!(
+                f,
+                "Can't raise an int to the negative power {exp}, use floats instead."
+            ),
+            Self::InvalidShift(amount) => write!(
+                f,
+                "Can't shift an int by {amount} bits, it has to be between 0 and 63."
+            ),
+            Self::IndexOutOfBounds { index, len } => write!(
+                f,
+                "Index {index} is out of bounds for a list of length {len}."
+            ),
+            Self::NoMatch => write!(f, "No match arm matched the value."),
+            Self::InvalidBytecode(err) => write!(f, "{err}"),
+        }
+    }
 }
 
 pub type RuntimeResult<T> = Result<T, RuntimeErr>;
 
+/// A `RuntimeErr` with the location of the instruction that raised it.
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub kind: RuntimeErr,
+    pub location: Span,
+    /// The functions being executed when the error happened, innermost first.
+    pub trace: Vec<TraceFrame>,
+}
+
+/// A function on the call stack and where it was in it's body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub location: Option<Span>,
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+
+        for frame in &self.trace {
+            match &frame.location {
+                Some(location) => write!(
+                    f,
+                    "\n    in {} at {}..{}",
+                    frame.function, location.start, location.end
+                )?,
+                None => write!(f, "\n    in {}", frame.function)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDiagnostic for RuntimeError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(self.kind.to_string(), self.location.clone());
+
+        // Every outer frame stopped at the call of the frame inside it.
+        // Recursion makes the same call many times, it's labelled once with a count.
+        let mut calls: Vec<(&TraceFrame, &TraceFrame, usize)> = Vec::new();
+        for (callee, caller) in self.trace.iter().zip(self.trace.iter().skip(1)) {
+            match calls.iter_mut().find(|(other_callee, other_caller, _)| {
+                other_callee.function == callee.function && *other_caller == caller
+            }) {
+                Some((.., count)) => *count += 1,
+                None => calls.push((callee, caller, 1)),
+            }
+        }
+
+        for (callee, caller, count) in calls {
+            if let Some(location) = &caller.location {
+                let mut message = format!("{} called in {}", callee.function, caller.function);
+                if count > 1 {
+                    message.push_str(&format!(" {count} times"));
+                }
+                diagnostic = diagnostic.with_label(Label::new(location.clone(), &message));
+            }
+        }
+
+        diagnostic
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
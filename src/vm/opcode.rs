@@ -46,9 +46,93 @@ pub enum OpCode {
     PopN = 23,
 
     /// Jump Instructions
+    /// Take two operands, the big endian `u16` index of the instruction to jump to.
     Jump = 24,
     JumpIfTrue = 25,
     JumpIfFalse = 26,
+
+    /// Calls the function under the arguments on top of the stack,
+    /// takes the number of arguments as an operand.
+    Call = 27,
+
+    /// Removes the `n` locals under the value on top of the stack, used when a `let` goes out of scope.
+    /// Any of the removed locals captured by a closure are closed over.
+    EndScope = 28,
+
+    /// Wraps the function on top of the stack in a closure,
+    /// capturing the variables listed in the function's `captures`.
+    Closure = 29,
+
+    /// Pushes the value of the current closure's upvalue at the operand's index.
+    GetUpvalue = 30,
+
+    /// Jumps backwards to the start of a loop, takes the big endian `u16` distance
+    /// from the end of the instruction back to the destination.
+    Loop = 31,
+
+    /// Pops the value on top of the stack into the current closure's upvalue at the operand's index.
+    SetUpvalue = 32,
+
+    /// List OpCodes
+    /// Replaces the operand's number of values on top of the stack with a list of them.
+    BuildList = 33,
+    /// Pops an index and a list, pushes the list's element at that index.
+    Index = 34,
+
+    /// Builtin OpCodes
+    /// Each replaces it's arguments on top of the stack with the result.
+    Len = 35,
+    Push = 36,
+    Map = 37,
+    Filter = 38,
+    Fold = 39,
+
+    /// Struct OpCodes
+    /// Replaces a layout and the operand's number of field values above it with a record.
+    BuildRecord = 40,
+    /// Replaces the record on top of the stack with one of it's fields,
+    /// the operand is the field's position in the struct.
+    GetField = 41,
+
+    /// Enum OpCodes
+    /// Replaces the constructor's name on top of the stack and the field values under it
+    /// with a variant, the operands are it's tag and it's number of fields.
+    BuildVariant = 42,
+    /// Replaces the variant on top of the stack with whether it's tag is the operand.
+    TestTag = 43,
+    /// Replaces the variant on top of the stack with it's field at the operand's index.
+    VariantField = 44,
+    /// Raises an error, the value being matched didn't match any arm.
+    NoMatch = 45,
+    /// Replaces the variant on top of the stack with it's first field if it's tag is zero,
+    /// `Some` or `Ok`. Otherwise returns the variant from the current function.
+    Try = 46,
+
+    /// Tuple OpCodes
+    /// Replaces the operand's number of values on top of the stack with a tuple of them.
+    BuildTuple = 47,
+    /// Replaces the tuple on top of the stack with it's elements,
+    /// the operand is the number of elements it should have.
+    Unpack = 48,
+
+    /// Conversion OpCodes
+    /// Replaces the int on top of the stack with the nearest float.
+    ToFloat = 49,
+    /// Replaces the float on top of the stack with it's whole part as an int.
+    ToInt = 50,
+
+    /// Integer OpCodes
+    /// The remainder of dividing, it takes the sign of the dividend.
+    Modulo = 51,
+    /// Raises the value under the top of the stack to the power on top.
+    Power = 52,
+    BitAnd = 53,
+    BitOr = 54,
+    BitXor = 55,
+    ShiftLeft = 56,
+    /// An arithmetic shift, it keeps the sign.
+    ShiftRight = 57,
+    BitNot = 58,
 }
 
 impl OpCode {
@@ -57,19 +141,22 @@ impl OpCode {
         let end_offset = offset + self.arity();
 
         if end_offset > chunk.code.len() - 1 {
-            panic!(
-                "Error: Expected {} more instruction(s).",
+            println!(
+                "Error: {self} expected {} more instruction(s).",
                 end_offset - (chunk.code.len() - 1)
             );
+            return chunk.code.len();
         }
 
         use OpCode::*;
         let repr = match self {
             GetConstant => {
                 let index = chunk.code[offset + 1];
-                let constant: &Value = chunk.get_constant(index as usize).unwrap();
 
-                Some(format!(" [{index}] -> {constant}"))
+                Some(format!(
+                    " [{index}] -> {}",
+                    constant_repr(chunk, index as usize)
+                ))
             }
             GetConstantLong => {
                 let p1 = chunk.code[offset + 1];
@@ -77,9 +164,10 @@ impl OpCode {
                 let p3 = chunk.code[offset + 3];
                 let index: u32 = u32::from_be_bytes([0, p1, p2, p3]);
 
-                let constant: &Value = chunk.get_constant(index as usize).unwrap();
-
-                Some(format!(" [{index}] -> {constant}"))
+                Some(format!(
+                    " [{index}] -> {}",
+                    constant_repr(chunk, index as usize)
+                ))
             }
             SetLocal => {
                 let index = chunk.code[offset + 1];
@@ -91,21 +179,39 @@ impl OpCode {
 
                 Some(format!(" {index}"))
             }
-            Jump => {
-                let index = chunk.code[offset + 1];
+            Jump | JumpIfTrue | JumpIfFalse => {
+                let destination =
+                    u16::from_be_bytes([chunk.code[offset + 1], chunk.code[offset + 2]]);
 
-                Some(format!(" {index}"))
+                Some(format!(" {destination}"))
             }
-            JumpIfTrue => {
-                let index = chunk.code[offset + 1];
+            Loop => {
+                let distance =
+                    u16::from_be_bytes([chunk.code[offset + 1], chunk.code[offset + 2]]) as usize;
 
-                Some(format!(" {index}"))
+                Some(format!(" {}", (end_offset + 1).saturating_sub(distance)))
+            }
+            Call => {
+                let arg_count = chunk.code[offset + 1];
+
+                Some(format!(" {arg_count}"))
             }
-            JumpIfFalse => {
+            PopN | EndScope | BuildList | BuildRecord | BuildTuple | Unpack => {
+                let count = chunk.code[offset + 1];
+
+                Some(format!(" {count}"))
+            }
+            GetUpvalue | SetUpvalue | GetField | TestTag | VariantField => {
                 let index = chunk.code[offset + 1];
 
                 Some(format!(" {index}"))
             }
+            BuildVariant => {
+                let tag = chunk.code[offset + 1];
+                let count = chunk.code[offset + 2];
+
+                Some(format!(" {tag} {count}"))
+            }
             _ => None,
         };
 
@@ -132,9 +238,22 @@ impl OpCode {
             SetLocal => 1,
             GetLocal => 1,
             GetConstantLong => 3,
-            Jump => 1,
-            JumpIfTrue => 1,
-            JumpIfFalse => 1,
+            Jump => 2,
+            JumpIfTrue => 2,
+            JumpIfFalse => 2,
+            Loop => 2,
+            Call => 1,
+            EndScope => 1,
+            Closure => 0,
+            GetUpvalue => 1,
+            SetUpvalue => 1,
+            BuildList | BuildRecord | GetField => 1,
+            Index | Len | Push | Map | Filter | Fold | ToFloat | ToInt => 0,
+            BuildVariant => 2,
+            TestTag | VariantField => 1,
+            NoMatch | Try => 0,
+            BuildTuple | Unpack => 1,
+            Modulo | Power | BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight | BitNot => 0,
 
             // Binary OpCodes
             Negate | Add | Subtract | Multiply | Divide | Equal | NotEqual | Less | LessEqual
@@ -146,6 +265,68 @@ impl OpCode {
     }
 }
 
+impl OpCode {
+    /// Returns how many values the OpCode adds to (or removes from) the stack.
+    pub fn stack_effect(&self, operands: &[u8]) -> isize {
+        use OpCode::*;
+
+        match self {
+            GetConstant | GetConstantLong | GetLocal | GetUpvalue | Unit | True | False => 1,
+            Negate | LogicalNot | Jump | Loop | Closure | Len | GetField | TestTag
+            | VariantField | NoMatch | Try | ToFloat | ToInt | BitNot => 0,
+            Add | Subtract | Multiply | Divide | Equal | NotEqual | Less | LessEqual | Greater
+            | GreaterEqual | LogicalAnd | LogicalOr | Index | Push | Map | Filter | Modulo
+            | Power | BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight => -1,
+            Fold => -2,
+            Return | SetLocal | SetUpvalue | Pop | JumpIfTrue | JumpIfFalse => -1,
+            // The callee and arguments are replaced by the returned value.
+            Call => -(operands[0] as isize),
+            PopN | EndScope => -(operands[0] as isize),
+            BuildList | BuildTuple => 1 - operands[0] as isize,
+            Unpack => operands[0] as isize - 1,
+            // The layout is replaced by the record.
+            BuildRecord => -(operands[0] as isize),
+            // The name is replaced by the variant.
+            BuildVariant => -(operands[1] as isize),
+        }
+    }
+}
+
+impl OpCode {
+    /// Returns how many values the OpCode needs on the stack.
+    pub fn stack_inputs(&self, operands: &[u8]) -> usize {
+        use OpCode::*;
+
+        match self {
+            GetConstant | GetConstantLong | GetLocal | GetUpvalue | Unit | True | False | Jump
+            | Loop | NoMatch => 0,
+            Negate | LogicalNot | Closure | Return | SetLocal | SetUpvalue | Pop | JumpIfTrue
+            | JumpIfFalse | Len | GetField | TestTag | VariantField | Try | Unpack | ToFloat
+            | ToInt | BitNot => 1,
+            Add | Subtract | Multiply | Divide | Equal | NotEqual | Less | LessEqual | Greater
+            | GreaterEqual | LogicalAnd | LogicalOr | Index | Push | Map | Filter | Modulo
+            | Power | BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight => 2,
+            Fold => 3,
+            // The callee sits under the arguments.
+            Call => operands[0] as usize + 1,
+            PopN | BuildList | BuildTuple => operands[0] as usize,
+            // The layout sits under the fields.
+            BuildRecord => operands[0] as usize + 1,
+            // The locals sit under the scope's result.
+            EndScope => operands[0] as usize + 1,
+            // The name sits above the fields.
+            BuildVariant => operands[1] as usize + 1,
+        }
+    }
+}
+
+fn constant_repr(chunk: &Chunk, index: usize) -> String {
+    chunk
+        .get_constant(index)
+        .map(Value::to_string)
+        .unwrap_or("<out of range>".to_string())
+}
+
 impl std::fmt::Display for OpCode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:04} {SEP} {:?}", *self as u8, self)
@@ -188,6 +369,41 @@ impl TryFrom<u8> for OpCode {
             25 => OpCode::JumpIfTrue,
             26 => OpCode::JumpIfFalse,
 
+            27 => OpCode::Call,
+            28 => OpCode::EndScope,
+            29 => OpCode::Closure,
+            30 => OpCode::GetUpvalue,
+            31 => OpCode::Loop,
+            32 => OpCode::SetUpvalue,
+
+            33 => OpCode::BuildList,
+            34 => OpCode::Index,
+            35 => OpCode::Len,
+            36 => OpCode::Push,
+            37 => OpCode::Map,
+            38 => OpCode::Filter,
+            39 => OpCode::Fold,
+
+            40 => OpCode::BuildRecord,
+            41 => OpCode::GetField,
+            42 => OpCode::BuildVariant,
+            43 => OpCode::TestTag,
+            44 => OpCode::VariantField,
+            45 => OpCode::NoMatch,
+            46 => OpCode::Try,
+            47 => OpCode::BuildTuple,
+            48 => OpCode::Unpack,
+            49 => OpCode::ToFloat,
+            50 => OpCode::ToInt,
+            51 => OpCode::Modulo,
+            52 => OpCode::Power,
+            53 => OpCode::BitAnd,
+            54 => OpCode::BitOr,
+            55 => OpCode::BitXor,
+            56 => OpCode::ShiftLeft,
+            57 => OpCode::ShiftRight,
+            58 => OpCode::BitNot,
+
             _ => return Err("Invalid OpCode".to_string()),
         };
 
@@ -0,0 +1,314 @@
+//! Checks that a `Chunk` is safe to run before the VM executes any of it.
+//!
+//! Every instruction has to decode with all of it's operands, jumps have to land on an instruction,
+//! constant, local and upvalue indices have to be in range and the stack has to have the same depth
+//! whichever path reaches an instruction. The chunks of function constants are verified too.
+use crate::{function::Function, lexer::Span, value::Value};
+
+use super::{chunk::Chunk, opcode::OpCode};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyErr {
+    InvalidOpCode(u8),
+    /// The chunk ends before the instruction's operands.
+    TruncatedOperands,
+    /// The jump's destination isn't the start of an instruction.
+    InvalidJump {
+        destination: usize,
+    },
+    ConstantOutOfRange {
+        index: usize,
+    },
+    LocalOutOfRange {
+        index: usize,
+    },
+    UpvalueOutOfRange {
+        index: usize,
+    },
+    /// The instruction pops more values than are on the stack.
+    StackUnderflow,
+    /// Two paths reach the instruction with a different number of values on the stack.
+    InconsistentStack {
+        expected: usize,
+        found: usize,
+    },
+    /// Execution can run past the last instruction.
+    FallsOffEnd,
+}
+
+/// A `VerifyErr` with the function and instruction it was found in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    pub kind: VerifyErr,
+    pub function: String,
+    pub offset: usize,
+    pub location: Option<Span>,
+}
+
+impl std::fmt::Display for VerifyErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use VerifyErr::*;
+
+        match self {
+            InvalidOpCode(byte) => write!(f, "invalid opcode {byte}"),
+            TruncatedOperands => write!(f, "the instruction is missing operands"),
+            InvalidJump { destination } => {
+                write!(f, "jump to {destination} doesn't land on an instruction")
+            }
+            ConstantOutOfRange { index } => write!(f, "constant {index} is out of range"),
+            LocalOutOfRange { index } => write!(f, "local {index} is out of range"),
+            UpvalueOutOfRange { index } => write!(f, "upvalue {index} is out of range"),
+            StackUnderflow => write!(f, "the stack underflows"),
+            InconsistentStack { expected, found } => write!(
+                f,
+                "the stack has {found} values on one path but {expected} on another"
+            ),
+            FallsOffEnd => write!(f, "execution runs past the end of the chunk"),
+        }
+    }
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid bytecode in {} at {}: {}.",
+            self.function, self.offset, self.kind
+        )
+    }
+}
+
+pub type VerifyResult<T> = Result<T, VerifyError>;
+
+/// Verifies a top level script and every function it contains.
+pub fn verify(chunk: &Chunk) -> VerifyResult<()> {
+    verify_script(chunk, 0)
+}
+
+/// Verifies a script that starts with `locals` values already on the stack, like a REPL entry.
+pub fn verify_script(chunk: &Chunk, locals: usize) -> VerifyResult<()> {
+    let script = Verifier {
+        name: "<fn script>".to_string(),
+        chunk,
+        upvalue_count: 0,
+    };
+
+    verify_nested(script, locals)
+}
+
+/// Verifies a function, it's frame starts with the callee and it's arguments.
+pub fn verify_function(function: &Function) -> VerifyResult<()> {
+    verify_nested(Verifier::function(function), function.arity + 1)
+}
+
+/// Verifies a chunk and the functions nested in it's constants.
+/// The nested functions are kept on a worklist so deeply nested chunks can't overflow the stack.
+fn verify_nested(root: Verifier, initial_depth: usize) -> VerifyResult<()> {
+    let mut worklist = vec![(root, initial_depth)];
+
+    while let Some((verifier, initial_depth)) = worklist.pop() {
+        verifier.verify(initial_depth)?;
+
+        for (offset, constant) in verifier.chunk.constants.iter().enumerate() {
+            if let Value::Fn(function) = constant {
+                // Upvalues are captured from the enclosing closure when the function isn't a local.
+                for capture in &function.captures {
+                    if !capture.is_local && capture.index >= verifier.upvalue_count {
+                        return Err(VerifyError {
+                            kind: VerifyErr::UpvalueOutOfRange {
+                                index: capture.index,
+                            },
+                            function: function.to_string(),
+                            offset,
+                            location: None,
+                        });
+                    }
+                }
+
+                worklist.push((Verifier::function(function), function.arity + 1));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Verifier<'a> {
+    name: String,
+    chunk: &'a Chunk,
+    upvalue_count: usize,
+}
+
+impl<'a> Verifier<'a> {
+    fn function(function: &'a Function) -> Verifier<'a> {
+        Verifier {
+            name: function.to_string(),
+            chunk: &function.chunk,
+            upvalue_count: function.captures.len(),
+        }
+    }
+
+    /// Verifies the chunk's own code, nested functions are left to `verify_nested`.
+    fn verify(&self, initial_depth: usize) -> VerifyResult<()> {
+        let instructions = self.decode()?;
+        self.check_stack(&instructions, initial_depth)
+    }
+
+    /// Decodes every instruction, returns the opcode at each offset that starts an instruction.
+    fn decode(&self) -> VerifyResult<Vec<Option<OpCode>>> {
+        let code = &self.chunk.code;
+        let mut instructions = vec![None; code.len()];
+        let mut offset = 0;
+
+        while offset < code.len() {
+            let op = OpCode::try_from(code[offset])
+                .map_err(|_| self.error(VerifyErr::InvalidOpCode(code[offset]), offset))?;
+
+            if offset + op.arity() >= code.len() {
+                return Err(self.error(VerifyErr::TruncatedOperands, offset));
+            }
+
+            instructions[offset] = Some(op);
+            offset += op.arity() + 1;
+        }
+
+        Ok(instructions)
+    }
+
+    /// Follows every path through the chunk tracking the number of values on the stack.
+    fn check_stack(
+        &self,
+        instructions: &[Option<OpCode>],
+        initial_depth: usize,
+    ) -> VerifyResult<()> {
+        let code = &self.chunk.code;
+        let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+        let mut worklist = vec![(0, initial_depth)];
+
+        while let Some((offset, depth)) = worklist.pop() {
+            let op = match instructions.get(offset) {
+                Some(Some(op)) => *op,
+                // Decoding already made sure jumps and fallthroughs land in the chunk's code.
+                _ => return Err(self.error(VerifyErr::FallsOffEnd, offset.saturating_sub(1))),
+            };
+
+            match depths[offset] {
+                Some(expected) if expected != depth => {
+                    return Err(self.error(
+                        VerifyErr::InconsistentStack {
+                            expected,
+                            found: depth,
+                        },
+                        offset,
+                    ))
+                }
+                Some(_) => continue,
+                None => depths[offset] = Some(depth),
+            }
+
+            let operands = &code[offset + 1..offset + 1 + op.arity()];
+
+            if depth < op.stack_inputs(operands) {
+                return Err(self.error(VerifyErr::StackUnderflow, offset));
+            }
+
+            let next_depth = (depth as isize + op.stack_effect(operands)) as usize;
+            let next = offset + op.arity() + 1;
+
+            self.check_operands(op, operands, depth, next, instructions)
+                .map_err(|kind| self.error(kind, offset))?;
+
+            match op {
+                OpCode::Return | OpCode::NoMatch => {}
+                OpCode::Jump => worklist.push((jump_destination(operands), next_depth)),
+                OpCode::Loop => worklist.push((next - loop_distance(operands), next_depth)),
+                OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                    worklist.push((jump_destination(operands), next_depth));
+                    worklist.push((next, next_depth));
+                }
+                _ => worklist.push((next, next_depth)),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_operands(
+        &self,
+        op: OpCode,
+        operands: &[u8],
+        depth: usize,
+        next: usize,
+        instructions: &[Option<OpCode>],
+    ) -> Result<(), VerifyErr> {
+        use OpCode::*;
+
+        match op {
+            GetConstant | GetConstantLong => {
+                let index = match operands {
+                    [index] => *index as usize,
+                    [i1, i2, i3] => u32::from_be_bytes([0, *i1, *i2, *i3]) as usize,
+                    _ => unreachable!("Constants take one or three operands."),
+                };
+
+                if index >= self.chunk.constants.len() {
+                    return Err(VerifyErr::ConstantOutOfRange { index });
+                }
+            }
+            GetLocal => {
+                let index = operands[0] as usize;
+                if index >= depth {
+                    return Err(VerifyErr::LocalOutOfRange { index });
+                }
+            }
+            SetLocal => {
+                // The value is popped before the local is set.
+                let index = operands[0] as usize;
+                if index >= depth - 1 {
+                    return Err(VerifyErr::LocalOutOfRange { index });
+                }
+            }
+            GetUpvalue | SetUpvalue => {
+                let index = operands[0] as usize;
+                if index >= self.upvalue_count {
+                    return Err(VerifyErr::UpvalueOutOfRange { index });
+                }
+            }
+            Jump | JumpIfTrue | JumpIfFalse => {
+                let destination = jump_destination(operands);
+                if !matches!(instructions.get(destination), Some(Some(_))) {
+                    return Err(VerifyErr::InvalidJump { destination });
+                }
+            }
+            Loop => {
+                // A loop can't jump back past the start of the chunk.
+                let destination = next.checked_sub(loop_distance(operands));
+                if !matches!(destination.and_then(|d| instructions.get(d)), Some(Some(_))) {
+                    return Err(VerifyErr::InvalidJump {
+                        destination: destination.unwrap_or(0),
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn error(&self, kind: VerifyErr, offset: usize) -> VerifyError {
+        VerifyError {
+            kind,
+            function: self.name.clone(),
+            offset,
+            location: self.chunk.span_at(offset),
+        }
+    }
+}
+
+fn jump_destination(operands: &[u8]) -> usize {
+    u16::from_be_bytes([operands[0], operands[1]]) as usize
+}
+
+fn loop_distance(operands: &[u8]) -> usize {
+    u16::from_be_bytes([operands[0], operands[1]]) as usize
+}
@@ -0,0 +1,214 @@
+use chumsky::Parser;
+use pico_typechecker::{
+    ast::Program,
+    compiler::Compiler,
+    lexer::{lexer, Span},
+    parser,
+    token::Token,
+    value::Value,
+    vm::{
+        bytecode::{self, BytecodeError, MAGIC},
+        chunk::Chunk,
+        VM,
+    },
+};
+
+fn try_compiling_program(src: &str) -> Chunk {
+    let toks: Vec<(Token, Span)> = lexer().parse(src).unwrap();
+    let program: Program = parser::program_parser()
+        .parse(chumsky::Stream::from_iter(1..1, toks.into_iter()))
+        .unwrap();
+
+    let mut chunky = Chunk::new();
+    Compiler::new()
+        .compile_program(&mut chunky, &program)
+        .unwrap();
+    chunky
+}
+
+const SRC: &str = "
+    funk greet(name: string) -> fn() -> string {
+        fn() -> string { \"hello \" + name }
+    }
+
+    funk main() -> string {
+        let greeting = greet(\"pico\");
+        if 1 < 2 { greeting() } else { \"bye\" }
+    }
+";
+
+#[test]
+fn chunks_round_trip() {
+    let chunky = try_compiling_program(SRC);
+    let bytes = bytecode::serialize(&chunky).unwrap();
+
+    assert_eq!(&bytes[..4], MAGIC);
+    assert_eq!(bytecode::deserialize(&bytes).unwrap(), chunky);
+}
+
+#[test]
+fn loaded_files_run() {
+    let path = std::env::temp_dir().join("pico_loaded_files_run.jkc");
+    bytecode::write_file(&path, &try_compiling_program(SRC)).unwrap();
+
+    let mut vm = bytecode::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        vm.run().unwrap(),
+        Value::Str(Box::new("hello pico".to_string()))
+    );
+}
+
+#[test]
+fn rejects_corrupt_files() {
+    let bytes = bytecode::serialize(&try_compiling_program(SRC)).unwrap();
+
+    let mut corrupt = bytes.clone();
+    *corrupt.last_mut().unwrap() ^= 0xff;
+    assert!(matches!(
+        bytecode::deserialize(&corrupt),
+        Err(BytecodeError::ChecksumMismatch { .. })
+    ));
+
+    assert!(matches!(
+        bytecode::deserialize(b"#!/bin/sh"),
+        Err(BytecodeError::BadMagic)
+    ));
+
+    // Truncating the file also breaks the checksum.
+    assert!(matches!(
+        bytecode::deserialize(&bytes[..bytes.len() - 3]),
+        Err(BytecodeError::ChecksumMismatch { .. })
+    ));
+    assert!(matches!(
+        bytecode::deserialize(&bytes[..6]),
+        Err(BytecodeError::Truncated)
+    ));
+}
+
+#[test]
+fn rejects_other_versions() {
+    let mut bytes = bytecode::serialize(&Chunk::new()).unwrap();
+    bytes[4] = 99;
+
+    let err = bytecode::deserialize(&bytes).unwrap_err();
+
+    assert!(matches!(
+        err,
+        BytecodeError::UnsupportedVersion { found: 99 }
+    ));
+    assert!(err.to_string().contains("Unsupported bytecode version 99"));
+}
+
+#[test]
+fn closures_cant_be_serialized() {
+    let mut vm = VM::new(try_compiling_program(SRC));
+    vm.run().unwrap();
+
+    // The first slot holds the `greet` closure.
+    let mut chunky = Chunk::new();
+    chunky.add_constant(vm.values[0].clone());
+
+    assert!(matches!(
+        bytecode::serialize(&chunky),
+        Err(BytecodeError::UnsupportedConstant(_))
+    ));
+}
+
+#[test]
+fn struct_layouts_round_trip() {
+    let chunky = try_compiling_program(
+        "
+        struct Point { x: int, y: int }
+
+        funk main() -> int {
+            let p = Point { x: 3, y: 4 };
+            p.x + p.y
+        }
+    ",
+    );
+    let bytes = bytecode::serialize(&chunky).unwrap();
+    let loaded = bytecode::deserialize(&bytes).unwrap();
+
+    assert_eq!(loaded, chunky);
+    assert_eq!(VM::new(loaded).run().unwrap(), Value::Int(7));
+}
+
+#[test]
+fn floats_round_trip() {
+    let chunky = try_compiling_program("funk main() -> float { 1.25 * 4e-1 }");
+    let bytes = bytecode::serialize(&chunky).unwrap();
+    let loaded = bytecode::deserialize(&bytes).unwrap();
+
+    assert_eq!(loaded, chunky);
+    assert_eq!(VM::new(loaded).run().unwrap(), Value::Float(0.5));
+}
+
+#[test]
+fn float_constants_keep_their_bits() {
+    let floats = [0.1, -0.0, f64::INFINITY, f64::NAN];
+
+    let mut chunky = Chunk::new();
+    for float in floats {
+        chunky.add_constant(Value::Float(float));
+    }
+
+    let loaded = bytecode::deserialize(&bytecode::serialize(&chunky).unwrap()).unwrap();
+    let bits: Vec<u64> = loaded
+        .constants
+        .iter()
+        .map(|constant| match constant {
+            Value::Float(float) => float.to_bits(),
+            constant => panic!("Expected a float, got {constant}"),
+        })
+        .collect();
+
+    assert_eq!(bits, floats.map(f64::to_bits));
+}
+
+#[test]
+fn rejects_files_from_before_floats() {
+    let mut bytes =
+        bytecode::serialize(&try_compiling_program("funk main() -> float { 1.5 }")).unwrap();
+    bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+
+    assert!(matches!(
+        bytecode::deserialize(&bytes),
+        Err(BytecodeError::UnsupportedVersion { found: 1 })
+    ));
+}
+
+#[test]
+fn rejects_deeply_nested_functions() {
+    // Each level is a chunk holding a single function constant named `f`.
+    let depth = 5000;
+    let mut payload = Vec::new();
+    for _ in 0..depth {
+        payload.extend_from_slice(&0u32.to_le_bytes()); // code
+        payload.extend_from_slice(&1u32.to_le_bytes()); // constants
+        payload.push(4); // FN
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.push(b'f');
+        payload.extend_from_slice(&0u32.to_le_bytes()); // arity
+        payload.extend_from_slice(&0u32.to_le_bytes()); // captures
+    }
+    payload.extend_from_slice(&[0; 8]); // the innermost chunk's code and constants
+    for _ in 0..=depth {
+        payload.extend_from_slice(&0u32.to_le_bytes()); // lines
+    }
+
+    let checksum = payload.iter().fold(0x811c9dc5, |hash: u32, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    });
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&bytecode::VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes.extend_from_slice(&payload);
+
+    assert!(matches!(
+        bytecode::deserialize(&bytes),
+        Err(BytecodeError::TooDeep)
+    ));
+}
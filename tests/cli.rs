@@ -0,0 +1,211 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn pico(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pico"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+const FIB: &str = "
+funk fib(n: int) -> int {
+    if n < 2 { 1 } else { fib(n - 1) + fib(n - 2) }
+}
+
+funk main() -> int {
+    fib(10)
+}
+";
+
+#[test]
+fn run_prints_mains_result() {
+    let output = pico(&["run"], FIB);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "89\n");
+}
+
+#[test]
+fn run_reads_files() {
+    let path = std::env::temp_dir().join("pico_run_reads_files.jk");
+    std::fs::write(&path, FIB).unwrap();
+
+    let output = pico(&["run", path.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(stdout(&output), "89\n");
+}
+
+#[test]
+fn compiled_files_run() {
+    let dir = std::env::temp_dir();
+    let path = dir.join("pico_compiled_files_run.jk");
+    let out = dir.join("pico_compiled_files_run.jkc");
+    std::fs::write(&path, FIB).unwrap();
+
+    let output = pico(&["compile", path.to_str().unwrap()], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pico(&["run", out.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&out).unwrap();
+
+    assert_eq!(stdout(&output), "89\n");
+}
+
+#[test]
+fn compile_writes_to_the_output_flag() {
+    let out = std::env::temp_dir().join("pico_compile_writes_to_the_output_flag.jkc");
+
+    let output = pico(&["compile", "-", "-o", out.to_str().unwrap()], FIB);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = pico(&["disasm", out.to_str().unwrap()], "");
+    std::fs::remove_file(&out).unwrap();
+
+    assert!(stdout(&output).contains("Call"));
+
+    let output = pico(&["compile"], FIB);
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn runtime_errors_exit_with_a_status() {
+    let output = pico(&["run", "-"], "funk main() -> int { 1 / 0 }");
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr(&output).contains("Division by zero."));
+}
+
+#[test]
+fn check_reports_type_errors() {
+    let output = pico(&["check"], "funk main() -> int { true + 1 }");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("<stdin>:1:22"));
+
+    let output = pico(&["check"], FIB);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "main returns int\n");
+}
+
+#[test]
+fn parse_errors_are_diagnostics() {
+    let output = pico(&["ast"], "funk main() -> int { let x = ; x }");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("Unexpected ';'"));
+}
+
+#[test]
+fn out_of_range_ints_are_diagnostics() {
+    let output = pico(&["run"], "funk main() -> int { 99999999999999999999 }");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("The integer literal 99999999999999999999 is too large"));
+    assert!(stderr(&output).contains("<stdin>:1:22"));
+}
+
+#[test]
+fn tokens_ast_and_disasm() {
+    let output = pico(&["tokens"], "funk main() { 1 }");
+    assert!(stdout(&output).contains("Identifier { value: \"main\" }"));
+
+    let output = pico(&["ast"], "funk main() { 1 }");
+    assert!(stdout(&output).contains("Funk {"));
+
+    let output = pico(&["disasm"], FIB);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Call"));
+}
+
+#[test]
+fn unknown_commands_print_usage() {
+    let output = pico(&["frobnicate"], "");
+
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).contains("Usage: pico <command> [file]"));
+}
+
+#[test]
+fn doc_prints_documented_funks() {
+    let src = "
+/* Not part of the docs. */
+funk helper(x) { x }
+
+/// Applies `f` twice.
+///
+/// Handy for small examples.
+funk twice(f, x) { f(f(x)) }
+
+/// Doubles `n`.
+funk double(n: int) -> int { helper(n) * 2 }
+";
+    let output = pico(&["doc"], src);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "# <stdin>
+
+## twice
+
+```text
+twice: forall a. fn(fn(a) -> a, a) -> a
+```
+
+Applies `f` twice.
+
+Handy for small examples.
+
+## double
+
+```text
+double: fn(int) -> int
+```
+
+Doubles `n`.
+"
+    );
+
+    let output = pico(&["doc"], "/// Broken.\nfunk broken() { 1 + true }");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn doc_keeps_type_param_names() {
+    let src = "
+/// Pairs `k` up.
+funk pair<K, V>(k: K, v: V, f) -> (K, V) { f(k); (k, v) }
+";
+    let output = pico(&["doc"], src);
+
+    assert!(
+        stdout(&output).contains("pair: forall K V a. fn(K, V, fn(K) -> a) -> (K, V)"),
+        "{}",
+        stdout(&output)
+    );
+}
@@ -58,3 +58,103 @@ fn basic() {
         )
     }
 }
+
+#[test]
+fn floats_need_a_fraction_or_an_exponent() {
+    let tokens: Vec<Token> = lexer()
+        .parse("1.5 2e10 3.25E-2 4 5.x")
+        .unwrap()
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect();
+
+    let float = |value: &str| Token::Float {
+        value: value.to_string(),
+    };
+    assert_eq!(
+        tokens,
+        vec![
+            float("1.5"),
+            float("2e10"),
+            float("3.25e-2"),
+            Token::Int {
+                value: "4".to_string()
+            },
+            Token::Int {
+                value: "5".to_string()
+            },
+            Token::Dot,
+            Token::Identifier {
+                value: "x".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn lexes_integer_operators() {
+    let tokens: Vec<Token> = lexer()
+        .parse("% ** * & | ^ ~ << <= >> >=")
+        .unwrap()
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect();
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Percent,
+            Token::StarStar,
+            Token::Star,
+            Token::Ampersand,
+            Token::Pipe,
+            Token::Caret,
+            Token::Tilde,
+            Token::LessLess,
+            Token::LessEqual,
+            Token::Greater,
+            Token::Greater,
+            Token::GreaterEqual,
+        ]
+    );
+}
+
+#[test]
+fn comments_are_skipped() {
+    let src = "
+        // A line comment.
+        /// A doc comment, it's dropped as it isn't above a funk.
+        //// Not a doc comment.
+        1 /* a /* nested */ comment */ + 2 // The end.
+        /**/";
+    let tokens: Vec<Token> = lexer()
+        .parse(src)
+        .unwrap()
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect();
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Int {
+                value: "1".to_string()
+            },
+            Token::Plus,
+            Token::Int {
+                value: "2".to_string()
+            },
+        ]
+    );
+
+    assert!(lexer().parse("1 /* /* */").is_err());
+
+    let tokens = lexer().parse("/// Kept.\n// Skipped.\nfunk").unwrap();
+    assert_eq!(
+        tokens[0].0,
+        Token::DocComment {
+            value: " Kept.".to_string()
+        }
+    );
+    assert_eq!(tokens.len(), 2);
+}
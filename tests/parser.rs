@@ -1,6 +1,6 @@
 use chumsky::Parser;
 use pico_typechecker::{
-    ast::Expr,
+    ast::{Expr, Op, Pattern, Program},
     lexer::{lexer, Span},
     parser,
     token::Token,
@@ -13,6 +13,13 @@ fn try_parsing(src: &str) -> Expr {
         .unwrap()
 }
 
+fn try_parsing_program(src: &str) -> Program {
+    let toks: Vec<(Token, Span)> = lexer().parse(src).unwrap();
+    parser::program_parser()
+        .parse(chumsky::Stream::from_iter(1..1, toks.into_iter()))
+        .unwrap()
+}
+
 #[test]
 fn basic() {
     let exprs = try_parsing("let x = if true { 12 } else { 34 }; true");
@@ -90,6 +97,302 @@ fn can_parse_goal() {
     }
     "#;
 
-    let ast = try_parsing(src);
+    let _ast = try_parsing(src);
     // panic!("{ast:?}")
 }
+
+#[test]
+fn can_parse_program() {
+    let src = r#"
+    funk fib(n: int) -> int {
+        if n < 2 {
+            1
+        } else {
+            fib(n - 1) + fib(n - 2)
+        }
+    }
+
+    funk main() {
+        fib(10)
+    }
+    "#;
+
+    let program = try_parsing_program(src);
+
+    let names: Vec<&str> = program
+        .items
+        .iter()
+        .map(|item| match item {
+            Expr::Funk { name, .. } => name.as_str(),
+            _ => panic!("Expected only funks, got {item:?}"),
+        })
+        .collect();
+    assert_eq!(names, vec!["fib", "main"]);
+}
+
+#[test]
+fn program_rejects_trailing_expressions() {
+    let toks: Vec<(Token, Span)> = lexer().parse("funk main() { 1 } 2").unwrap();
+    let result = parser::program_parser().parse(chumsky::Stream::from_iter(1..1, toks.into_iter()));
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn semicolons_sequence_expressions() {
+    let expr = try_parsing("while true { break }; 1");
+
+    let Expr::Sequence { first, then, .. } = expr else {
+        panic!("Expected a sequence, got {expr:?}");
+    };
+    assert!(matches!(*first, Expr::While { .. }));
+    assert!(matches!(*then, Expr::Int { .. }));
+}
+
+#[test]
+fn let_initializers_end_at_the_semicolon() {
+    let expr = try_parsing("let x = 1; x; x");
+
+    let Expr::Let {
+        initializer, then, ..
+    } = expr
+    else {
+        panic!("Expected a let, got {expr:?}");
+    };
+    assert!(matches!(*initializer, Expr::Int { .. }));
+    assert!(matches!(*then, Expr::Sequence { .. }));
+}
+
+#[test]
+fn can_parse_assignment() {
+    let expr = try_parsing("let mut x = 1; x = x + 1");
+
+    let Expr::Let { mutable, then, .. } = expr else {
+        panic!("Expected a let, got {expr:?}");
+    };
+    assert!(mutable);
+    assert!(matches!(*then, Expr::Assign { .. }));
+}
+
+#[test]
+fn can_parse_lists_and_indexing() {
+    let expr = try_parsing("[1, 2, 3][0]");
+
+    let Expr::Index { list, .. } = expr else {
+        panic!("Expected an index, got {expr:?}");
+    };
+    let Expr::List { elements, .. } = *list else {
+        panic!("Expected a list, got {list:?}");
+    };
+    assert_eq!(elements.len(), 3);
+}
+
+#[test]
+fn builtins_can_be_called_like_methods() {
+    let expr = try_parsing("xs.map(f)");
+
+    let Expr::Call { callee, args, .. } = expr else {
+        panic!("Expected a call, got {expr:?}");
+    };
+    assert!(matches!(*callee, Expr::Identifier { ref value, .. } if value == "map"));
+    assert!(
+        matches!(&args[..], [Expr::Identifier { value: xs, .. }, Expr::Identifier { value: f, .. }] if xs == "xs" && f == "f")
+    );
+
+    // Other fields holding functions are called like any other value.
+    let expr = try_parsing("point.scale(2)");
+    let Expr::Call { callee, .. } = expr else {
+        panic!("Expected a call, got {expr:?}");
+    };
+    assert!(matches!(*callee, Expr::Field { .. }));
+}
+
+#[test]
+fn can_parse_structs() {
+    let expr = try_parsing("struct Point { x: int, y: int } Point { x: 1, y: 2 }.x");
+
+    let Expr::Struct { fields, then, .. } = expr else {
+        panic!("Expected a struct, got {expr:?}");
+    };
+    assert_eq!(fields.len(), 2);
+
+    let Expr::Field { expr, field, .. } = *then else {
+        panic!("Expected a field, got {then:?}");
+    };
+    assert_eq!(field, "x");
+    assert!(matches!(*expr, Expr::Construct { .. }));
+}
+
+#[test]
+fn blocks_after_identifiers_arent_struct_literals() {
+    let expr = try_parsing("let x = true; if x { 1 } else { 2 }");
+
+    let Expr::Let { then, .. } = expr else {
+        panic!("Expected a let, got {expr:?}");
+    };
+    assert!(matches!(*then, Expr::If { .. }));
+}
+
+#[test]
+fn can_parse_enums_and_matches() {
+    let expr = try_parsing(
+        "
+        enum Shape { Circle(int), Rect(int, int), Empty }
+        match Circle(1) {
+            Circle(r) if r > 0 => r,
+            Rect(w, 2) => w,
+            _ => 0,
+        }",
+    );
+
+    let Expr::Enum { variants, then, .. } = expr else {
+        panic!("Expected an enum, got {expr:?}");
+    };
+    assert_eq!(variants.len(), 3);
+    assert_eq!(variants[1].1.len(), 2);
+
+    let Expr::Match { arms, .. } = *then else {
+        panic!("Expected a match, got {then:?}");
+    };
+    assert_eq!(arms.len(), 3);
+    assert!(arms[0].guard.is_some());
+    assert!(matches!(arms[2].pattern, Pattern::Wildcard(_)));
+
+    let Pattern::Constructor { args, .. } = &arms[1].pattern else {
+        panic!("Expected a constructor pattern, got {:?}", arms[1].pattern);
+    };
+    assert!(matches!(args[0], Pattern::Binding { .. }));
+    assert!(matches!(args[1], Pattern::Literal(Expr::Int { .. })));
+}
+
+#[test]
+fn can_parse_try() {
+    let expr = try_parsing("parse(s)?.x");
+
+    let Expr::Field { expr, .. } = expr else {
+        panic!("Expected a field, got {expr:?}");
+    };
+    let Expr::Try { expr, .. } = *expr else {
+        panic!("Expected a try, got {expr:?}");
+    };
+    assert!(matches!(*expr, Expr::Call { .. }));
+}
+
+#[test]
+fn can_parse_tuples_and_destructuring() {
+    let expr = try_parsing("let (q, r): (int, int) = (7 / 2, (7 - 1),); ()");
+
+    let Expr::LetTuple {
+        names,
+        let_tipo,
+        initializer,
+        then,
+        ..
+    } = expr
+    else {
+        panic!("Expected a tuple let, got {expr:?}");
+    };
+    assert_eq!(names, vec!["q".to_string(), "r".to_string()]);
+    assert_eq!(let_tipo.unwrap().0.to_string(), "(int, int)");
+    assert!(matches!(*then, Expr::Unit(_)));
+
+    let Expr::Tuple { elements, .. } = *initializer else {
+        panic!("Expected a tuple, got {initializer:?}");
+    };
+    assert!(matches!(elements[1], Expr::Binary { .. }));
+
+    assert!(matches!(try_parsing("(1,)"), Expr::Tuple { .. }));
+    assert!(matches!(try_parsing("(1)"), Expr::Int { .. }));
+}
+
+#[test]
+fn integer_operators_have_precedence() {
+    let op = |expr: &Expr| match expr {
+        Expr::Binary { op, .. } | Expr::Unary { op, .. } => *op,
+        _ => panic!("Expected an operator, got {expr:?}"),
+    };
+
+    // `|` binds looser than `^`, which binds looser than `&`.
+    let expr = try_parsing("1 | 2 ^ 3 & 4 == 1");
+    assert_eq!(op(&expr), Op::EqualEqual);
+    let Expr::Binary { lhs, .. } = expr else {
+        unreachable!()
+    };
+    assert_eq!(op(&lhs), Op::BitOr);
+
+    // Shifts bind looser than `+` and `**` groups to the right, under unary minus.
+    let expr = try_parsing("1 << 2 + 3 >> 1");
+    assert_eq!(op(&expr), Op::ShiftRight);
+    let expr = try_parsing("-2 ** 3 ** 2");
+    assert_eq!(op(&expr), Op::Minus);
+    let Expr::Unary { rhs, .. } = expr else {
+        unreachable!()
+    };
+    let Expr::Binary { rhs, .. } = *rhs else {
+        panic!("Expected a power, got {rhs:?}");
+    };
+    assert_eq!(op(&rhs), Op::Power);
+
+    assert_eq!(op(&try_parsing("7 % 2")), Op::Modulo);
+    assert_eq!(op(&try_parsing("~7")), Op::BitNot);
+    assert_eq!(op(&try_parsing("2 > 1")), Op::Greater);
+    assert_eq!(op(&try_parsing("2 >= 1")), Op::GreaterEqual);
+    assert_eq!(op(&try_parsing("2 != 1")), Op::NotEqual);
+}
+
+#[test]
+fn nested_type_arguments_arent_a_shift() {
+    let expr = try_parsing("let xs: list<list<int>> = [[1]]; xs");
+
+    let Expr::Let { let_tipo, .. } = expr else {
+        panic!("Expected a let, got {expr:?}");
+    };
+    assert_eq!(let_tipo.unwrap().0.to_string(), "list<list<int>>");
+}
+
+#[test]
+fn doc_comments_go_on_funks() {
+    let src = "
+        /// Adds one.
+        ///
+        ///    Keeps indentation.
+        funk inc(x: int) -> int { x + 1 }
+
+        funk main() { inc(1) }
+    ";
+    let program = try_parsing_program(src);
+
+    let Expr::Funk { doc, location, .. } = &program.items[0] else {
+        panic!("Expected a funk, got {:?}", program.items[0]);
+    };
+    assert_eq!(doc.as_deref(), Some("Adds one.\n\n   Keeps indentation."));
+    assert!(src[location.clone()].starts_with("funk inc"));
+    assert!(matches!(&program.items[1], Expr::Funk { doc: None, .. }));
+
+    let expr = try_parsing("/// Doubles.\nfunk double(x) { x * 2 } double(2)");
+    assert!(matches!(expr, Expr::Funk { doc: Some(_), .. }));
+}
+
+#[test]
+fn doc_comments_elsewhere_are_comments() {
+    let program = try_parsing_program(
+        "
+        /// A point.
+        struct Point { x: int, y: int }
+
+        /// A shape.
+        enum Shape { Circle(int) }
+
+        funk main() -> int {
+            /// Not a funk.
+            let p = Point { x: 1, y: 2 };
+            p.x /// After an expression.
+        }
+        /// At the end.
+    ",
+    );
+    assert_eq!(program.items.len(), 3);
+
+    let expr = try_parsing("/// A let.\nlet x = 1; x /// The end.");
+    assert!(matches!(expr, Expr::Let { .. }));
+}
@@ -0,0 +1,117 @@
+use std::io::Cursor;
+
+use pico_typechecker::repl::Repl;
+
+fn eval_all(entries: &[&str]) -> Vec<Result<String, String>> {
+    let mut repl = Repl::new();
+    entries.iter().map(|entry| repl.eval(entry)).collect()
+}
+
+#[test]
+fn definitions_persist() {
+    let results = eval_all(&["let x = 40", "let y = x + 1", "y + 1"]);
+
+    assert_eq!(results[0], Ok("x : int".to_string()));
+    assert_eq!(results[1], Ok("y : int".to_string()));
+    assert_eq!(results[2], Ok("42 : int".to_string()));
+}
+
+#[test]
+fn funks_can_recurse() {
+    let results = eval_all(&[
+        "funk fact(n: int) -> int { if n < 2 { 1 } else { n * fact(n - 1) } }",
+        "fact(5)",
+    ]);
+
+    assert_eq!(results[0], Ok("fact : fn(int) -> int".to_string()));
+    assert_eq!(results[1], Ok("120 : int".to_string()));
+}
+
+#[test]
+fn closures_capture_earlier_definitions() {
+    let results = eval_all(&["let n = 2", "let add = fn(x) { x + n }", "add(3)"]);
+
+    assert_eq!(results[2], Ok("5 : int".to_string()));
+}
+
+#[test]
+fn errors_dont_change_the_state() {
+    let mut repl = Repl::new();
+
+    assert!(repl.eval("let x = 1").is_ok());
+    assert!(repl.eval("let y = x + true").is_err());
+    assert!(repl.eval("let z = 1 / 0").is_err());
+    assert!(repl.eval("y").is_err());
+    assert!(repl.eval("z").is_err());
+    assert_eq!(repl.eval("let w = x + 1"), Ok("w : int".to_string()));
+    assert_eq!(repl.eval("w"), Ok("2 : int".to_string()));
+}
+
+#[test]
+fn type_command_generalises() {
+    let mut repl = Repl::new();
+
+    assert_eq!(
+        repl.eval(":type fn(x) { x }"),
+        Ok("forall a. fn(a) -> a".to_string())
+    );
+    assert!(repl.eval("fn(x) { x }").is_ok());
+}
+
+#[test]
+fn reset_forgets_definitions() {
+    let mut repl = Repl::new();
+
+    assert!(repl.eval("let x = 1").is_ok());
+    assert!(repl.eval(":reset").is_ok());
+    assert!(repl.eval("x").is_err());
+}
+
+#[test]
+fn entries_continue_over_unclosed_brackets() {
+    let input = "funk double(n: int) -> int {\n  n * 2\n}\ndouble(21)\n:quit\n1\n";
+    let mut output = Vec::new();
+
+    Repl::new().run(Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.contains("double : fn(int) -> int"));
+    assert!(output.contains("42 : int"));
+    assert!(!output.contains("1 : int"));
+}
+
+#[test]
+fn errors_point_into_the_entry_they_came_from() {
+    let mut repl = Repl::new();
+
+    assert!(repl.eval("let x = 1").is_ok());
+    assert!(repl.eval("funk div(n: int) -> int {\n  n / 0\n}").is_ok());
+
+    let err = repl.eval("div(x)").unwrap_err();
+    assert!(err.contains("<repl:2>:2:3"), "{err}");
+    assert!(err.contains("n / 0"), "{err}");
+    assert!(err.contains("<repl:3>"), "{err}");
+}
+
+#[test]
+fn out_of_range_ints_are_errors() {
+    let mut repl = Repl::new();
+
+    let err = repl.eval("99999999999999999999").unwrap_err();
+    assert!(err.contains("too large"), "{err}");
+    assert_eq!(repl.eval("1 + 1"), Ok("2 : int".to_string()));
+}
+
+#[test]
+fn comments_dont_affect_continuation() {
+    let input = "1 + 1 // {\n/* a\n { still a comment\n*/ 2 + 2\n\"}\" // (\n";
+    let mut output = Vec::new();
+
+    Repl::new().run(Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.contains("2 : int"), "{output}");
+    assert!(output.contains("4 : int"), "{output}");
+    assert!(output.contains("} : string"), "{output}");
+    assert_eq!(output.matches("  ... ").count(), 2, "{output}");
+}
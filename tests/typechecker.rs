@@ -1,17 +1,13 @@
-use std::vec;
-
 use chumsky::Parser;
 
 use pico_typechecker::{
-    ast::Expr,
-    compiler::Compiler,
+    ast::{Expr, Program},
+    diagnostic::ToDiagnostic,
     lexer::{lexer, Span},
     parser,
     tipo::Tipo,
     token::Token,
     typechecker::*,
-    value::Value,
-    vm::{chunk::Chunk, opcode::OpCode, VM},
 };
 
 fn try_parsing(src: &str) -> Expr {
@@ -21,6 +17,13 @@ fn try_parsing(src: &str) -> Expr {
         .unwrap()
 }
 
+fn try_parsing_program(src: &str) -> Program {
+    let toks: Vec<(Token, Span)> = lexer().parse(src).unwrap();
+    parser::program_parser()
+        .parse(chumsky::Stream::from_iter(1..1, toks.into_iter()))
+        .unwrap()
+}
+
 #[test]
 fn dummy() {
     let expr = try_parsing(
@@ -40,3 +43,864 @@ fn dummy() {
 
     assert_eq!(tipo, Tipo::int_type())
 }
+
+#[test]
+fn type_errors_carry_spans() {
+    let src = "let x = 1; x + \"one\"";
+    let expr = try_parsing(src);
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+
+    assert!(matches!(err, TypeError::Binary { .. }));
+    assert_eq!(&src[err.location()], "x + \"one\"");
+}
+
+#[test]
+fn annotation_mismatch_points_at_annotation() {
+    let src = "let x: string = 12; x";
+    let expr = try_parsing(src);
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+    let labels = err.labels();
+
+    assert_eq!(&src[err.location()], "12");
+    assert_eq!(labels.len(), 1);
+    assert_eq!(&src[labels[0].location.clone()], "string");
+}
+
+#[test]
+fn diagnostics_render_line_and_column() {
+    let src = "let x = 1;\nlet y = true;\nx + y";
+    let expr = try_parsing(src);
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+    let rendered = err.to_diagnostic().render("main.jk", src);
+
+    assert!(rendered.contains("main.jk:3:1"), "{rendered}");
+    assert!(rendered.contains("x + y"), "{rendered}");
+    assert!(rendered.contains("^^|^^"), "{rendered}");
+}
+
+#[test]
+fn check_all_reports_every_error() {
+    let src = r#"
+        let a: int = "one";
+        let b = a + true;
+        let c = if 3 { 1 } else { 2 };
+        let d = nope;
+        d
+    "#;
+    let expr = try_parsing(src);
+
+    let errors = TypeChecker::new().check_all(&expr).unwrap_err();
+
+    assert_eq!(errors.len(), 4, "{errors:?}");
+    assert!(matches!(errors[0], TypeError::AnnotationMismatch { .. }));
+    assert!(matches!(errors[1], TypeError::Binary { .. }));
+    assert!(matches!(errors[2], TypeError::NonBoolCondition { .. }));
+    assert!(matches!(errors[3], TypeError::VarDoesntExist { .. }));
+}
+
+#[test]
+fn poisoned_expressions_dont_cascade() {
+    let src = "let x = nope + 1; let y = x * 2; y - 3";
+    let expr = try_parsing(src);
+
+    let errors = TypeChecker::new().check_all(&expr).unwrap_err();
+
+    assert_eq!(errors.len(), 1, "{errors:?}");
+}
+
+#[test]
+fn the_poison_type_cant_be_written() {
+    let program = try_parsing_program(
+        "
+        funk f(x: __error__) -> __error__ { x }
+        funk main() -> int { 1 + f(\"oops\") }
+    ",
+    );
+
+    assert!(TypeChecker::new().check_program(&program).is_err());
+}
+
+#[test]
+fn check_all_returns_tipo_without_errors() {
+    let expr = try_parsing("let x = 1; x + 2");
+
+    assert_eq!(TypeChecker::new().check_all(&expr), Ok(Tipo::int_type()));
+}
+
+#[test]
+fn infers_polymorphic_identity() {
+    let expr = try_parsing("fn(x) { x }");
+
+    let scheme = TypeChecker::new().infer_scheme(&expr).unwrap();
+
+    assert_eq!(scheme.to_string(), "forall a. fn(a) -> a");
+}
+
+#[test]
+fn let_bindings_are_generalized() {
+    let src = "let id = fn(x) { x }; if id(true) { id(1) } else { id(2) }";
+    let expr = try_parsing(src);
+
+    let tipo = TypeChecker::new().check_expr(&expr).unwrap();
+
+    assert_eq!(tipo, Tipo::int_type());
+}
+
+#[test]
+fn infers_unannotated_funk_params_and_return() {
+    let src = "funk double(n) { n + n } double(21)";
+    let expr = try_parsing(src);
+
+    let tipo = TypeChecker::new().check_expr(&expr).unwrap();
+
+    assert_eq!(tipo, Tipo::int_type());
+}
+
+#[test]
+fn infers_recursive_funk() {
+    let src = r#"
+    funk fib(n) {
+        if n < 2 {
+            1
+        } else {
+            fib(n - 1) + fib(n - 2)
+        }
+    }
+    fib
+    "#;
+    let expr = try_parsing(src);
+
+    let tipo = TypeChecker::new().check_expr(&expr).unwrap();
+
+    assert_eq!(tipo.to_string(), "fn(int) -> int");
+}
+
+#[test]
+fn occurs_check_rejects_infinite_types() {
+    let expr = try_parsing("fn(f) { f(f) }");
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+
+    assert!(matches!(err, TypeError::InfiniteType { .. }), "{err:?}");
+}
+
+#[test]
+fn lambda_bound_params_are_monomorphic() {
+    let expr = try_parsing("fn(f) { if f(true) { f(1) } else { 2 } }");
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+
+    assert!(matches!(err, TypeError::IncorrectArgType { .. }), "{err:?}");
+}
+
+#[test]
+fn generic_funks_are_instantiated_per_call() {
+    let src = r#"
+    funk apply<A, B>(x: A, f: fn(A) -> B) -> B {
+        f(x)
+    }
+    let n = apply(1, fn(n: int) -> int { n + 1 });
+    apply(n, fn(n: int) -> bool { n == 2 })
+    "#;
+    let expr = try_parsing(src);
+
+    let tipo = TypeChecker::new().check_expr(&expr).unwrap();
+
+    assert_eq!(tipo, Tipo::bool_type());
+}
+
+#[test]
+fn generic_funk_signature() {
+    let src = r#"
+    funk map<A, B>(xs: list<A>, f: fn(A) -> B) -> list<B> {
+        map(xs, f)
+    }
+    map
+    "#;
+    let expr = try_parsing(src);
+
+    let scheme = TypeChecker::new().infer_scheme(&expr).unwrap();
+
+    assert_eq!(
+        scheme.to_string(),
+        "forall a b. fn(list<a>, fn(a) -> b) -> list<b>"
+    );
+}
+
+#[test]
+fn type_params_are_opaque_in_the_body() {
+    let expr = try_parsing("funk bad<A>(x: A) -> A { x + 1 } bad");
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+
+    assert!(matches!(err, TypeError::Binary { .. }), "{err:?}");
+}
+
+#[test]
+fn program_funks_can_be_mutually_recursive() {
+    let src = r#"
+    funk main() {
+        is_even(10)
+    }
+
+    funk is_even(n) {
+        if n == 0 { true } else { is_odd(n - 1) }
+    }
+
+    funk is_odd(n) {
+        if n == 0 { false } else { is_even(n - 1) }
+    }
+    "#;
+    let program = try_parsing_program(src);
+
+    let tipo = TypeChecker::new().check_program(&program).unwrap();
+
+    assert_eq!(tipo, Tipo::bool_type());
+}
+
+#[test]
+fn annotated_generic_funks_are_polymorphic_within_a_program() {
+    let src = r#"
+    funk id<A>(x: A) -> A { x }
+
+    funk main() {
+        if id(true) { id(1) } else { 2 }
+    }
+    "#;
+    let program = try_parsing_program(src);
+
+    let tipo = TypeChecker::new().check_program(&program).unwrap();
+
+    assert_eq!(tipo, Tipo::int_type());
+}
+
+#[test]
+fn unannotated_funks_are_polymorphic_within_a_program() {
+    let src = r#"
+    funk main() -> int {
+        let s = twice_id("a");
+        id(1)
+    }
+
+    funk twice_id(x) { id(id(x)) }
+
+    funk id(x) { x }
+    "#;
+    let program = try_parsing_program(src);
+
+    let tipo = TypeChecker::new().check_program(&program).unwrap();
+
+    assert_eq!(tipo, Tipo::int_type());
+}
+
+#[test]
+fn mutually_recursive_funks_are_monomorphic_in_each_other() {
+    let src = r#"
+    funk f(x) { g(1); x }
+
+    funk g(x) { f(true); x }
+
+    funk main() { f(1) }
+    "#;
+    let program = try_parsing_program(src);
+
+    let err = TypeChecker::new().check_program(&program).unwrap_err();
+
+    assert!(matches!(err, TypeError::IncorrectArgType { .. }), "{err:?}");
+}
+
+#[test]
+fn program_needs_main() {
+    let program = try_parsing_program("funk helper() { 1 }");
+
+    let err = TypeChecker::new().check_program(&program).unwrap_err();
+
+    assert!(matches!(err, TypeError::MissingMain { .. }), "{err:?}");
+}
+
+#[test]
+fn items_are_checked_without_main() {
+    let program = try_parsing_program("funk id(x) { x } funk one() { 1 }");
+    let mut checker = TypeChecker::new();
+
+    checker.check_items(&program).unwrap();
+
+    assert_eq!(
+        checker.scheme_of("id").unwrap().to_string(),
+        "forall a. fn(a) -> a"
+    );
+    assert_eq!(
+        checker.scheme_of("one").unwrap().tipo,
+        Tipo::new_fn(vec![], Tipo::int_type())
+    );
+    assert_eq!(checker.scheme_of("main"), None);
+}
+
+#[test]
+fn funks_cant_be_defined_twice() {
+    let src = "funk f() { 1 } funk main() { f() } funk f() { 2 }";
+    let program = try_parsing_program(src);
+
+    let err = TypeChecker::new().check_program(&program).unwrap_err();
+
+    assert!(
+        matches!(err, TypeError::DuplicateDefinition { ref name, .. } if name == "f"),
+        "{err:?}"
+    );
+    assert_eq!(&src[err.location()], "funk f() { 2 }");
+    assert_eq!(&src[err.labels()[0].location.clone()], "funk f() { 1 }");
+}
+
+#[test]
+fn main_takes_no_arguments() {
+    let program = try_parsing_program("funk main(n: int) { n }");
+
+    let err = TypeChecker::new().check_program(&program).unwrap_err();
+
+    assert!(matches!(err, TypeError::InvalidMain { .. }), "{err:?}");
+}
+
+#[test]
+fn while_loops_are_unit() {
+    let expr = try_parsing("while 1 < 2 { if true { break } else { continue } }");
+
+    let tipo = TypeChecker::new().check_expr(&expr).unwrap();
+
+    assert_eq!(tipo, Tipo::unit_type());
+}
+
+#[test]
+fn while_conditions_are_bools() {
+    let expr = try_parsing("while 1 { 2 }");
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+
+    assert!(matches!(err, TypeError::NonBoolCondition { .. }), "{err:?}");
+}
+
+#[test]
+fn break_only_works_in_loops() {
+    let expr = try_parsing("break");
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+    assert!(matches!(err, TypeError::OutsideLoop { .. }), "{err:?}");
+
+    // A function can't break out of the loop it's declared in.
+    let expr = try_parsing("while true { let f = fn() { continue }; 1 }");
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+    assert!(matches!(err, TypeError::OutsideLoop { .. }), "{err:?}");
+}
+
+#[test]
+fn only_mutable_variables_can_be_assigned() {
+    let expr = try_parsing("let mut x = 1; x = x + 1; x");
+    assert_eq!(
+        TypeChecker::new().check_expr(&expr).unwrap(),
+        Tipo::int_type()
+    );
+
+    let expr = try_parsing("let x = 1; x = 2; x");
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+    assert!(
+        matches!(err, TypeError::ImmutableAssignment { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn assignment_cant_change_the_type() {
+    let expr = try_parsing("let mut x = 1; x = \"one\"; x");
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+
+    assert!(
+        matches!(err, TypeError::AssignmentMismatch { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn mutable_variables_arent_generalized() {
+    let expr = try_parsing("let mut f = fn(x) { x }; f = fn(x) { x + 1 }; f(true)");
+
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+
+    assert!(matches!(err, TypeError::IncorrectArgType { .. }), "{err:?}");
+}
+
+#[test]
+fn list_elements_have_the_same_type() {
+    let expr = try_parsing("[1, 2, 3]");
+    assert_eq!(
+        TypeChecker::new().check_expr(&expr).unwrap(),
+        Tipo::list_type(Tipo::int_type())
+    );
+
+    let expr = try_parsing("[1, true]");
+    let err = TypeChecker::new().check_expr(&expr).unwrap_err();
+    assert!(
+        matches!(err, TypeError::ListElementMismatch { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn indexing_needs_a_list_and_an_int() {
+    let expr = try_parsing("[\"a\", \"b\"][1]");
+    assert_eq!(
+        TypeChecker::new().check_expr(&expr).unwrap(),
+        Tipo::string_type()
+    );
+
+    let err = TypeChecker::new()
+        .check_expr(&try_parsing("[1][true]"))
+        .unwrap_err();
+    assert!(matches!(err, TypeError::NonIntIndex { .. }), "{err:?}");
+
+    let err = TypeChecker::new()
+        .check_expr(&try_parsing("1[0]"))
+        .unwrap_err();
+    assert!(matches!(err, TypeError::NotAList { .. }), "{err:?}");
+}
+
+#[test]
+fn builtins_are_generic() {
+    let expr = try_parsing(
+        "let xs = map([1, 2], fn(x) { x > 1 }); fold(xs, 0, fn(acc, b) { if b { acc + 1 } else { acc } })",
+    );
+
+    assert_eq!(
+        TypeChecker::new().check_expr(&expr).unwrap(),
+        Tipo::int_type()
+    );
+}
+
+#[test]
+fn variables_shadow_builtins() {
+    let expr = try_parsing("let len = fn(x: int) -> bool { x > 0 }; len(1)");
+
+    assert_eq!(
+        TypeChecker::new().check_expr(&expr).unwrap(),
+        Tipo::bool_type()
+    );
+}
+
+#[allow(clippy::result_large_err)]
+fn check(src: &str) -> Result<Tipo, TypeError> {
+    TypeChecker::new().check_expr(&try_parsing(src))
+}
+
+#[test]
+fn struct_fields_are_checked() {
+    let decl = "struct Point { x: int, y: int } ";
+
+    assert_eq!(
+        check(&format!("{decl} Point {{ y: 2, x: 1 }}.y")).unwrap(),
+        Tipo::int_type()
+    );
+
+    let err = check(&format!("{decl} Point {{ x: 1 }}")).unwrap_err();
+    assert!(matches!(err, TypeError::MissingFields { .. }), "{err:?}");
+
+    let err = check(&format!("{decl} Point {{ x: 1, y: 2, z: 3 }}")).unwrap_err();
+    assert!(matches!(err, TypeError::UnknownField { .. }), "{err:?}");
+
+    let err = check(&format!("{decl} Point {{ x: 1, y: true }}")).unwrap_err();
+    assert!(matches!(err, TypeError::FieldMismatch { .. }), "{err:?}");
+
+    let err = check(&format!("{decl} Point {{ x: 1, y: 2 }}.z")).unwrap_err();
+    assert!(matches!(err, TypeError::UnknownField { .. }), "{err:?}");
+}
+
+#[test]
+fn structs_are_declared_once() {
+    let src = "struct P { x: int } struct P { y: int } 1";
+    let err = check(src).unwrap_err();
+
+    assert!(matches!(err, TypeError::DuplicateType { .. }), "{err:?}");
+    assert_eq!(&src[err.location()], "struct P { y: int } 1");
+    assert_eq!(err.labels().len(), 1);
+
+    let err = check("struct int { x: int } 1").unwrap_err();
+    assert!(matches!(err, TypeError::DuplicateType { .. }), "{err:?}");
+}
+
+#[test]
+fn annotations_name_declared_types() {
+    let err = check("struct P { x: Q } 1").unwrap_err();
+    assert!(
+        matches!(err, TypeError::UnknownType { ref name, .. } if name == "Q"),
+        "{err:?}"
+    );
+
+    let src = "let x: list<Q> = []; 1";
+    let err = check(src).unwrap_err();
+    assert!(matches!(err, TypeError::UnknownType { .. }), "{err:?}");
+    assert_eq!(&src[err.location()], "list<Q>");
+
+    let err = check("fn(x: Q) { x }").unwrap_err();
+    assert!(matches!(err, TypeError::UnknownType { .. }), "{err:?}");
+
+    let src = "funk f<A>(x: A, y: int) -> list<B> { [] } f";
+    let err = check(src).unwrap_err();
+    assert!(matches!(err, TypeError::UnknownType { .. }), "{err:?}");
+    assert_eq!(&src[err.location()], "list<B>");
+
+    // Type parameters are in scope in the body, and fields can name types declared later.
+    let program = try_parsing_program(
+        "
+        struct Line { from: Point, to: Point }
+        struct Point { x: int, y: int }
+
+        funk id<T>(x: T) -> T {
+            let y: T = x;
+            let f = fn(z: T) -> T { z };
+            f(y)
+        }
+
+        funk main() -> int { id(1) }
+    ",
+    );
+    assert_eq!(
+        TypeChecker::new().check_program(&program),
+        Ok(Tipo::int_type())
+    );
+}
+
+#[test]
+fn type_params_are_declared_once() {
+    let src = "funk f<A, A>(x: A) -> A { x } f";
+    let err = check(src).unwrap_err();
+    assert!(
+        matches!(err, TypeError::DuplicateTypeParam { ref name, .. } if name == "A"),
+        "{err:?}"
+    );
+
+    let err = check("fn<T, T>(x: T) { x }").unwrap_err();
+    assert!(
+        matches!(err, TypeError::DuplicateTypeParam { .. }),
+        "{err:?}"
+    );
+
+    let err = check("enum E<T, T> { V(T) } 1").unwrap_err();
+    assert!(
+        matches!(err, TypeError::DuplicateTypeParam { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn only_structs_have_fields() {
+    let err = TypeChecker::new()
+        .check_expr(&try_parsing("let x = 1; x.y"))
+        .unwrap_err();
+    assert!(matches!(err, TypeError::NotAStruct { .. }), "{err:?}");
+
+    let err = TypeChecker::new()
+        .check_expr(&try_parsing("Point { x: 1 }"))
+        .unwrap_err();
+    assert!(matches!(err, TypeError::UnknownStruct { .. }), "{err:?}");
+}
+
+#[test]
+fn match_arms_have_the_same_type() {
+    let decl = "enum Shape { Circle(int), Rect(int, int) } let s = Circle(1); ";
+
+    assert_eq!(
+        check(&format!(
+            "{decl} match s {{ Circle(r) if r > 1 => r, Circle(_) => 0, Rect(w, h) => w * h }}"
+        ))
+        .unwrap(),
+        Tipo::int_type()
+    );
+
+    let err = check(&format!(
+        "{decl} match s {{ Circle(r) => r, Rect(_, _) => true }}"
+    ))
+    .unwrap_err();
+    assert!(matches!(err, TypeError::ArmMismatch { .. }), "{err:?}");
+    assert_eq!(err.to_diagnostic().labels.len(), 1);
+}
+
+#[test]
+fn matches_must_be_exhaustive() {
+    let decl = "enum Shape { Circle(int), Rect(int, int) } let s = Circle(1); ";
+
+    let err = check(&format!("{decl} match s {{ Circle(r) => r }}")).unwrap_err();
+    let TypeError::NonExhaustive { missing, .. } = &err else {
+        panic!("Expected a non exhaustive match, got {err:?}");
+    };
+    assert_eq!(missing, "Rect(_, _)");
+
+    // Guarded arms can fail so they don't count.
+    let err = check(&format!(
+        "{decl} match s {{ Circle(r) if r > 0 => r, Rect(_, _) => 0 }}"
+    ))
+    .unwrap_err();
+    assert!(matches!(err, TypeError::NonExhaustive { .. }), "{err:?}");
+
+    let err = check("match true { true => 1 }").unwrap_err();
+    let TypeError::NonExhaustive { missing, .. } = &err else {
+        panic!("Expected a non exhaustive match, got {err:?}");
+    };
+    assert_eq!(missing, "false");
+
+    let err = check("match 1 { 0 => 1, 1 => 2 }").unwrap_err();
+    assert!(matches!(err, TypeError::NonExhaustive { .. }), "{err:?}");
+}
+
+#[test]
+fn unreachable_arms_are_errors() {
+    let decl = "enum Shape { Circle(int), Rect(int, int) } let s = Circle(1); ";
+
+    let err = check(&format!("{decl} match s {{ _ => 0, Circle(r) => r }}")).unwrap_err();
+    assert!(matches!(err, TypeError::UnreachableArm { .. }), "{err:?}");
+
+    let err = check("match 1 { 1 => 1, 1 => 2, _ => 3 }").unwrap_err();
+    assert!(matches!(err, TypeError::UnreachableArm { .. }), "{err:?}");
+}
+
+#[test]
+fn patterns_match_the_scrutinee_type() {
+    let decl = "enum Shape { Circle(int), Rect(int, int) } ";
+
+    let err = check(&format!("{decl} match 1 {{ Circle(r) => r, _ => 0 }}")).unwrap_err();
+    assert!(matches!(err, TypeError::PatternMismatch { .. }), "{err:?}");
+
+    let err = check(&format!(
+        "{decl} match Circle(1) {{ Circle(r, h) => r, _ => 0 }}"
+    ))
+    .unwrap_err();
+    assert!(matches!(err, TypeError::ConstructorArity { .. }), "{err:?}");
+
+    let err = check(&format!(
+        "{decl} match Circle(1) {{ Circle(true) => 1, _ => 0 }}"
+    ))
+    .unwrap_err();
+    assert!(matches!(err, TypeError::PatternMismatch { .. }), "{err:?}");
+
+    let err = check("match 1 { Square(r) => r, _ => 0 }").unwrap_err();
+    assert!(
+        matches!(err, TypeError::UnknownConstructor { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn failed_patterns_dont_cascade() {
+    let expr = try_parsing("match Some(1) { Sume(n) => n, _ => 0 }");
+    let errors = TypeChecker::new().check_all(&expr).unwrap_err();
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(matches!(errors[0], TypeError::UnknownConstructor { .. }));
+
+    let expr = try_parsing("match Some(1) { Some(n, m) => n + m, None => 0 }");
+    let errors = TypeChecker::new().check_all(&expr).unwrap_err();
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(matches!(errors[0], TypeError::ConstructorArity { .. }));
+}
+
+#[test]
+fn patterns_bind_a_name_once() {
+    let src = "enum R { R(int, int) } match R(1, 2) { R(a, a) => a }";
+    let err = check(src).unwrap_err();
+    assert!(
+        matches!(err, TypeError::DuplicateBinding { ref name, .. } if name == "a"),
+        "{err:?}"
+    );
+
+    assert_eq!(
+        check("enum R { R(int, int) } match R(1, 2) { R(a, b) => a + b }"),
+        Ok(Tipo::int_type())
+    );
+}
+
+#[test]
+fn enums_and_constructors_are_declared_once() {
+    let src = "enum A { X, Y } enum B { Y, Z } 1";
+    let err = check(src).unwrap_err();
+    assert!(
+        matches!(err, TypeError::DuplicateConstructor { ref name, .. } if name == "Y"),
+        "{err:?}"
+    );
+    assert_eq!(&src[err.labels()[0].location.clone()], src);
+
+    let err = check("enum Maybe<T> { Some(T), Nothing } 1").unwrap_err();
+    assert!(
+        matches!(err, TypeError::DuplicateConstructor { .. }),
+        "{err:?}"
+    );
+    assert!(err.labels().is_empty());
+
+    let err = check("enum A { X } enum A { Y } 1").unwrap_err();
+    assert!(matches!(err, TypeError::DuplicateType { .. }), "{err:?}");
+
+    let err = check("enum option { Nope } 1").unwrap_err();
+    assert!(matches!(err, TypeError::DuplicateType { .. }), "{err:?}");
+}
+
+#[test]
+fn generic_enums_are_instantiated_per_use() {
+    let src = "
+        enum Maybe<T> { Just(T), Nothing }
+        let a = Just(1);
+        let b = Just(true);
+        let c = Nothing;
+        match b { Just(x) => x, Nothing => false }
+    ";
+
+    assert_eq!(check(src).unwrap(), Tipo::bool_type());
+
+    let err = check("enum Maybe<T> { Just(T), Nothing } match Just(1) { Just(true) => 1, _ => 0 }")
+        .unwrap_err();
+    assert!(matches!(err, TypeError::PatternMismatch { .. }), "{err:?}");
+}
+
+#[test]
+fn options_and_results_are_builtin() {
+    assert_eq!(
+        check("Some(1)").unwrap(),
+        Tipo::option_type(Tipo::int_type())
+    );
+
+    let src = "
+        let half = fn(x: int) -> result<int, string> {
+            if x / 2 * 2 == x { Ok(x / 2) } else { Err(\"odd\") }
+        };
+        match half(4) { Ok(x) => x, Err(_) => 0 }
+    ";
+    assert_eq!(check(src).unwrap(), Tipo::int_type());
+
+    let err = check("match Some(1) { Some(x) => x }").unwrap_err();
+    let TypeError::NonExhaustive { missing, .. } = &err else {
+        panic!("Expected a non exhaustive match, got {err:?}");
+    };
+    assert_eq!(missing, "None");
+}
+
+#[test]
+fn try_unwraps_and_matches_the_return_type() {
+    let src = "
+        let first = fn(xs: list<int>) -> option<int> {
+            if len(xs) > 0 { Some(xs[0]) } else { None }
+        };
+        let sum_firsts = fn(a, b) { Some(first(a)? + first(b)?) };
+        sum_firsts
+    ";
+    let scheme = TypeChecker::new().infer_scheme(&try_parsing(src)).unwrap();
+    assert_eq!(
+        scheme.to_string(),
+        "fn(list<int>, list<int>) -> option<int>"
+    );
+
+    let err = check("let f = fn(x: option<int>) -> int { x? }; f").unwrap_err();
+    assert!(matches!(err, TypeError::TryMismatch { .. }), "{err:?}");
+
+    let err =
+        check("let f = fn(x: result<int, string>) -> result<int, bool> { Ok(x?) }; f").unwrap_err();
+    assert!(matches!(err, TypeError::TryMismatch { .. }), "{err:?}");
+
+    let err = check("let f = fn(x: int) { Some(x?) }; f").unwrap_err();
+    assert!(matches!(err, TypeError::NotTryable { .. }), "{err:?}");
+
+    let err = check("Some(1)?").unwrap_err();
+    assert!(
+        matches!(err, TypeError::TryOutsideFunction { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn tuples_are_destructured_by_let() {
+    let src = "
+        let divmod = fn(a: int, b: int) -> (int, int) { (a / b, a - a / b * b) };
+        let (q, r) = divmod(7, 2);
+        (q + r, \"done\")
+    ";
+    assert_eq!(
+        check(src).unwrap(),
+        Tipo::tuple_type(vec![Tipo::int_type(), Tipo::string_type()])
+    );
+
+    // Each element is generalized on it's own.
+    let src = "let (id, x) = (fn(x) { x }, 1); (id(x), id(true))";
+    assert_eq!(check(src).unwrap().to_string(), "(int, bool)");
+
+    let err = check("let (a, b) = (1, 2, 3); a").unwrap_err();
+    assert!(
+        matches!(
+            err,
+            TypeError::TupleArity {
+                expected: 2,
+                got: 3,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+
+    let err = check("let (a, b) = 1; a").unwrap_err();
+    assert!(matches!(err, TypeError::NotATuple { .. }), "{err:?}");
+
+    let err = check("let (a, b): (int, bool) = (1, 2); a").unwrap_err();
+    assert!(
+        matches!(err, TypeError::AnnotationMismatch { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn unit_is_the_empty_tuple() {
+    assert_eq!(check("()").unwrap().to_string(), "()");
+    assert_eq!(
+        check("let f = fn() -> () { () }; f()").unwrap(),
+        Tipo::unit_type()
+    );
+}
+
+#[test]
+fn floats_dont_mix_with_ints() {
+    assert_eq!(check("1.5 * 2.0 - 0.5").unwrap(), Tipo::float_type());
+    assert_eq!(check("1.5 < 2.0").unwrap(), Tipo::bool_type());
+    assert_eq!(check("-1.5").unwrap(), Tipo::float_type());
+    assert_eq!(
+        check("let half = fn(x) { x / 2.0 }; half")
+            .unwrap()
+            .to_string(),
+        "fn(float) -> float"
+    );
+    assert_eq!(check("float(3) / 2.0").unwrap(), Tipo::float_type());
+    assert_eq!(check("int(1.5) + 1").unwrap(), Tipo::int_type());
+
+    let err = check("1 + 1.5").unwrap_err();
+    assert!(matches!(err, TypeError::MixedNumbers { .. }), "{err:?}");
+
+    let err = check("let x = 2.0; x < 3").unwrap_err();
+    assert!(matches!(err, TypeError::MixedNumbers { .. }), "{err:?}");
+}
+
+#[test]
+fn bitwise_operators_only_take_ints() {
+    assert_eq!(check("17 % 5 + 2 ** 3").unwrap(), Tipo::int_type());
+    assert_eq!(check("7.5 % 2.0 ** 0.5").unwrap(), Tipo::float_type());
+    assert_eq!(
+        check("~(6 & 3 | 1 ^ 2) << 1 >> 1").unwrap(),
+        Tipo::int_type()
+    );
+    assert_eq!(
+        check("let mask = fn(x) { x & 255 }; mask")
+            .unwrap()
+            .to_string(),
+        "fn(int) -> int"
+    );
+
+    let err = check("1.5 & 2.5").unwrap_err();
+    assert!(matches!(err, TypeError::Binary { .. }), "{err:?}");
+
+    let err = check("~true").unwrap_err();
+    assert!(matches!(err, TypeError::Unary { .. }), "{err:?}");
+
+    let err = check("2 ** 0.5").unwrap_err();
+    assert!(matches!(err, TypeError::MixedNumbers { .. }), "{err:?}");
+}
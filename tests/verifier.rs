@@ -0,0 +1,175 @@
+use std::rc::Rc;
+
+use chumsky::Parser;
+use pico_typechecker::{
+    ast::Program,
+    compiler::Compiler,
+    function::Function,
+    lexer::{lexer, Span},
+    parser,
+    token::Token,
+    value::Value,
+    vm::{
+        chunk::Chunk,
+        opcode::OpCode,
+        verifier::{verify, VerifyErr},
+        RuntimeErr, VM,
+    },
+};
+
+fn try_compiling_program(src: &str) -> Chunk {
+    let toks: Vec<(Token, Span)> = lexer().parse(src).unwrap();
+    let program: Program = parser::program_parser()
+        .parse(chumsky::Stream::from_iter(1..1, toks.into_iter()))
+        .unwrap();
+
+    let mut chunky = Chunk::new();
+    Compiler::new()
+        .compile_program(&mut chunky, &program)
+        .unwrap();
+    chunky
+}
+
+fn verify_err(chunky: &Chunk) -> VerifyErr {
+    verify(chunky).unwrap_err().kind
+}
+
+#[test]
+fn compiled_programs_verify() {
+    let chunky = try_compiling_program(
+        "
+        funk fib(n: int) -> int {
+            if n < 2 { 1 } else { fib(n - 1) + fib(n - 2) }
+        }
+
+        funk main() -> int {
+            let add = fn(a: int) -> int { a + fib(3) };
+            add(1)
+        }
+    ",
+    );
+
+    assert_eq!(verify(&chunky), Ok(()));
+}
+
+#[test]
+fn rejects_invalid_opcodes() {
+    let mut chunky = Chunk::new();
+    chunky.code = vec![OpCode::Unit as u8, 200];
+
+    assert_eq!(verify_err(&chunky), VerifyErr::InvalidOpCode(200));
+}
+
+#[test]
+fn rejects_truncated_operands() {
+    let mut chunky = Chunk::new();
+    chunky.code = vec![OpCode::Unit as u8, OpCode::Jump as u8, 0];
+
+    assert_eq!(verify_err(&chunky), VerifyErr::TruncatedOperands);
+}
+
+#[test]
+fn rejects_jumps_into_operands() {
+    let mut chunky = Chunk::new();
+    chunky.add_constant(Value::Int(1));
+    chunky.write_opcode(OpCode::Jump, &[0, 4], 0..1);
+    chunky.write_opcode(OpCode::GetConstant, &[0], 0..1);
+    chunky.write_opcode(OpCode::Return, &[], 0..1);
+
+    assert_eq!(
+        verify_err(&chunky),
+        VerifyErr::InvalidJump { destination: 4 }
+    );
+}
+
+#[test]
+fn rejects_out_of_range_indices() {
+    let mut chunky = Chunk::new();
+    chunky.write_opcode(OpCode::GetConstant, &[3], 0..1);
+    chunky.write_opcode(OpCode::Return, &[], 0..1);
+
+    assert_eq!(
+        verify_err(&chunky),
+        VerifyErr::ConstantOutOfRange { index: 3 }
+    );
+
+    let mut chunky = Chunk::new();
+    chunky.write_opcode(OpCode::Unit, &[], 0..1);
+    chunky.write_opcode(OpCode::GetLocal, &[1], 0..1);
+    chunky.write_opcode(OpCode::Return, &[], 0..1);
+
+    assert_eq!(verify_err(&chunky), VerifyErr::LocalOutOfRange { index: 1 });
+}
+
+#[test]
+fn rejects_stack_underflow() {
+    let mut chunky = Chunk::new();
+    chunky.write_opcode(OpCode::True, &[], 0..1);
+    chunky.write_opcode(OpCode::Add, &[], 0..1);
+    chunky.write_opcode(OpCode::Return, &[], 0..1);
+
+    assert_eq!(verify_err(&chunky), VerifyErr::StackUnderflow);
+}
+
+#[test]
+fn rejects_inconsistent_stacks() {
+    // The truthy path pushes one more value than the falsy path.
+    let mut chunky = Chunk::new();
+    chunky.write_opcode(OpCode::True, &[], 0..1);
+    chunky.write_opcode(OpCode::JumpIfFalse, &[0, 6], 0..1);
+    chunky.write_opcode(OpCode::Unit, &[], 0..1);
+    chunky.write_opcode(OpCode::Unit, &[], 0..1);
+    chunky.write_opcode(OpCode::Return, &[], 0..1);
+
+    assert!(matches!(
+        verify_err(&chunky),
+        VerifyErr::InconsistentStack { .. }
+    ));
+}
+
+#[test]
+fn rejects_falling_off_the_end() {
+    let mut chunky = Chunk::new();
+    chunky.write_opcode(OpCode::Unit, &[], 0..1);
+
+    assert_eq!(verify_err(&chunky), VerifyErr::FallsOffEnd);
+}
+
+#[test]
+fn vm_refuses_to_run_invalid_chunks() {
+    let mut chunky = Chunk::new();
+    chunky.write_opcode(OpCode::Unit, &[], 0..1);
+    chunky.write_opcode(OpCode::GetLocal, &[9], 2..3);
+    chunky.write_opcode(OpCode::Return, &[], 0..1);
+
+    let err = VM::new(chunky).run().unwrap_err();
+
+    assert!(matches!(err.kind, RuntimeErr::InvalidBytecode(_)));
+    assert_eq!(err.location, 2..3);
+}
+
+#[test]
+fn rejects_loops_before_the_chunk() {
+    let mut chunky = Chunk::new();
+    chunky.write_opcode(OpCode::Loop, &[0, 4], 0..1);
+    chunky.write_opcode(OpCode::Unit, &[], 0..1);
+    chunky.write_opcode(OpCode::Return, &[], 0..1);
+
+    assert!(matches!(verify_err(&chunky), VerifyErr::InvalidJump { .. }));
+}
+
+#[test]
+fn verifies_deeply_nested_functions() {
+    let mut chunky = Chunk::new();
+    chunky.code = vec![200];
+
+    for _ in 0..2_000 {
+        let mut outer = Chunk::new();
+        outer.add_constant(Value::Fn(Rc::new(Function::new("f", 0, chunky))));
+        outer.write_opcode(OpCode::Unit, &[], 0..1);
+        outer.write_opcode(OpCode::Return, &[], 0..1);
+        chunky = outer;
+    }
+
+    assert_eq!(verify_err(&chunky), VerifyErr::InvalidOpCode(200));
+}
@@ -0,0 +1,708 @@
+use chumsky::Parser;
+use pico_typechecker::{
+    ast::{Expr, Program},
+    compiler::Compiler,
+    diagnostic::ToDiagnostic,
+    lexer::{lexer, Span},
+    parser,
+    token::Token,
+    typechecker::TypeChecker,
+    value::Value,
+    vm::{chunk::Chunk, opcode::OpCode, Overflow, RuntimeErr, RuntimeError, FRAMES_MAX, VM},
+};
+
+fn try_parsing(src: &str) -> Expr {
+    let toks: Vec<(Token, Span)> = lexer().parse(src).unwrap();
+    parser::expr_parser()
+        .parse(chumsky::Stream::from_iter(1..1, toks.into_iter()))
+        .unwrap()
+}
+
+fn try_parsing_program(src: &str) -> Program {
+    let toks: Vec<(Token, Span)> = lexer().parse(src).unwrap();
+    parser::program_parser()
+        .parse(chumsky::Stream::from_iter(1..1, toks.into_iter()))
+        .unwrap()
+}
+
+fn try_running(src: &str) -> Result<Value, RuntimeError> {
+    try_running_with(src, Overflow::Checked)
+}
+
+fn try_running_with(src: &str, overflow: Overflow) -> Result<Value, RuntimeError> {
+    let expr = try_parsing(src);
+    let mut chunky = Chunk::new();
+
+    Compiler::new().compile(&mut chunky, &expr).unwrap();
+    chunky.write_opcode(OpCode::Return, &[], 0..0);
+
+    VM::new(chunky).with_overflow(overflow).run()
+}
+
+fn try_running_program(src: &str) -> Result<Value, RuntimeError> {
+    let program = try_parsing_program(src);
+    let mut chunky = Chunk::new();
+
+    Compiler::new()
+        .compile_program(&mut chunky, &program)
+        .unwrap();
+
+    VM::new(chunky).run()
+}
+
+#[test]
+fn binary_ops_keep_operand_order() {
+    assert_eq!(try_running("10 - 3").unwrap(), Value::Int(7));
+    assert_eq!(try_running("12 / 4").unwrap(), Value::Int(3));
+    assert_eq!(try_running("1 < 2").unwrap(), Value::Bool(true));
+}
+
+#[test]
+fn if_else_takes_the_right_branch() {
+    assert_eq!(
+        try_running("if 1 < 2 { 7 } else { 9 }").unwrap(),
+        Value::Int(7)
+    );
+    assert_eq!(
+        try_running("if 2 < 1 { 7 } else { 9 }").unwrap(),
+        Value::Int(9)
+    );
+}
+
+#[test]
+fn let_shadowing() {
+    let value = try_running("let x = 1; let y = let x = 10; x + 1; x + y").unwrap();
+
+    assert_eq!(value, Value::Int(12));
+}
+
+#[test]
+fn can_call_fns() {
+    let value = try_running("let add = fn(a: int, b: int) -> int { a - b }; add(10, 4)").unwrap();
+
+    assert_eq!(value, Value::Int(6));
+}
+
+#[test]
+fn funks_can_recurse() {
+    let src = "
+        funk fib(n: int) -> int {
+            if n < 2 { 1 } else { fib(n - 1) + fib(n - 2) }
+        }
+        fib(10)
+    ";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(89));
+}
+
+#[test]
+fn can_run_program() {
+    let src = "
+        funk fib(n: int) -> int {
+            if n < 2 { 1 } else { fib(n - 1) + fib(n - 2) }
+        }
+
+        funk main() -> int {
+            let add = fn(a: int, b: int) -> int { a + b };
+            add(1, 2)
+        }
+    ";
+
+    assert_eq!(try_running_program(src).unwrap(), Value::Int(3));
+}
+
+#[test]
+fn unbounded_recursion_overflows() {
+    let src = "
+        funk forever(n: int) -> int { forever(n + 1) }
+        forever(0)
+    ";
+
+    let err = try_running(src).unwrap_err();
+
+    assert!(matches!(err.kind, RuntimeErr::StackOverflow));
+}
+
+#[test]
+fn fns_can_capture_locals() {
+    let value =
+        try_running("let x = 40; let add_x = fn(n: int) -> int { n + x }; add_x(2)").unwrap();
+
+    assert_eq!(value, Value::Int(42));
+}
+
+#[test]
+fn closures_outlive_the_captured_scope() {
+    let src = "
+        funk make_adder(x: int) -> fn(int) -> int {
+            fn(n: int) -> int { n + x }
+        }
+        let add_ten = make_adder(10);
+        let add_one = make_adder(1);
+        add_ten(5) - add_one(2)
+    ";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(12));
+}
+
+#[test]
+fn closures_capture_through_nested_fns() {
+    let src = "
+        funk outer(a: int) -> fn() -> fn() -> int {
+            fn() -> fn() -> int { fn() -> int { a * 2 } }
+        }
+        outer(21)()()
+    ";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(42));
+}
+
+#[test]
+fn main_can_call_other_funks() {
+    let src = "
+        funk main() -> int {
+            double(fib(5))
+        }
+
+        funk fib(n: int) -> int {
+            if n < 2 { 1 } else { fib(n - 1) + fib(n - 2) }
+        }
+
+        funk double(n: int) -> int { n * 2 }
+    ";
+
+    assert_eq!(try_running_program(src).unwrap(), Value::Int(16));
+}
+
+#[test]
+fn runtime_errors_point_at_the_failing_instruction() {
+    let src = "funk f(n: int) -> int { n(1) } f(2)";
+    let err = try_running(src).unwrap_err();
+
+    assert_eq!(&src[err.location.clone()], "n(1)");
+    assert_eq!(err.trace.len(), 2);
+    assert_eq!(err.trace[0].function, "<fn f>");
+    assert_eq!(err.trace[1].function, "<fn script>");
+    assert_eq!(&src[err.trace[1].location.clone().unwrap()], "f(2)");
+}
+
+#[test]
+fn runtime_errors_render_with_a_stack_trace() {
+    let src = "funk f(n: int) -> int { n(1) } f(2)";
+    let err = try_running(src).unwrap_err();
+    let rendered = err.to_diagnostic().render("test.pico", src);
+
+    assert!(rendered.contains("Can't call the value '2'."));
+    assert!(rendered.contains("<fn f> called in <fn script>"));
+}
+
+#[test]
+fn recursive_calls_are_labelled_once() {
+    let src = "funk forever(n: int) -> int { forever(n + 1) } forever(0)";
+    let err = try_running(src).unwrap_err();
+    let diagnostic = err.to_diagnostic();
+
+    assert_eq!(diagnostic.labels.len(), 2, "{:?}", diagnostic.labels);
+    assert_eq!(
+        diagnostic.labels[0].message,
+        format!(
+            "<fn forever> called in <fn forever> {} times",
+            FRAMES_MAX - 2
+        )
+    );
+    assert_eq!(
+        diagnostic.labels[1].message,
+        "<fn forever> called in <fn script>"
+    );
+}
+
+#[test]
+fn integer_overflow_is_an_error() {
+    let src = "1 + 9223372036854775807 * 1";
+    let err = try_running(src).unwrap_err();
+
+    assert!(matches!(err.kind, RuntimeErr::IntegerOverflow));
+    assert_eq!(&src[err.location], src);
+
+    let err = try_running("-(0 - 9223372036854775807 - 1)").unwrap_err();
+    assert!(matches!(err.kind, RuntimeErr::IntegerOverflow));
+}
+
+#[test]
+fn division_by_zero_is_an_error() {
+    let src = "let zero = 0; 10 / zero";
+    let err = try_running(src).unwrap_err();
+
+    assert!(matches!(err.kind, RuntimeErr::DivisionByZero));
+    assert_eq!(&src[err.location], "10 / zero");
+
+    let err = try_running_with("1 / 0", Overflow::Wrapping).unwrap_err();
+    assert!(matches!(err.kind, RuntimeErr::DivisionByZero));
+}
+
+#[test]
+fn overflow_can_wrap_or_saturate() {
+    let src = "9223372036854775807 + 1";
+
+    assert_eq!(
+        try_running_with(src, Overflow::Wrapping).unwrap(),
+        Value::Int(i64::MIN)
+    );
+    assert_eq!(
+        try_running_with(src, Overflow::Saturating).unwrap(),
+        Value::Int(i64::MAX)
+    );
+    assert_eq!(
+        try_running_with("(0 - 9223372036854775807 - 1) / -1", Overflow::Saturating).unwrap(),
+        Value::Int(i64::MAX)
+    );
+}
+
+#[test]
+fn large_programs_use_wide_jumps_and_long_constants() {
+    let sum = (1..=300)
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let src = format!("if 1 < 2 {{ {sum} }} else {{ 0 }}");
+
+    assert_eq!(try_running(&src).unwrap(), Value::Int(45150));
+
+    let src = format!("if 2 < 1 {{ {sum} }} else {{ 7 }}");
+
+    assert_eq!(try_running(&src).unwrap(), Value::Int(7));
+}
+
+#[test]
+fn repeated_literals_share_a_constant() {
+    let expr = try_parsing("let a = 1 + 1; let b = \"hi\"; if a == 2 { b } else { \"hi\" }");
+    let mut chunky = Chunk::new();
+
+    Compiler::new().compile(&mut chunky, &expr).unwrap();
+
+    assert_eq!(
+        chunky.constants,
+        vec![
+            Value::Int(1),
+            Value::Str(Box::new("hi".to_string())),
+            Value::Int(2)
+        ]
+    );
+}
+
+#[test]
+fn while_loops_stop_at_break() {
+    let value = try_running(
+        "while false { 1 }; while true { let x = 2; if x == 2 { break } else { continue } }; 3",
+    );
+
+    assert_eq!(value.unwrap(), Value::Int(3));
+}
+
+#[test]
+fn break_pops_the_loop_locals() {
+    let src = "let a = 1; while true { let b = 2; let c = { let d = 3; break }; 4 }; a";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(1));
+}
+
+#[test]
+fn assignment_updates_locals() {
+    let src =
+        "let mut i = 0; let mut total = 0; while i < 5 { i = i + 1; total = total + i }; total";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(15));
+}
+
+#[test]
+fn closures_can_assign_captured_variables() {
+    // `n` is still on the stack while `inc` runs, `count` has been closed over.
+    let src = "
+        let mut n = 0;
+        let inc = fn() { n = n + 1 };
+        inc(); inc();
+        let counter = fn() { let mut count = 0; fn() { count = count + 1; count } };
+        let next = counter();
+        next(); next();
+        n + next()
+    ";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(5));
+}
+
+#[test]
+fn lists_can_be_indexed() {
+    assert_eq!(try_running("[10, 20, 30][1]").unwrap(), Value::Int(20));
+    assert_eq!(try_running("len(push([1, 2], 3))").unwrap(), Value::Int(3));
+}
+
+#[test]
+fn indexing_out_of_bounds_is_an_error() {
+    let err = try_running("[1, 2, 3][3]").unwrap_err();
+    assert!(
+        matches!(err.kind, RuntimeErr::IndexOutOfBounds { index: 3, len: 3 }),
+        "{err:?}"
+    );
+
+    let err = try_running("[1, 2, 3][0 - 1]").unwrap_err();
+    assert!(
+        matches!(err.kind, RuntimeErr::IndexOutOfBounds { index: -1, .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn builtins_call_back_into_closures() {
+    let src = "
+        let offset = 1;
+        let xs = map([1, 2, 3, 4], fn(x) { x + offset });
+        let evens = filter(xs, fn(x) { x / 2 * 2 == x });
+        fold(evens, 0, fn(acc, x) { acc + x })
+    ";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(6));
+
+    let src = "fold(map([1, 2, 3], fn(x) { x * 2 }), 0, fn(acc, x) { acc + x })";
+    assert_eq!(try_running(src).unwrap(), Value::Int(12));
+}
+
+#[test]
+fn builtins_can_be_called_like_methods() {
+    let src =
+        "[1, 2, 3, 4].map(fn(x) { x * x }).filter(fn(x) { x > 1 }).fold(0, fn(acc, x) { acc + x })";
+    assert_eq!(try_running(src).unwrap(), Value::Int(29));
+
+    assert_eq!(try_running("[1, 2].push(3).len()").unwrap(), Value::Int(3));
+}
+
+#[test]
+fn errors_in_callbacks_keep_the_trace() {
+    let err = try_running("map([1, 0], fn(x) { 1 / x })").unwrap_err();
+
+    assert!(matches!(err.kind, RuntimeErr::DivisionByZero), "{err:?}");
+    assert_eq!(err.trace.len(), 2);
+}
+
+#[test]
+fn structs_store_their_fields() {
+    let src = "
+        struct Point { x: int, y: int }
+        struct Line { from: Point, to: Point }
+        let line = Line { to: Point { x: 4, y: 6 }, from: Point { y: 2, x: 1 } };
+        line.to.y - line.from.x
+    ";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(5));
+}
+
+#[test]
+fn records_display_their_fields() {
+    let value = try_running("struct Point { x: int, y: int } Point { y: 2, x: 1 }").unwrap();
+
+    assert_eq!(value.to_string(), "Point { x: 1, y: 2 }");
+}
+
+#[test]
+fn match_takes_the_first_matching_arm() {
+    let area = "
+        enum Shape { Circle(int), Rect(int, int), Empty }
+        let area = fn(s) {
+            match s {
+                Circle(r) if r < 0 => 0,
+                Circle(r) => 3 * r * r,
+                Rect(w, 0) => w,
+                Rect(w, h) => w * h,
+                Empty => 0,
+            }
+        };
+    ";
+
+    for (shape, expected) in [
+        ("Circle(-1)", 0),
+        ("Circle(2)", 12),
+        ("Rect(5, 0)", 5),
+        ("Rect(2, 3)", 6),
+        ("Empty", 0),
+    ] {
+        let value = try_running(&format!("{area} area({shape})")).unwrap();
+        assert_eq!(value, Value::Int(expected), "{shape}");
+    }
+}
+
+#[test]
+fn nested_patterns_and_literals_match() {
+    let src = "
+        enum Maybe<T> { Just(T), Nothing }
+        let describe = fn(m) {
+            match m {
+                Just(Just(\"a\")) => 1,
+                Just(Just(s)) => 2,
+                Just(Nothing) => 3,
+                Nothing => 4,
+            }
+        };
+        describe(Just(Just(\"a\"))) * 1000 + describe(Just(Just(\"b\"))) * 100
+            + describe(Just(Nothing)) * 10 + describe(Nothing)
+    ";
+
+    assert_eq!(try_running(src).unwrap(), Value::Int(1234));
+}
+
+#[test]
+fn constructors_are_values() {
+    let src = "
+        enum Maybe<T> { Just(T), Nothing }
+        map([1, 2], Just)
+    ";
+
+    assert_eq!(try_running(src).unwrap().to_string(), "[Just(1), Just(2)]");
+
+    let src = "enum Maybe<T> { Just(T), Nothing } let Just = fn(x) { x }; Just(1)";
+    assert_eq!(try_running(src).unwrap(), Value::Int(1));
+}
+
+#[test]
+fn programs_can_match_on_enums() {
+    let src = "
+        enum List { Cons(int, List), Nil }
+
+        funk sum(xs) {
+            match xs {
+                Cons(x, rest) => x + sum(rest),
+                Nil => 0,
+            }
+        }
+
+        funk main() {
+            sum(Cons(1, Cons(2, Cons(3, Nil))))
+        }
+    ";
+
+    assert_eq!(try_running_program(src).unwrap(), Value::Int(6));
+}
+
+#[test]
+fn try_returns_early_on_none_and_err() {
+    let src = "
+        let check = fn(x: int) -> result<int, string> {
+            if x > 0 { Ok(x) } else { Err(\"not positive\") }
+        };
+        let sum = fn(a, b) {
+            let a = check(a)?;
+            Ok(a + check(b)?)
+        };
+        [sum(1, 2), sum(1, -2), sum(-1, 2)]
+    ";
+
+    assert_eq!(
+        try_running(src).unwrap().to_string(),
+        "[Ok(3), Err(not positive), Err(not positive)]"
+    );
+
+    let src = "
+        let get = fn(xs: list<int>, i: int) {
+            if i < len(xs) { Some(xs[i]) } else { None }
+        };
+        let pair = fn(xs) { Some(get(xs, 0)? * 10 + get(xs, 1)?) };
+        [pair([1, 2]), pair([1])]
+    ";
+    assert_eq!(try_running(src).unwrap().to_string(), "[Some(12), None]");
+}
+
+#[test]
+fn tuples_can_be_returned_and_destructured() {
+    let src = "
+        funk divmod(a: int, b: int) -> (int, int) {
+            (a / b, a - a / b * b)
+        }
+
+        funk main() {
+            let (q, r) = divmod(17, 5);
+            let pair = (q, r);
+            let (x, y) = pair;
+            x * 10 + y
+        }
+    ";
+    assert_eq!(try_running_program(src).unwrap(), Value::Int(32));
+
+    let value = try_running("(1, \"one\", (true,))").unwrap();
+    assert_eq!(value.to_string(), "(1, one, (true,))");
+}
+
+#[test]
+fn floats_have_arithmetic_and_comparison() {
+    let src = "
+        let xs = [1.5, 2.5, 3.5];
+        let average = fold(xs, 0.0, fn(acc, x) { acc + x }) / float(len(xs));
+        (average, -average * 2e1, average > 2.4, 1.0 / 0.0, int(-average))
+    ";
+
+    assert_eq!(
+        try_running(src).unwrap().to_string(),
+        "(2.5, -50.0, true, inf, -2)"
+    );
+
+    let err = try_running("int(1e300)").unwrap_err();
+    assert!(matches!(err.kind, RuntimeErr::IntegerOverflow), "{err:?}");
+}
+
+#[test]
+fn integer_operators() {
+    let src = "(17 % 5, -17 % 5, 2 ** 10, 2 ** 3 ** 2, -2 ** 2, 6 & 3, 6 | 3, 6 ^ 3, 1 << 4, -16 >> 2, ~5, 1 + 2 * 3 % 4)";
+
+    assert_eq!(
+        try_running(src).unwrap().to_string(),
+        "(2, -2, 1024, 512, -4, 2, 7, 5, 16, -4, -6, 3)"
+    );
+    assert_eq!(
+        try_running("(7.5 % 2.0, 2.0 ** -1.0)").unwrap().to_string(),
+        "(1.5, 0.5)"
+    );
+
+    let src = "
+        funk collatz(n: int, steps: int) -> int {
+            if n == 1 {
+                steps
+            } else {
+                if n % 2 == 0 { collatz(n / 2, steps + 1) } else { collatz(n * 3 + 1, steps + 1) }
+            }
+        }
+
+        funk main() {
+            collatz(7, 0)
+        }
+    ";
+    assert_eq!(try_running_program(src).unwrap(), Value::Int(16));
+}
+
+#[test]
+fn integer_operators_are_checked() {
+    let err = try_running("5 % 0").unwrap_err();
+    assert!(matches!(err.kind, RuntimeErr::DivisionByZero), "{err:?}");
+
+    let err = try_running("2 ** -1").unwrap_err();
+    assert!(
+        matches!(err.kind, RuntimeErr::NegativeExponent(-1)),
+        "{err:?}"
+    );
+
+    let err = try_running("1 << 64").unwrap_err();
+    assert!(matches!(err.kind, RuntimeErr::InvalidShift(64)), "{err:?}");
+
+    let err = try_running("1 >> -1").unwrap_err();
+    assert!(matches!(err.kind, RuntimeErr::InvalidShift(-1)), "{err:?}");
+
+    let err = try_running("2 ** 63").unwrap_err();
+    assert!(matches!(err.kind, RuntimeErr::IntegerOverflow), "{err:?}");
+
+    let base = "let base = -2; ";
+    assert_eq!(
+        try_running(&format!("{base} base ** 63")).unwrap(),
+        Value::Int(i64::MIN)
+    );
+    assert_eq!(
+        try_running_with("2 ** 64", Overflow::Wrapping).unwrap(),
+        Value::Int(0)
+    );
+    assert_eq!(
+        try_running_with(&format!("{base} base ** 65"), Overflow::Saturating).unwrap(),
+        Value::Int(i64::MIN)
+    );
+    assert_eq!(
+        try_running("let one = -1; one ** 5000000001").unwrap(),
+        Value::Int(-1)
+    );
+}
+
+#[test]
+fn long_variant_chains_are_dropped() {
+    let src = "
+        enum L { Nil, Cons(int, L) }
+        struct Node { next: option<Node>, items: list<L> }
+
+        funk main() -> int {
+            let mut l = Nil;
+            let mut node = Node { next: None, items: [] };
+            let mut i = 0;
+            while i < 100000 {
+                l = Cons(i, l);
+                node = Node { next: Some(node), items: [Cons(i, Nil)] };
+                i = i + 1
+            };
+            i
+        }
+    ";
+
+    assert_eq!(try_running_program(src).unwrap(), Value::Int(100000));
+}
+
+#[test]
+fn fields_are_read_by_position() {
+    let ints = |range: std::ops::Range<i32>| range.map(|i| i.to_string()).collect::<Vec<_>>();
+    let constants = format!(
+        "let ints = [[{}], [{}]];",
+        ints(0..150).join(", "),
+        ints(150..300).join(", ")
+    );
+    let src = format!(
+        "
+        struct A {{ x: int, y: int }}
+        struct B {{ y: int, x: int }}
+        struct C {{ b: B, a: A }}
+
+        funk main() -> int {{
+            {constants}
+            let a = A {{ x: 1, y: 2 }};
+            let b = B {{ x: 30, y: 40 }};
+            let c = C {{ a: a, b: b }};
+            a.x + b.x + c.a.x
+        }}
+    "
+    );
+    let program = try_parsing_program(&src);
+    let mut checker = TypeChecker::new();
+    checker.check_program(&program).unwrap();
+
+    let mut chunky = Chunk::new();
+    let mut compiler = Compiler::new();
+    compiler.set_field_indices(checker.take_field_indices());
+    compiler.compile_program(&mut chunky, &program).unwrap();
+
+    assert_eq!(VM::new(chunky).run().unwrap(), Value::Int(32));
+
+    let err = Compiler::new()
+        .compile_program(&mut Chunk::new(), &program)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Can't tell where the field 'x' is without type checking."
+    );
+}
+
+#[test]
+fn variants_are_built_after_many_constants() {
+    let ints = |range: std::ops::Range<i32>| range.map(|i| i.to_string()).collect::<Vec<_>>();
+    let src = format!(
+        "
+        enum E {{ A(int), B }}
+        let ints = [[{}], [{}]];
+        let make = A;
+        match make(1) {{ A(n) => match A(n + 1) {{ A(m) => m, B => 0 }}, B => 0 }}
+    ",
+        ints(0..200).join(", "),
+        ints(200..400).join(", ")
+    );
+
+    assert_eq!(try_running(&src).unwrap(), Value::Int(2));
+}
+
+#[test]
+fn programs_cant_define_a_funk_twice() {
+    let program = try_parsing_program("funk main() { 1 } funk main() { 2 }");
+
+    let err = Compiler::new()
+        .compile_program(&mut Chunk::new(), &program)
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "'main' is defined more than once.");
+}